@@ -325,6 +325,49 @@ pub(crate) unsafe fn client_channel_disable(
     Ok(())
 }
 
+pub(crate) unsafe fn client_channel_get_statistics(
+    channel: *mut crate::ClientChannel,
+    visitor: ffi::StatisticsVisitor,
+) -> Result<(), ffi::ParamError> {
+    let channel = channel.as_ref().ok_or(ffi::ParamError::NullParameter)?;
+    let stats = channel.runtime.block_on(channel.inner.statistics())??;
+
+    visitor.on_channel(stats.reconnects);
+    for (id, unit) in stats.units.iter() {
+        visitor.on_unit(
+            id.value,
+            unit.requests,
+            unit.responses,
+            unit.timeouts,
+            unit.bad_frames,
+            unit.latency.max(),
+        );
+        for (ex, count) in unit.exceptions.iter() {
+            visitor.on_exception(id.value, (*ex).into(), *count);
+        }
+        for bucket in unit.latency.buckets() {
+            visitor.on_latency_bucket(
+                id.value,
+                bucket.upper_bound.unwrap_or_default(),
+                bucket.upper_bound.is_some(),
+                bucket.count,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) unsafe fn client_channel_reset_statistics(
+    channel: *mut crate::ClientChannel,
+) -> Result<(), ffi::ParamError> {
+    let channel = channel.as_ref().ok_or(ffi::ParamError::NullParameter)?;
+    channel
+        .runtime
+        .block_on(channel.inner.reset_statistics())??;
+    Ok(())
+}
+
 pub(crate) unsafe fn client_channel_set_decode_level(
     channel: *mut crate::ClientChannel,
     level: ffi::DecodeLevel,
@@ -336,6 +379,17 @@ pub(crate) unsafe fn client_channel_set_decode_level(
     Ok(())
 }
 
+pub(crate) unsafe fn client_channel_set_decode_format(
+    channel: *mut crate::ClientChannel,
+    format: ffi::DecodeFormat,
+) -> Result<(), ffi::ParamError> {
+    let channel = channel.as_mut().ok_or(ffi::ParamError::NullParameter)?;
+    channel
+        .runtime
+        .spawn(channel.inner.set_decode_format(format.into()))?;
+    Ok(())
+}
+
 impl From<ClientState> for ffi::ClientState {
     fn from(x: ClientState) -> Self {
         match x {
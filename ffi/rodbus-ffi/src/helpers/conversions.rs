@@ -28,6 +28,15 @@ impl From<ffi::DecodeLevel> for rodbus::DecodeLevel {
     }
 }
 
+impl From<ffi::DecodeFormat> for rodbus::DecodeFormat {
+    fn from(format: ffi::DecodeFormat) -> Self {
+        match format {
+            ffi::DecodeFormat::Text => rodbus::DecodeFormat::Text,
+            ffi::DecodeFormat::Structured => rodbus::DecodeFormat::Structured,
+        }
+    }
+}
+
 impl From<rodbus::RequestError> for ffi::RequestError {
     fn from(err: rodbus::RequestError) -> Self {
         match err {
@@ -543,6 +543,17 @@ pub(crate) unsafe fn server_set_decode_level(
     Ok(())
 }
 
+pub(crate) unsafe fn server_set_decode_format(
+    server: *mut crate::Server,
+    format: ffi::DecodeFormat,
+) -> Result<(), ffi::ParamError> {
+    let server = server.as_mut().ok_or(ffi::ParamError::NullParameter)?;
+    server
+        .runtime
+        .block_on(server.inner.set_decode_format(format.into()))??;
+    Ok(())
+}
+
 pub enum AddressFilter {
     Any,
     WildcardIpv4(WildcardIPv4),
@@ -225,6 +225,13 @@ pub(crate) fn build(lib: &mut LibraryBuilder, common: &CommonDefinitions) -> Bac
         .doc("Set the decoding level for the channel")?
         .build()?;
 
+    let set_decode_format_fn = lib
+        .define_method("set_decode_format", channel.clone())?
+        .param("format", common.decode_format.clone(), "Decoding format")?
+        .fails_with(common.error_type.clone())?
+        .doc("Set the format of the decoded data for the channel")?
+        .build()?;
+
     let enable_fn = lib
         .define_method("enable", channel.clone())?
         .fails_with(common.error_type.clone())?
@@ -243,6 +250,31 @@ pub(crate) fn build(lib: &mut LibraryBuilder, common: &CommonDefinitions) -> Bac
         )?
         .build()?;
 
+    let statistics_visitor = define_statistics_visitor(lib)?;
+
+    let get_statistics_fn = lib
+        .define_method("get_statistics", channel.clone())?
+        .param(
+            "visitor",
+            statistics_visitor,
+            "Visitor that receives the statistics",
+        )?
+        .fails_with(common.error_type.clone())?
+        .doc(
+            doc("Retrieve a snapshot of the communication statistics of the channel")
+                .warning("May not be called from within the context of the runtime"),
+        )?
+        .build()?;
+
+    let reset_statistics_fn = lib
+        .define_method("reset_statistics", channel.clone())?
+        .fails_with(common.error_type.clone())?
+        .doc(
+            doc("Reset the communication statistics of the channel to zero")
+                .warning("May not be called from within the context of the runtime"),
+        )?
+        .build()?;
+
     lib.define_class(&channel)?
         // abstract factory methods
         .static_method(tcp_client_create_fn)?
@@ -253,6 +285,10 @@ pub(crate) fn build(lib: &mut LibraryBuilder, common: &CommonDefinitions) -> Bac
         .method(disable_fn)?
         // setting methods
         .method(set_decode_level_fn)?
+        .method(set_decode_format_fn)?
+        // statistics
+        .method(get_statistics_fn)?
+        .method(reset_statistics_fn)?
         // read methods
         .async_method(read_coils_method)?
         .async_method(read_discrete_inputs_method)?
@@ -275,6 +311,88 @@ pub(crate) fn build(lib: &mut LibraryBuilder, common: &CommonDefinitions) -> Bac
     Ok(())
 }
 
+fn define_statistics_visitor(lib: &mut LibraryBuilder) -> BackTraced<SynchronousInterface> {
+    let visitor = lib
+        .define_interface(
+            "statistics_visitor",
+            "Callback interface used to visit a snapshot of the channel statistics",
+        )?
+        .begin_callback("on_channel", "Called once with the channel-wide statistics")?
+        .param(
+            "reconnects",
+            Primitive::U64,
+            "Number of connections or port openings after the first one",
+        )?
+        .end_callback()?
+        .begin_callback(
+            "on_unit",
+            "Called for every unit id the channel communicated with",
+        )?
+        .param("unit_id", Primitive::U8, "Unit id")?
+        .param(
+            "requests",
+            Primitive::U64,
+            "Number of requests sent, including retries",
+        )?
+        .param(
+            "responses",
+            Primitive::U64,
+            "Number of responses received, including exception responses",
+        )?
+        .param(
+            "timeouts",
+            Primitive::U64,
+            "Number of requests that timed out",
+        )?
+        .param(
+            "bad_frames",
+            Primitive::U64,
+            "Number of malformed frames received while waiting for a response",
+        )?
+        .param(
+            "max_latency",
+            DurationType::Milliseconds,
+            "Longest round-trip time",
+        )?
+        .end_callback()?
+        .begin_callback(
+            "on_exception",
+            "Called for every exception code received from a unit",
+        )?
+        .param("unit_id", Primitive::U8, "Unit id")?
+        .param("exception", Primitive::U8, "Raw Modbus exception code")?
+        .param(
+            "count",
+            Primitive::U64,
+            "Number of exception responses with this code",
+        )?
+        .end_callback()?
+        .begin_callback(
+            "on_latency_bucket",
+            "Called for every bucket of the round-trip time histogram of a unit",
+        )?
+        .param("unit_id", Primitive::U8, "Unit id")?
+        .param(
+            "upper_bound",
+            DurationType::Milliseconds,
+            "Inclusive upper bound of the bucket",
+        )?
+        .param(
+            "is_bounded",
+            Primitive::Bool,
+            "False for the last bucket, which has no upper bound",
+        )?
+        .param(
+            "count",
+            Primitive::U64,
+            "Number of responses that fell into the bucket",
+        )?
+        .end_callback()?
+        .build_sync()?;
+
+    Ok(visitor)
+}
+
 fn define_port_state_listener(lib: &mut LibraryBuilder) -> BackTraced<AsynchronousInterface> {
     let port_state = lib
         .define_enum("port_state")?
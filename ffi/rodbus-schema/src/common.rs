@@ -4,6 +4,7 @@ pub(crate) struct CommonDefinitions {
     pub(crate) error_type: ErrorTypeHandle,
     pub(crate) nothing: EnumHandle,
     pub(crate) decode_level: UniversalStructHandle,
+    pub(crate) decode_format: EnumHandle,
     pub(crate) runtime_handle: ClassDeclarationHandle,
     pub(crate) error_info: ErrorTypeHandle,
     pub(crate) address_range: UniversalStructHandle,
@@ -24,6 +25,7 @@ impl CommonDefinitions {
         let error_type = build_error_type(lib)?;
         let nothing = build_nothing_type(lib)?;
         let decode_level = crate::decoding::define(lib)?;
+        let decode_format = crate::decoding::define_format(lib)?;
         let bit_value = build_bit_value(lib)?;
         let register_value = build_register_value(lib)?;
 
@@ -31,6 +33,7 @@ impl CommonDefinitions {
             error_type: error_type.clone(),
             nothing,
             decode_level,
+            decode_format,
             runtime_handle: sfio_tokio_ffi::define(lib, error_type)?,
             error_info: build_request_error(lib)?,
             address_range: build_address_range(lib)?,
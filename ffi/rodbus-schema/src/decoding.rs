@@ -65,3 +65,17 @@ pub(crate) fn define(lib: &mut LibraryBuilder) -> BackTraced<UniversalStructHand
 
     Ok(decode_level_struct)
 }
+
+pub(crate) fn define_format(lib: &mut LibraryBuilder) -> BackTraced<EnumHandle> {
+    let decode_format_enum = lib
+        .define_enum("decode_format")?
+        .push("text", "Emit human-readable messages with multi-line hex dumps")?
+        .push("structured", "Emit a single event per message with the decoded data as structured fields")?
+        .doc(
+            doc("Controls how decoded data is emitted at each layer")
+                .details("Fields are only present if enabled by the decode level of the layer. Raw bytes are formatted as a single line of space separated hex.")
+        )?
+        .build()?;
+
+    Ok(decode_format_enum)
+}
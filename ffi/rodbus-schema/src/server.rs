@@ -188,6 +188,13 @@ pub(crate) fn build_server(
         .doc("Set the decoding level for the server")?
         .build()?;
 
+    let set_decode_format_fn = lib
+        .define_method("set_decode_format", server.clone())?
+        .param("format", common.decode_format.clone(), "Decoding format")?
+        .fails_with(common.error_type.clone())?
+        .doc("Set the format of the decoded data for the server")?
+        .build()?;
+
     let server = lib.define_class(&server)?
         .static_method(tcp_constructor)?
         .static_method(rtu_constructor)?
@@ -195,6 +202,7 @@ pub(crate) fn build_server(
         .static_method(tls_constructor_raw)?
         .method(update_fn)?
         .method(set_decode_level_fn)?
+        .method(set_decode_format_fn)?
         .destructor(destructor)?
         .custom_destroy("shutdown")?
         .doc("Handle to the running server. The server runs on a background task until this class is destroyed.")?
@@ -0,0 +1,507 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use rodbus::decoder::*;
+use rodbus::{ExceptionCode, ETHERNET_LINK_TYPE};
+use serde_json::{json, Value};
+
+use crate::Error;
+
+/// Source of the bytes to decode
+pub(crate) enum Input {
+    Hex(String),
+    Capture(String),
+}
+
+pub(crate) struct DecodeArgs {
+    pub(crate) input: Input,
+    pub(crate) framing: Framing,
+    pub(crate) message: MessageType,
+    pub(crate) server_port: u16,
+    pub(crate) json: bool,
+}
+
+pub(crate) fn run(args: &DecodeArgs) -> Result<(), Error> {
+    match &args.input {
+        Input::Hex(hex) => {
+            let mut decoder = FrameDecoder::new(args.framing, args.message);
+            decoder.push(&parse_hex(hex)?);
+            while let Some(frame) = decoder.next_frame() {
+                print_frame(args, None, frame);
+            }
+            if decoder.remaining() > 0 {
+                println!("{} trailing bytes", decoder.remaining());
+            }
+        }
+        Input::Capture(path) => {
+            let bytes = std::fs::read(path)?;
+            let mut streams = HashMap::new();
+            for packet in read_capture(&bytes)? {
+                decode_packet(args, &mut streams, packet);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_hex(hex: &str) -> Result<Vec<u8>, Error> {
+    let digits: Vec<char> = hex
+        .trim_start_matches("0x")
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ':' && *c != '-')
+        .collect();
+
+    let chunks = digits.chunks_exact(2);
+    if !chunks.remainder().is_empty() {
+        return Err(Error::BadHex);
+    }
+
+    chunks
+        .map(|x| {
+            let byte: String = x.iter().collect();
+            u8::from_str_radix(&byte, 16).map_err(|_| Error::BadHex)
+        })
+        .collect()
+}
+
+struct Packet<'a> {
+    timestamp_micros: u64,
+    link: u16,
+    data: &'a [u8],
+}
+
+struct Segment<'a> {
+    source: SocketAddr,
+    destination: SocketAddr,
+    payload: &'a [u8],
+}
+
+fn decode_packet(
+    args: &DecodeArgs,
+    streams: &mut HashMap<(SocketAddr, SocketAddr), FrameDecoder>,
+    packet: Packet<'_>,
+) {
+    let context = Context {
+        timestamp_micros: packet.timestamp_micros,
+        endpoints: None,
+    };
+
+    match packet.link {
+        ETHERNET_LINK_TYPE => {
+            let segment = match parse_ethernet(packet.data) {
+                Some(x) if !x.payload.is_empty() => x,
+                _ => return,
+            };
+            let message = if segment.destination.port() == args.server_port {
+                MessageType::Request
+            } else {
+                MessageType::Response
+            };
+            let decoder = streams
+                .entry((segment.source, segment.destination))
+                .or_insert_with(|| FrameDecoder::new(Framing::Tcp, message));
+            decoder.push(segment.payload);
+            let context = Context {
+                endpoints: Some((segment.source, segment.destination)),
+                ..context
+            };
+            while let Some(frame) = decoder.next_frame() {
+                print_frame(args, Some(&context), frame);
+            }
+        }
+        #[cfg(feature = "serial")]
+        rodbus::RTU_LINK_TYPE => {
+            // the direction of serial frames is unknown, so try both
+            let frame = decode_frame(Framing::Rtu, MessageType::Request, packet.data)
+                .or_else(|_| decode_frame(Framing::Rtu, MessageType::Response, packet.data));
+            print_frame(args, Some(&context), frame);
+        }
+        link => {
+            eprintln!("skipping packet with unsupported link type {link}");
+        }
+    }
+}
+
+struct Context {
+    timestamp_micros: u64,
+    endpoints: Option<(SocketAddr, SocketAddr)>,
+}
+
+fn print_frame(
+    args: &DecodeArgs,
+    context: Option<&Context>,
+    frame: Result<DecodedFrame, rodbus::RequestError>,
+) {
+    if args.json {
+        let mut value = match &frame {
+            Ok(frame) => frame_to_json(frame),
+            Err(err) => json!({ "error": err.to_string() }),
+        };
+        if let Some(context) = context {
+            value["timestamp_us"] = json!(context.timestamp_micros);
+            if let Some((source, destination)) = context.endpoints {
+                value["source"] = json!(source.to_string());
+                value["destination"] = json!(destination.to_string());
+            }
+        }
+        println!("{value}");
+        return;
+    }
+
+    if let Some(context) = context {
+        let seconds = context.timestamp_micros / 1_000_000;
+        let micros = context.timestamp_micros % 1_000_000;
+        print!("{seconds}.{micros:06} ");
+        if let Some((source, destination)) = context.endpoints {
+            print!("{source} -> {destination} ");
+        }
+    }
+
+    match frame {
+        Ok(frame) => println!("{frame}"),
+        Err(err) => println!("error: {err}"),
+    }
+}
+
+fn frame_to_json(frame: &DecodedFrame) -> Value {
+    let mut value = json!({
+        "unit_id": frame.unit_id.value,
+        "function": frame.pdu.function(),
+    });
+    if let Some(tx_id) = frame.tx_id {
+        value["tx_id"] = json!(tx_id);
+    }
+
+    let indexed = |index: u16, value: Value| json!({ "index": index, "value": value });
+    let range =
+        |range: &rodbus::AddressRange| json!({ "start": range.start, "count": range.count });
+
+    let (kind, details) = match &frame.pdu {
+        Pdu::Request(request) => {
+            let details = match request {
+                RequestPdu::ReadCoils(x)
+                | RequestPdu::ReadDiscreteInputs(x)
+                | RequestPdu::ReadHoldingRegisters(x)
+                | RequestPdu::ReadInputRegisters(x) => json!({ "range": range(x) }),
+                RequestPdu::WriteSingleCoil(x) => indexed(x.index, json!(x.value)),
+                RequestPdu::WriteSingleRegister(x) => indexed(x.index, json!(x.value)),
+                RequestPdu::WriteMultipleCoils(x) => {
+                    json!({ "values": x.iter().map(|x| indexed(x.index, json!(x.value))).collect::<Vec<_>>() })
+                }
+                RequestPdu::WriteMultipleRegisters(x) => {
+                    json!({ "values": x.iter().map(|x| indexed(x.index, json!(x.value))).collect::<Vec<_>>() })
+                }
+            };
+            ("request", details)
+        }
+        Pdu::Response(response) => {
+            let details = match response {
+                ResponsePdu::ReadCoils(x) | ResponsePdu::ReadDiscreteInputs(x) => {
+                    json!({ "values": x })
+                }
+                ResponsePdu::ReadHoldingRegisters(x) | ResponsePdu::ReadInputRegisters(x) => {
+                    json!({ "values": x })
+                }
+                ResponsePdu::WriteSingleCoil(x) => indexed(x.index, json!(x.value)),
+                ResponsePdu::WriteSingleRegister(x) => indexed(x.index, json!(x.value)),
+                ResponsePdu::WriteMultipleCoils(x) | ResponsePdu::WriteMultipleRegisters(x) => {
+                    json!({ "range": range(x) })
+                }
+            };
+            ("response", details)
+        }
+        Pdu::Exception { exception, .. } => (
+            "exception",
+            json!({ "exception": u8::from(*exception), "description": exception_name(*exception) }),
+        ),
+        Pdu::Raw { data, .. } => ("raw", json!({ "data": data })),
+    };
+
+    value["type"] = json!(kind);
+    value["pdu"] = details;
+    value
+}
+
+fn exception_name(ex: ExceptionCode) -> String {
+    match ex {
+        ExceptionCode::Unknown(x) => format!("unknown ({x})"),
+        _ => format!("{ex:?}"),
+    }
+}
+
+fn read_u16(bytes: &[u8], pos: usize, big_endian: bool) -> Option<u16> {
+    let x: [u8; 2] = bytes.get(pos..pos + 2)?.try_into().ok()?;
+    Some(if big_endian {
+        u16::from_be_bytes(x)
+    } else {
+        u16::from_le_bytes(x)
+    })
+}
+
+fn read_u32(bytes: &[u8], pos: usize, big_endian: bool) -> Option<u32> {
+    let x: [u8; 4] = bytes.get(pos..pos + 4)?.try_into().ok()?;
+    Some(if big_endian {
+        u32::from_be_bytes(x)
+    } else {
+        u32::from_le_bytes(x)
+    })
+}
+
+/// Read the packets of a pcap or pcapng file
+fn read_capture(bytes: &[u8]) -> Result<Vec<Packet<'_>>, Error> {
+    match read_u32(bytes, 0, false) {
+        Some(0x0A0D_0D0A) => read_pcapng(bytes),
+        Some(_) => read_pcap(bytes),
+        None => Err(Error::BadCapture("file is too short")),
+    }
+}
+
+fn read_pcap(bytes: &[u8]) -> Result<Vec<Packet<'_>>, Error> {
+    let magic = read_u32(bytes, 0, false).ok_or(Error::BadCapture("missing header"))?;
+    let (big_endian, nanos) = match magic {
+        0xA1B2_C3D4 => (false, false),
+        0xD4C3_B2A1 => (true, false),
+        0xA1B2_3C4D => (false, true),
+        0x4D3C_B2A1 => (true, true),
+        _ => return Err(Error::BadCapture("unknown file format")),
+    };
+    // the upper bits of the link type field hold the FCS length, if any
+    let link = read_u32(bytes, 20, big_endian).ok_or(Error::BadCapture("missing header"))? as u16;
+
+    let truncated = || Error::BadCapture("truncated packet record");
+    let mut packets = Vec::new();
+    let mut pos = 24;
+    while pos < bytes.len() {
+        let seconds = read_u32(bytes, pos, big_endian).ok_or_else(truncated)? as u64;
+        let fraction = read_u32(bytes, pos + 4, big_endian).ok_or_else(truncated)? as u64;
+        let length = read_u32(bytes, pos + 8, big_endian).ok_or_else(truncated)? as usize;
+        let data = bytes
+            .get(pos + 16..pos + 16 + length)
+            .ok_or_else(truncated)?;
+        let micros = if nanos { fraction / 1000 } else { fraction };
+        packets.push(Packet {
+            timestamp_micros: seconds * 1_000_000 + micros,
+            link,
+            data,
+        });
+        pos += 16 + length;
+    }
+    Ok(packets)
+}
+
+fn read_pcapng(bytes: &[u8]) -> Result<Vec<Packet<'_>>, Error> {
+    let truncated = || Error::BadCapture("truncated block");
+    let big_endian = match read_u32(bytes, 8, false) {
+        Some(0x1A2B_3C4D) => false,
+        Some(0x4D3C_2B1A) => true,
+        _ => return Err(Error::BadCapture("unknown byte order")),
+    };
+
+    let mut links = Vec::new();
+    let mut packets = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let kind = read_u32(bytes, pos, big_endian).ok_or_else(truncated)?;
+        let length = read_u32(bytes, pos + 4, big_endian).ok_or_else(truncated)? as usize;
+        if length < 12 {
+            return Err(Error::BadCapture("invalid block length"));
+        }
+        let block = bytes.get(pos..pos + length).ok_or_else(truncated)?;
+
+        match kind {
+            // interface description
+            1 => links.push(read_u16(block, 8, big_endian).ok_or_else(truncated)?),
+            // enhanced packet, timestamps use the default resolution of microseconds
+            6 => {
+                let interface = read_u32(block, 8, big_endian).ok_or_else(truncated)? as usize;
+                let high = read_u32(block, 12, big_endian).ok_or_else(truncated)? as u64;
+                let low = read_u32(block, 16, big_endian).ok_or_else(truncated)? as u64;
+                let captured = read_u32(block, 20, big_endian).ok_or_else(truncated)? as usize;
+                let data = block.get(28..28 + captured).ok_or_else(truncated)?;
+                let link = *links
+                    .get(interface)
+                    .ok_or(Error::BadCapture("unknown interface"))?;
+                packets.push(Packet {
+                    timestamp_micros: (high << 32) | low,
+                    link,
+                    data,
+                });
+            }
+            _ => {}
+        }
+        pos += length;
+    }
+    Ok(packets)
+}
+
+/// Extract the TCP payload of an Ethernet frame
+fn parse_ethernet(frame: &[u8]) -> Option<Segment<'_>> {
+    let ether_type = read_u16(frame, 12, true)?;
+    let ip = frame.get(14..)?;
+    let (source, destination, tcp) = match ether_type {
+        0x0800 => {
+            let header_length = ((ip.first()? & 0x0F) as usize) * 4;
+            let total_length = read_u16(ip, 2, true)? as usize;
+            if *ip.get(9)? != 6 {
+                return None;
+            }
+            let source: [u8; 4] = ip.get(12..16)?.try_into().ok()?;
+            let destination: [u8; 4] = ip.get(16..20)?.try_into().ok()?;
+            (
+                IpAddr::V4(Ipv4Addr::from(source)),
+                IpAddr::V4(Ipv4Addr::from(destination)),
+                ip.get(header_length..total_length)?,
+            )
+        }
+        0x86DD => {
+            let payload_length = read_u16(ip, 4, true)? as usize;
+            if *ip.get(6)? != 6 {
+                return None;
+            }
+            let source: [u8; 16] = ip.get(8..24)?.try_into().ok()?;
+            let destination: [u8; 16] = ip.get(24..40)?.try_into().ok()?;
+            (
+                IpAddr::V6(Ipv6Addr::from(source)),
+                IpAddr::V6(Ipv6Addr::from(destination)),
+                ip.get(40..40 + payload_length)?,
+            )
+        }
+        _ => return None,
+    };
+
+    let data_offset = ((tcp.get(12)? >> 4) as usize) * 4;
+    Some(Segment {
+        source: SocketAddr::new(source, read_u16(tcp, 0, true)?),
+        destination: SocketAddr::new(destination, read_u16(tcp, 2, true)?),
+        payload: tcp.get(data_offset..)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodbus::{
+        CaptureConfig, CaptureFormat, CaptureWriter, Direction, Endpoints, TrafficEvent,
+        TrafficObserver, UnitId, RTU_LINK_TYPE,
+    };
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    const REQUEST: &[u8] = &[
+        0x00, 0x07, 0x00, 0x00, 0x00, 0x06, 0x01, 0x03, 0x00, 0x00, 0x00, 0x01,
+    ];
+    const RESPONSE: &[u8] = &[
+        0x00, 0x07, 0x00, 0x00, 0x00, 0x05, 0x01, 0x03, 0x02, 0xCA, 0xFE,
+    ];
+    const RTU_REQUEST: &[u8] = &[0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0A];
+
+    fn event(
+        endpoints: Option<Endpoints>,
+        direction: Direction,
+        micros: u64,
+        bytes: &[u8],
+    ) -> TrafficEvent<'_> {
+        TrafficEvent {
+            timestamp: UNIX_EPOCH + Duration::from_micros(micros),
+            direction,
+            session: 0,
+            endpoints,
+            unit_id: Some(UnitId::new(1)),
+            function: Some(0x03),
+            bytes,
+        }
+    }
+
+    /// write the events with a capture writer and read the file once it holds every packet
+    fn capture(name: &str, format: CaptureFormat, events: &[TrafficEvent]) -> Vec<u8> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        let writer = CaptureWriter::start(CaptureConfig::new(&path, format)).unwrap();
+        for event in events {
+            writer.on_frame(*event);
+        }
+
+        let deadline = SystemTime::now() + Duration::from_secs(5);
+        loop {
+            let bytes = std::fs::read(&path).unwrap();
+            if matches!(read_capture(&bytes), Ok(packets) if packets.len() == events.len()) {
+                return bytes;
+            }
+            assert!(SystemTime::now() < deadline, "capture was not written");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn assert_segment(packet: &Packet, source: &str, destination: &str, payload: &[u8]) {
+        assert_eq!(packet.link, ETHERNET_LINK_TYPE);
+        let segment = parse_ethernet(packet.data).unwrap();
+        assert_eq!(segment.source, source.parse().unwrap());
+        assert_eq!(segment.destination, destination.parse().unwrap());
+        assert_eq!(segment.payload, payload);
+    }
+
+    #[test]
+    fn reads_tcp_frames_written_to_pcap() {
+        let endpoints = Endpoints {
+            local: "127.0.0.1:40000".parse().unwrap(),
+            remote: "127.0.0.2:502".parse().unwrap(),
+        };
+        let bytes = capture(
+            "tcp.pcap",
+            CaptureFormat::Pcap,
+            &[
+                event(Some(endpoints), Direction::Tx, 1_500_000, REQUEST),
+                event(Some(endpoints), Direction::Rx, 1_600_000, RESPONSE),
+            ],
+        );
+
+        let packets = read_pcap(&bytes).unwrap();
+        assert_eq!(packets[0].timestamp_micros, 1_500_000);
+        assert_eq!(packets[1].timestamp_micros, 1_600_000);
+        assert_segment(&packets[0], "127.0.0.1:40000", "127.0.0.2:502", REQUEST);
+        assert_segment(&packets[1], "127.0.0.2:502", "127.0.0.1:40000", RESPONSE);
+    }
+
+    #[test]
+    fn reads_tcp_and_rtu_frames_written_to_pcapng() {
+        let endpoints = Endpoints {
+            local: "[::1]:40000".parse().unwrap(),
+            remote: "[::2]:502".parse().unwrap(),
+        };
+        let bytes = capture(
+            "mixed.pcapng",
+            CaptureFormat::PcapNg,
+            &[
+                event(Some(endpoints), Direction::Rx, 1_500_000, REQUEST),
+                event(None, Direction::Tx, 1_600_000, RTU_REQUEST),
+            ],
+        );
+
+        let packets = read_pcapng(&bytes).unwrap();
+        assert_eq!(packets[0].timestamp_micros, 1_500_000);
+        assert_segment(&packets[0], "[::2]:502", "[::1]:40000", REQUEST);
+        assert_eq!(packets[1].timestamp_micros, 1_600_000);
+        assert_eq!(packets[1].link, RTU_LINK_TYPE);
+        assert_eq!(packets[1].data, RTU_REQUEST);
+    }
+
+    #[test]
+    fn rejects_truncated_captures() {
+        let endpoints = Endpoints {
+            local: "127.0.0.1:40000".parse().unwrap(),
+            remote: "127.0.0.2:502".parse().unwrap(),
+        };
+        let events = [event(Some(endpoints), Direction::Tx, 1_500_000, REQUEST)];
+        let pcap = capture("truncated.pcap", CaptureFormat::Pcap, &events);
+        let pcapng = capture("truncated.pcapng", CaptureFormat::PcapNg, &events);
+
+        assert!(read_capture(&pcap[..3]).is_err());
+        assert!(read_pcap(&pcap[..20]).is_err());
+        assert!(read_pcap(&pcap[..pcap.len() - 1]).is_err());
+        assert!(read_pcapng(&pcapng[..pcapng.len() - 1]).is_err());
+
+        let packet = &read_pcap(&pcap).unwrap()[0];
+        assert!(parse_ethernet(&packet.data[..13]).is_none());
+        assert!(parse_ethernet(&packet.data[..30]).is_none());
+        assert!(parse_ethernet(&packet.data[..packet.data.len() - 1]).is_none());
+    }
+}
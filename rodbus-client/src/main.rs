@@ -10,6 +10,10 @@ use rodbus::client::*;
 use rodbus::*;
 use rodbus::{InvalidRange, InvalidRequest, Shutdown};
 
+use crate::decode::{DecodeArgs, Input};
+
+mod decode;
+
 #[derive(Debug)]
 enum Error {
     BadRange(InvalidRange),
@@ -18,6 +22,10 @@ enum Error {
     BadBool(std::str::ParseBoolError),
     BadCharInBitString(char),
     Request(rodbus::RequestError),
+    BadHex,
+    BadCapture(&'static str),
+    BadArgument(&'static str),
+    Io(std::io::Error),
     MissingSubCommand,
     Shutdown,
 }
@@ -33,6 +41,12 @@ enum Command {
     WriteMultipleRegisters(WriteMultiple<u16>),
 }
 
+/// Decoding doesn't connect to a server, so it's not a [`Command`]
+enum Mode {
+    Request(Args),
+    Decode(DecodeArgs),
+}
+
 struct Args {
     address: SocketAddr,
     id: UnitId,
@@ -67,7 +81,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 async fn run() -> Result<(), Error> {
-    let args = parse_args()?;
+    let args = match parse_args()? {
+        Mode::Request(args) => args,
+        Mode::Decode(args) => return decode::run(&args),
+    };
+
     let mut channel = spawn_tcp_client_task(
         HostAddr::ip(args.address.ip(), args.address.port()),
         1,
@@ -232,7 +250,37 @@ fn get_command(matches: &ArgMatches) -> Result<Command, Error> {
     Err(Error::MissingSubCommand)
 }
 
-fn parse_args() -> Result<Args, Error> {
+fn get_decode_args(arg: &ArgMatches) -> Result<DecodeArgs, Error> {
+    let input = match (arg.value_of("hex"), arg.value_of("pcap")) {
+        (Some(hex), None) => Input::Hex(hex.to_string()),
+        (None, Some(path)) => Input::Capture(path.to_string()),
+        _ => return Err(Error::BadArgument("specify exactly one of --hex or --pcap")),
+    };
+    let framing = match arg.value_of("framing").unwrap() {
+        #[cfg(feature = "serial")]
+        "rtu" => rodbus::decoder::Framing::Rtu,
+        #[cfg(not(feature = "serial"))]
+        "rtu" => {
+            return Err(Error::BadArgument(
+                "RTU framing requires the serial feature",
+            ))
+        }
+        _ => rodbus::decoder::Framing::Tcp,
+    };
+    let message = match arg.value_of("type").unwrap() {
+        "response" => rodbus::decoder::MessageType::Response,
+        _ => rodbus::decoder::MessageType::Request,
+    };
+    Ok(DecodeArgs {
+        input,
+        framing,
+        message,
+        server_port: u16::from_str(arg.value_of("port").unwrap())?,
+        json: arg.is_present("json"),
+    })
+}
+
+fn parse_args() -> Result<Mode, Error> {
     let matches = App::new("Modbus Client Console")
         .version("0.1.0")
         .about("Simple program to show off client API")
@@ -422,8 +470,60 @@ fn parse_args() -> Result<Args, Error> {
                         .help("the values of the registers specified as a comma delimited list (e.g. 1,4,7)"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("decode")
+                .about("decode frames from a hex string or a pcap/pcapng file without connecting")
+                .arg(
+                    Arg::with_name("hex")
+                        .short("x")
+                        .long("hex")
+                        .takes_value(true)
+                        .help("the frame(s) to decode as hex (e.g. \"00 01 00 00 00 06 01 03 00 00 00 0A\")"),
+                )
+                .arg(
+                    Arg::with_name("pcap")
+                        .long("pcap")
+                        .takes_value(true)
+                        .help("a pcap or pcapng capture to decode"),
+                )
+                .arg(
+                    Arg::with_name("framing")
+                        .short("f")
+                        .long("framing")
+                        .takes_value(true)
+                        .possible_values(&["tcp", "rtu"])
+                        .default_value("tcp")
+                        .help("the framing of hex input"),
+                )
+                .arg(
+                    Arg::with_name("type")
+                        .short("t")
+                        .long("type")
+                        .takes_value(true)
+                        .possible_values(&["request", "response"])
+                        .default_value("request")
+                        .help("whether hex input is a request or a response"),
+                )
+                .arg(
+                    Arg::with_name("port")
+                        .long("port")
+                        .takes_value(true)
+                        .default_value("502")
+                        .help("TCP port of the server in a capture, used to tell requests from responses"),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .short("j")
+                        .long("json")
+                        .help("print one JSON object per frame"),
+                ),
+        )
         .get_matches();
 
+    if let Some(matches) = matches.subcommand_matches("decode") {
+        return Ok(Mode::Decode(get_decode_args(matches)?));
+    }
+
     let address = SocketAddr::from_str(matches.value_of("host").unwrap())?;
     let id = UnitId::new(u8::from_str(matches.value_of("id").unwrap())?);
     let period = match matches.value_of("period") {
@@ -432,7 +532,7 @@ fn parse_args() -> Result<Args, Error> {
     };
     let command = get_command(&matches)?;
 
-    Ok(Args::new(address, id, command, period))
+    Ok(Mode::Request(Args::new(address, id, command, period)))
 }
 
 impl std::error::Error for Error {}
@@ -446,6 +546,10 @@ impl std::fmt::Display for Error {
             Error::BadBool(err) => err.fmt(f),
             Error::BadCharInBitString(char) => write!(f, "Bad character in bit string: {char}"),
             Error::Request(err) => err.fmt(f),
+            Error::BadHex => f.write_str("Bad hex string"),
+            Error::BadCapture(reason) => write!(f, "Bad capture file: {reason}"),
+            Error::BadArgument(reason) => f.write_str(reason),
+            Error::Io(err) => err.fmt(f),
             Error::MissingSubCommand => f.write_str("No sub-command provided"),
             Error::Shutdown => f.write_str("channel was shut down"),
         }
@@ -458,6 +562,12 @@ impl From<rodbus::RequestError> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
 impl From<AddrParseError> for Error {
     fn from(err: AddrParseError) -> Self {
         Error::BadAddr(err)
@@ -0,0 +1,690 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::net::{IpAddr, Ipv6Addr};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::observer::{Direction, Endpoints, TrafficEvent, TrafficObserver};
+
+/// Link type of synthesized Ethernet frames (`LINKTYPE_ETHERNET`)
+pub const ETHERNET_LINK_TYPE: u16 = 1;
+/// Link type of raw RTU frames (`LINKTYPE_USER0`)
+pub const RTU_LINK_TYPE: u16 = 147;
+
+const SNAP_LENGTH: u32 = 65535;
+// sessions are not tracked, so the least recently used flows are forgotten beyond this number
+const MAX_FLOWS: usize = 256;
+const LOCAL_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+const REMOTE_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x02];
+
+/// File format of a capture
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CaptureFormat {
+    /// libpcap format
+    ///
+    /// A pcap file has a single link type, so a file only contains TCP or RTU frames
+    /// depending on which type is captured first. Frames of the other type are discarded.
+    Pcap,
+    /// pcapng format which can contain both TCP and RTU frames
+    PcapNg,
+}
+
+/// Settings of a [`CaptureWriter`]
+#[derive(Clone, Debug)]
+pub struct CaptureConfig {
+    /// Path of the first capture file
+    ///
+    /// When files are rotated, a sequence number is inserted before the extension,
+    /// e.g. `modbus.pcap`, `modbus.1.pcap`, `modbus.2.pcap`.
+    pub path: PathBuf,
+    /// File format
+    pub format: CaptureFormat,
+    /// Start a new file when the current file reaches this size in bytes
+    pub max_file_size: Option<u64>,
+    /// Start a new file when the current file was opened longer ago than this duration
+    pub max_file_age: Option<Duration>,
+    /// Number of frames that can wait for the writer thread before new frames are dropped
+    pub queue_size: usize,
+}
+
+impl CaptureConfig {
+    /// Create a configuration without file rotation
+    pub fn new(path: impl Into<PathBuf>, format: CaptureFormat) -> Self {
+        Self {
+            path: path.into(),
+            format,
+            max_file_size: None,
+            max_file_age: None,
+            queue_size: 1024,
+        }
+    }
+
+    /// Rotate files when they reach `size` bytes
+    pub fn max_file_size(self, size: u64) -> Self {
+        Self {
+            max_file_size: Some(size),
+            ..self
+        }
+    }
+
+    /// Rotate files when they are older than `age`
+    pub fn max_file_age(self, age: Duration) -> Self {
+        Self {
+            max_file_age: Some(age),
+            ..self
+        }
+    }
+
+    fn file_path(&self, index: u32) -> PathBuf {
+        if index == 0 {
+            return self.path.clone();
+        }
+
+        let mut name = self.path.file_stem().unwrap_or_default().to_os_string();
+        name.push(format!(".{index}"));
+        if let Some(ext) = self.path.extension() {
+            name.push(".");
+            name.push(ext);
+        }
+        self.path.with_file_name(name)
+    }
+}
+
+/// [`TrafficObserver`] that writes every transmitted and received frame to pcap or pcapng files
+///
+/// TCP and TLS frames are wrapped in synthesized Ethernet, IP and TCP headers using the real
+/// addresses of the session. RTU frames are written as is with [`RTU_LINK_TYPE`].
+///
+/// Files are written by a dedicated thread so that sessions never wait on disk I/O.
+/// If the thread falls behind by more than [`CaptureConfig::queue_size`] frames, new frames
+/// are dropped and counted in [`CaptureWriter::dropped`].
+///
+/// A channel or server holds a single [`TrafficObserver`]. To capture traffic while also
+/// observing it, install a `Vec<Arc<dyn TrafficObserver>>` holding the writer and the
+/// other observers.
+pub struct CaptureWriter {
+    tx: SyncSender<Record>,
+    dropped: AtomicU64,
+}
+
+impl CaptureWriter {
+    /// Create the first capture file and start the writer thread
+    ///
+    /// The thread stops once the writer is dropped, i.e. removed from all the channels and servers.
+    pub fn start(config: CaptureConfig) -> std::io::Result<Self> {
+        let writer = Writer::new(config)?;
+        let (tx, rx) = std::sync::mpsc::sync_channel(writer.config.queue_size);
+        std::thread::Builder::new()
+            .name("rodbus-capture".to_string())
+            .spawn(move || writer.run(rx))?;
+        Ok(Self {
+            tx,
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    /// Number of frames dropped because the writer thread could not keep up
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl TrafficObserver for CaptureWriter {
+    fn on_frame(&self, event: TrafficEvent) {
+        let record = Record {
+            timestamp: event.timestamp,
+            direction: event.direction,
+            endpoints: event.endpoints,
+            bytes: event.bytes.to_vec(),
+        };
+
+        if let Err(TrySendError::Full(_)) = self.tx.try_send(record) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+struct Record {
+    timestamp: SystemTime,
+    direction: Direction,
+    endpoints: Option<Endpoints>,
+    bytes: Vec<u8>,
+}
+
+/// Sequence numbers of the synthesized TCP segments of a session
+#[derive(Clone, Copy)]
+struct Flow {
+    tx: u32,
+    rx: u32,
+    // number of the last record written for the flow
+    last_record: u64,
+}
+
+impl Default for Flow {
+    fn default() -> Self {
+        Self {
+            tx: 1,
+            rx: 1,
+            last_record: 0,
+        }
+    }
+}
+
+struct Writer {
+    config: CaptureConfig,
+    file: CaptureFile,
+    index: u32,
+    flows: HashMap<Endpoints, Flow>,
+    records: u64,
+}
+
+impl Writer {
+    fn new(config: CaptureConfig) -> std::io::Result<Self> {
+        let file = CaptureFile::create(&config, 0)?;
+        Ok(Self {
+            config,
+            file,
+            index: 0,
+            flows: HashMap::new(),
+            records: 0,
+        })
+    }
+
+    fn run(mut self, rx: Receiver<Record>) {
+        while let Ok(record) = rx.recv() {
+            self.write(record);
+            for record in rx.try_iter() {
+                self.write(record);
+            }
+            // flush whenever the queue is empty so the file can be opened while sessions run
+            if let Err(err) = self.file.flush() {
+                tracing::warn!("unable to flush capture file: {}", err);
+            }
+        }
+    }
+
+    fn write(&mut self, record: Record) {
+        if self.should_rotate() {
+            self.rotate();
+        }
+
+        let (link, packet) = match record.endpoints {
+            Some(endpoints) => {
+                let flow = self.flow(endpoints);
+                let packet = synthesize_tcp(flow, endpoints, record.direction, &record.bytes);
+                (ETHERNET_LINK_TYPE, packet)
+            }
+            None => (RTU_LINK_TYPE, record.bytes),
+        };
+
+        if let Err(err) = self.file.write_packet(link, record.timestamp, &packet) {
+            tracing::warn!("unable to write to capture file: {}", err);
+        }
+    }
+
+    fn flow(&mut self, endpoints: Endpoints) -> &mut Flow {
+        if self.flows.len() >= MAX_FLOWS && !self.flows.contains_key(&endpoints) {
+            let oldest = self
+                .flows
+                .iter()
+                .min_by_key(|(_, flow)| flow.last_record)
+                .map(|(endpoints, _)| *endpoints);
+            if let Some(oldest) = oldest {
+                self.flows.remove(&oldest);
+            }
+        }
+
+        self.records += 1;
+        let flow = self.flows.entry(endpoints).or_default();
+        flow.last_record = self.records;
+        flow
+    }
+
+    fn should_rotate(&self) -> bool {
+        let too_big = self
+            .config
+            .max_file_size
+            .is_some_and(|max| self.file.size >= max);
+        let too_old = self
+            .config
+            .max_file_age
+            .is_some_and(|max| self.file.opened.elapsed() >= max);
+        too_big || too_old
+    }
+
+    fn rotate(&mut self) {
+        let index = self.index.wrapping_add(1);
+        match CaptureFile::create(&self.config, index) {
+            Ok(file) => {
+                if let Err(err) = self.file.flush() {
+                    tracing::warn!("unable to flush capture file: {}", err);
+                }
+                self.file = file;
+                self.index = index;
+                // sequence numbers restart in every file
+                self.flows.clear();
+            }
+            Err(err) => {
+                tracing::warn!("unable to create capture file: {}", err);
+            }
+        }
+    }
+}
+
+struct CaptureFile {
+    writer: BufWriter<File>,
+    format: CaptureFormat,
+    size: u64,
+    opened: Instant,
+    // link type of every pcapng interface or of the single pcap link, in order of appearance
+    links: Vec<u16>,
+}
+
+impl CaptureFile {
+    fn create(config: &CaptureConfig, index: u32) -> std::io::Result<Self> {
+        let path = config.file_path(index);
+        tracing::info!("writing capture to {}", path.display());
+        let mut file = Self {
+            writer: BufWriter::new(File::create(path)?),
+            format: config.format,
+            size: 0,
+            opened: Instant::now(),
+            links: Vec::new(),
+        };
+
+        if file.format == CaptureFormat::PcapNg {
+            // section header block
+            let mut block = Vec::new();
+            block.extend(0x0A0D_0D0Au32.to_le_bytes());
+            block.extend(28u32.to_le_bytes());
+            block.extend(0x1A2B_3C4Du32.to_le_bytes());
+            block.extend(1u16.to_le_bytes());
+            block.extend(0u16.to_le_bytes());
+            block.extend((-1i64).to_le_bytes()); // unknown section length
+            block.extend(28u32.to_le_bytes());
+            file.write_all(&block)?;
+        }
+
+        Ok(file)
+    }
+
+    fn write_all(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.writer.write_all(bytes)?;
+        self.size += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+
+    fn write_packet(
+        &mut self,
+        link: u16,
+        timestamp: SystemTime,
+        packet: &[u8],
+    ) -> std::io::Result<()> {
+        let micros = timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        let length = packet.len() as u32;
+
+        match self.format {
+            CaptureFormat::Pcap => {
+                match self.links.first() {
+                    None => {
+                        self.links.push(link);
+                        let mut header = Vec::new();
+                        header.extend(0xA1B2_C3D4u32.to_le_bytes());
+                        header.extend(2u16.to_le_bytes());
+                        header.extend(4u16.to_le_bytes());
+                        header.extend(0i32.to_le_bytes()); // GMT offset
+                        header.extend(0u32.to_le_bytes()); // timestamp accuracy
+                        header.extend(SNAP_LENGTH.to_le_bytes());
+                        header.extend((link as u32).to_le_bytes());
+                        self.write_all(&header)?;
+                    }
+                    Some(x) if *x != link => {
+                        tracing::warn!(
+                            "discarding frame with link type {} in pcap file with link type {}",
+                            link,
+                            x
+                        );
+                        return Ok(());
+                    }
+                    Some(_) => {}
+                }
+
+                let mut record = Vec::with_capacity(16 + packet.len());
+                record.extend(((micros / 1_000_000) as u32).to_le_bytes());
+                record.extend(((micros % 1_000_000) as u32).to_le_bytes());
+                record.extend(length.to_le_bytes());
+                record.extend(length.to_le_bytes());
+                record.extend_from_slice(packet);
+                self.write_all(&record)
+            }
+            CaptureFormat::PcapNg => {
+                let interface = match self.links.iter().position(|x| *x == link) {
+                    Some(x) => x,
+                    None => {
+                        // interface description block
+                        let mut block = Vec::new();
+                        block.extend(1u32.to_le_bytes());
+                        block.extend(20u32.to_le_bytes());
+                        block.extend(link.to_le_bytes());
+                        block.extend(0u16.to_le_bytes());
+                        block.extend(SNAP_LENGTH.to_le_bytes());
+                        block.extend(20u32.to_le_bytes());
+                        self.write_all(&block)?;
+                        self.links.push(link);
+                        self.links.len() - 1
+                    }
+                };
+
+                // enhanced packet block with the data padded to 32 bits
+                let padding = (4 - packet.len() % 4) % 4;
+                let total = (32 + packet.len() + padding) as u32;
+                let mut block = Vec::with_capacity(total as usize);
+                block.extend(6u32.to_le_bytes());
+                block.extend(total.to_le_bytes());
+                block.extend((interface as u32).to_le_bytes());
+                block.extend(((micros >> 32) as u32).to_le_bytes());
+                block.extend((micros as u32).to_le_bytes());
+                block.extend(length.to_le_bytes());
+                block.extend(length.to_le_bytes());
+                block.extend_from_slice(packet);
+                block.resize(block.len() + padding, 0);
+                block.extend(total.to_le_bytes());
+                self.write_all(&block)
+            }
+        }
+    }
+}
+
+/// Wrap a frame in Ethernet, IP and TCP headers and advance the sequence numbers of the flow
+fn synthesize_tcp(
+    flow: &mut Flow,
+    endpoints: Endpoints,
+    direction: Direction,
+    payload: &[u8],
+) -> Vec<u8> {
+    let (src, dst, src_mac, dst_mac, seq, ack) = match direction {
+        Direction::Tx => {
+            let seq = flow.tx;
+            flow.tx = flow.tx.wrapping_add(payload.len() as u32);
+            (
+                endpoints.local,
+                endpoints.remote,
+                LOCAL_MAC,
+                REMOTE_MAC,
+                seq,
+                flow.rx,
+            )
+        }
+        Direction::Rx => {
+            let seq = flow.rx;
+            flow.rx = flow.rx.wrapping_add(payload.len() as u32);
+            (
+                endpoints.remote,
+                endpoints.local,
+                REMOTE_MAC,
+                LOCAL_MAC,
+                seq,
+                flow.tx,
+            )
+        }
+    };
+
+    let mut segment = Vec::with_capacity(20 + payload.len());
+    segment.extend(src.port().to_be_bytes());
+    segment.extend(dst.port().to_be_bytes());
+    segment.extend(seq.to_be_bytes());
+    segment.extend(ack.to_be_bytes());
+    segment.push(0x50); // data offset of 5 words
+    segment.push(0x18); // PSH + ACK
+    segment.extend(u16::MAX.to_be_bytes()); // window
+    segment.extend(0u16.to_be_bytes()); // checksum
+    segment.extend(0u16.to_be_bytes()); // urgent pointer
+    segment.extend_from_slice(payload);
+
+    let segment_length = segment.len() as u16;
+    let mut packet = Vec::with_capacity(14 + 40 + segment.len());
+    packet.extend(dst_mac);
+    packet.extend(src_mac);
+
+    match (src.ip(), dst.ip()) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            let mut pseudo = Vec::with_capacity(12);
+            pseudo.extend(src.octets());
+            pseudo.extend(dst.octets());
+            pseudo.extend([0, 6]);
+            pseudo.extend(segment_length.to_be_bytes());
+            set_tcp_checksum(&mut segment, &pseudo);
+
+            let mut header = Vec::with_capacity(20);
+            header.extend([0x45, 0x00]); // version 4, 5 word header
+            header.extend((20 + segment_length).to_be_bytes());
+            header.extend([0x00, 0x00, 0x40, 0x00]); // id and don't fragment
+            header.extend([64, 6]); // TTL and protocol
+            header.extend([0x00, 0x00]); // checksum
+            header.extend(src.octets());
+            header.extend(dst.octets());
+            let checksum = checksum(&header);
+            header[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+            packet.extend(0x0800u16.to_be_bytes());
+            packet.extend(header);
+        }
+        (src, dst) => {
+            let (src, dst) = (to_ipv6(src), to_ipv6(dst));
+            let mut pseudo = Vec::with_capacity(40);
+            pseudo.extend(src.octets());
+            pseudo.extend(dst.octets());
+            pseudo.extend((segment_length as u32).to_be_bytes());
+            pseudo.extend([0, 0, 0, 6]);
+            set_tcp_checksum(&mut segment, &pseudo);
+
+            packet.extend(0x86DDu16.to_be_bytes());
+            packet.extend([0x60, 0x00, 0x00, 0x00]); // version 6
+            packet.extend(segment_length.to_be_bytes());
+            packet.extend([6, 64]); // next header and hop limit
+            packet.extend(src.octets());
+            packet.extend(dst.octets());
+        }
+    }
+
+    packet.extend(segment);
+    packet
+}
+
+fn to_ipv6(ip: IpAddr) -> Ipv6Addr {
+    match ip {
+        IpAddr::V4(x) => x.to_ipv6_mapped(),
+        IpAddr::V6(x) => x,
+    }
+}
+
+fn set_tcp_checksum(segment: &mut [u8], pseudo_header: &[u8]) {
+    let mut bytes = pseudo_header.to_vec();
+    bytes.extend_from_slice(segment);
+    let checksum = checksum(&bytes);
+    segment[16..18].copy_from_slice(&checksum.to_be_bytes());
+}
+
+/// Internet checksum (RFC 1071)
+fn checksum(bytes: &[u8]) -> u16 {
+    let mut sum: u32 = bytes
+        .chunks(2)
+        .map(|x| u16::from_be_bytes([x[0], x.get(1).copied().unwrap_or(0)]) as u32)
+        .sum();
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+
+    fn record(endpoints: Option<Endpoints>, direction: Direction, bytes: &[u8]) -> Record {
+        Record {
+            timestamp: UNIX_EPOCH + Duration::from_micros(1_500_000),
+            direction,
+            endpoints,
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    #[test]
+    fn computes_internet_checksum() {
+        // commonly used IPv4 header example
+        let header = [
+            0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7,
+        ];
+        assert_eq!(checksum(&header), 0xB861);
+    }
+
+    #[test]
+    fn synthesizes_tcp_segments_with_sequence_numbers() {
+        let endpoints = Endpoints {
+            local: "127.0.0.1:40000".parse().unwrap(),
+            remote: "127.0.0.2:502".parse().unwrap(),
+        };
+        let mut flow = Flow::default();
+
+        let request = synthesize_tcp(&mut flow, endpoints, Direction::Tx, &[1, 2, 3]);
+        assert_eq!(request.len(), 14 + 20 + 20 + 3);
+        assert_eq!(&request[12..14], &[0x08, 0x00]);
+        assert_eq!(&request[26..30], &[127, 0, 0, 1]);
+        assert_eq!(&request[30..34], &[127, 0, 0, 2]);
+        assert_eq!(&request[34..38], &[0x9C, 0x40, 0x01, 0xF6]);
+        assert_eq!(&request[38..42], &1u32.to_be_bytes());
+        assert_eq!(checksum(&request[14..34]), 0);
+
+        let response = synthesize_tcp(&mut flow, endpoints, Direction::Rx, &[4, 5]);
+        assert_eq!(&response[26..30], &[127, 0, 0, 2]);
+        assert_eq!(&response[38..42], &1u32.to_be_bytes());
+        assert_eq!(&response[42..46], &4u32.to_be_bytes()); // acknowledges the request
+    }
+
+    #[test]
+    fn writes_tcp_and_rtu_frames_to_pcapng() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = CaptureConfig::new(dir.path().join("mixed.pcapng"), CaptureFormat::PcapNg);
+        let endpoints = Endpoints {
+            local: "[::1]:40000".parse().unwrap(),
+            remote: "[::1]:502".parse().unwrap(),
+        };
+
+        let mut writer = Writer::new(config.clone()).unwrap();
+        writer.write(record(Some(endpoints), Direction::Tx, &[0xCA, 0xFE]));
+        writer.write(record(None, Direction::Rx, &[0x01, 0x02, 0x03]));
+        writer.file.flush().unwrap();
+
+        let bytes = std::fs::read(&config.path).unwrap();
+        let ethernet_packet = 14 + 40 + 20 + 2;
+        let blocks = 28 + 20 + (32 + ethernet_packet) + 20 + (32 + 4);
+        assert_eq!(bytes.len(), blocks);
+        // second interface uses the RTU link type
+        let second_interface = 28 + 20 + 32 + ethernet_packet;
+        assert_eq!(
+            &bytes[second_interface + 8..second_interface + 10],
+            &RTU_LINK_TYPE.to_le_bytes()
+        );
+        // the RTU packet is written as is
+        let rtu_data = second_interface + 20 + 28;
+        assert_eq!(&bytes[rtu_data..rtu_data + 4], &[0x01, 0x02, 0x03, 0x00]);
+    }
+
+    #[test]
+    fn rotates_pcap_files_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = CaptureConfig::new(dir.path().join("rotate.pcap"), CaptureFormat::Pcap)
+            .max_file_size(40);
+
+        let mut writer = Writer::new(config.clone()).unwrap();
+        for _ in 0..3 {
+            writer.write(record(None, Direction::Tx, &[0x01, 0x03, 0x00, 0x00]));
+        }
+        writer.file.flush().unwrap();
+
+        // every file holds the global header and a single record of 20 bytes
+        let first = std::fs::read(&config.path).unwrap();
+        assert_eq!(first.len(), 24 + 20);
+        assert_eq!(&first[20..24], &(RTU_LINK_TYPE as u32).to_le_bytes());
+        assert_eq!(&first[24..28], &1u32.to_le_bytes());
+        assert_eq!(&first[28..32], &500_000u32.to_le_bytes());
+        for index in 1..3 {
+            let path = config.file_path(index);
+            assert_eq!(
+                path.file_name().unwrap().to_str().unwrap(),
+                format!("rotate.{index}.pcap")
+            );
+            assert_eq!(std::fs::read(path).unwrap().len(), 24 + 20);
+        }
+    }
+
+    #[test]
+    fn forgets_the_least_recently_used_flows() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = CaptureConfig::new(dir.path().join("flows.pcap"), CaptureFormat::Pcap);
+        let endpoints = |port: u16| Endpoints {
+            local: SocketAddr::from(([127, 0, 0, 1], port)),
+            remote: "127.0.0.2:502".parse().unwrap(),
+        };
+
+        let mut writer = Writer::new(config).unwrap();
+        for port in 0..MAX_FLOWS as u16 {
+            writer.write(record(Some(endpoints(port)), Direction::Tx, &[0xCA, 0xFE]));
+        }
+        writer.write(record(Some(endpoints(0)), Direction::Tx, &[0xCA, 0xFE]));
+        writer.write(record(Some(endpoints(1000)), Direction::Tx, &[0xCA, 0xFE]));
+
+        assert_eq!(writer.flows.len(), MAX_FLOWS);
+        assert_eq!(writer.flows[&endpoints(0)].tx, 5);
+        assert!(!writer.flows.contains_key(&endpoints(1)));
+        assert!(writer.flows.contains_key(&endpoints(1000)));
+    }
+
+    #[test]
+    fn writes_frames_received_by_the_observer() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = CaptureConfig::new(dir.path().join("observer.pcap"), CaptureFormat::Pcap);
+        let writer = CaptureWriter::start(config.clone()).unwrap();
+        for bytes in [&[0x01, 0x03, 0x00, 0x00][..], &[0x01, 0x83, 0x02]] {
+            writer.on_frame(TrafficEvent {
+                timestamp: UNIX_EPOCH + Duration::from_micros(1_500_000),
+                direction: Direction::Tx,
+                session: 1,
+                endpoints: None,
+                unit_id: None,
+                function: None,
+                bytes,
+            });
+        }
+
+        // the file is flushed by the writer thread once it has written the queued frames
+        let expected = 24 + (16 + 4) + (16 + 3);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let bytes = loop {
+            let bytes = std::fs::read(&config.path).unwrap();
+            if bytes.len() == expected || Instant::now() > deadline {
+                break bytes;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+
+        assert_eq!(bytes.len(), expected);
+        assert_eq!(&bytes[20..24], &(RTU_LINK_TYPE as u32).to_le_bytes());
+        assert_eq!(&bytes[40..44], &[0x01, 0x03, 0x00, 0x00]);
+        assert_eq!(&bytes[60..63], &[0x01, 0x83, 0x02]);
+        assert_eq!(writer.dropped(), 0);
+    }
+}
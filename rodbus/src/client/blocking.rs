@@ -0,0 +1,516 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use crate::client::{
+    Channel, ChannelStatistics, ClientState, HostAddr, Listener, RequestParam, RetryPolicy,
+    RetryStrategy, WriteMultiple,
+};
+use crate::codec::{RegisterCodec, WordOrder};
+use crate::decode::{DecodeFormat, DecodeLevel};
+use crate::error::{RequestError, Shutdown, VerifyError};
+use crate::observer::TrafficObserver;
+use crate::types::{AddressRange, DeviceIdentification, Indexed, ReadDeviceInfoBlock};
+
+enum Executor {
+    Owned(tokio::runtime::Runtime),
+    Handle(tokio::runtime::Handle),
+}
+
+impl Executor {
+    fn new() -> std::io::Result<Self> {
+        // a worker thread keeps the connection alive between calls
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?;
+        Ok(Executor::Owned(runtime))
+    }
+
+    fn handle(&self) -> &tokio::runtime::Handle {
+        match self {
+            Executor::Owned(runtime) => runtime.handle(),
+            Executor::Handle(handle) => handle,
+        }
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.handle().block_on(future)
+    }
+}
+
+/// Synchronous wrapper around a [`Channel`] for programs that don't use async
+///
+/// Every method blocks the calling thread until the underlying request completes. The methods
+/// must not be called from within an async context, or they will panic.
+pub struct BlockingChannel {
+    // dropped before the runtime so that the channel task shuts down cleanly
+    channel: Channel,
+    executor: Executor,
+}
+
+impl BlockingChannel {
+    /// Wrap a channel that was spawned on the runtime of `handle`
+    ///
+    /// The runtime must be multi-threaded: the channel task only makes progress on a
+    /// `current_thread` runtime while it is driven by a `block_on` call, so requests would
+    /// never complete. Fails with [`std::io::ErrorKind::InvalidInput`] for such a runtime.
+    pub fn with_handle(handle: tokio::runtime::Handle, channel: Channel) -> std::io::Result<Self> {
+        if handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::CurrentThread {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "blocking channel requires a multi-threaded runtime",
+            ));
+        }
+
+        Ok(Self {
+            channel,
+            executor: Executor::Handle(handle),
+        })
+    }
+
+    /// Create a runtime owned by the channel and spawn a TCP channel on it
+    ///
+    /// See [`crate::client::spawn_tcp_client_task`] for a description of the arguments.
+    pub fn spawn_tcp(
+        host: HostAddr,
+        max_queued_requests: usize,
+        retry: Box<dyn RetryStrategy>,
+        decode: DecodeLevel,
+        listener: Option<Box<dyn Listener<ClientState>>>,
+    ) -> std::io::Result<Self> {
+        let executor = Executor::new()?;
+        let channel = {
+            let _guard = executor.handle().enter();
+            crate::client::spawn_tcp_client_task(host, max_queued_requests, retry, decode, listener)
+        };
+        Ok(Self { channel, executor })
+    }
+
+    /// Create a runtime owned by the channel and spawn a serial channel on it
+    ///
+    /// See [`crate::client::spawn_rtu_client_task`] for a description of the arguments.
+    #[cfg(feature = "serial")]
+    pub fn spawn_rtu(
+        path: &str,
+        serial_settings: crate::serial::SerialSettings,
+        max_queued_requests: usize,
+        retry: Box<dyn RetryStrategy>,
+        decode: DecodeLevel,
+        listener: Option<Box<dyn Listener<crate::client::PortState>>>,
+    ) -> std::io::Result<Self> {
+        let executor = Executor::new()?;
+        let channel = {
+            let _guard = executor.handle().enter();
+            crate::client::spawn_rtu_client_task(
+                path,
+                serial_settings,
+                max_queued_requests,
+                retry,
+                decode,
+                listener,
+            )
+        };
+        Ok(Self { channel, executor })
+    }
+
+    /// Create a runtime owned by the channel and spawn a TLS channel on it
+    ///
+    /// See [`crate::client::spawn_tls_client_task`] for a description of the arguments.
+    #[cfg(feature = "tls")]
+    pub fn spawn_tls(
+        host: HostAddr,
+        max_queued_requests: usize,
+        retry: Box<dyn RetryStrategy>,
+        tls_config: crate::client::TlsClientConfig,
+        decode: DecodeLevel,
+        listener: Option<Box<dyn Listener<ClientState>>>,
+    ) -> std::io::Result<Self> {
+        let executor = Executor::new()?;
+        let channel = {
+            let _guard = executor.handle().enter();
+            crate::client::spawn_tls_client_task(
+                host,
+                max_queued_requests,
+                retry,
+                tls_config,
+                decode,
+                listener,
+            )
+        };
+        Ok(Self { channel, executor })
+    }
+
+    /// Underlying async channel
+    pub fn channel(&mut self) -> &mut Channel {
+        &mut self.channel
+    }
+
+    /// Retry the requests made through this channel according to `policy`
+    ///
+    /// See [`Channel::with_retry_policy`]
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.channel = self.channel.with_retry_policy(policy);
+    }
+
+    /// See [`Channel::enable`]
+    pub fn enable(&self) -> Result<(), Shutdown> {
+        self.executor.block_on(self.channel.enable())
+    }
+
+    /// See [`Channel::disable`]
+    pub fn disable(&self) -> Result<(), Shutdown> {
+        self.executor.block_on(self.channel.disable())
+    }
+
+    /// See [`Channel::statistics`]
+    pub fn statistics(&self) -> Result<ChannelStatistics, Shutdown> {
+        self.executor.block_on(self.channel.statistics())
+    }
+
+    /// See [`Channel::reset_statistics`]
+    pub fn reset_statistics(&self) -> Result<(), Shutdown> {
+        self.executor.block_on(self.channel.reset_statistics())
+    }
+
+    /// See [`Channel::set_decode_level`]
+    pub fn set_decode_level(&mut self, level: DecodeLevel) -> Result<(), Shutdown> {
+        self.executor.block_on(self.channel.set_decode_level(level))
+    }
+
+    /// See [`Channel::set_decode_format`]
+    pub fn set_decode_format(&mut self, format: DecodeFormat) -> Result<(), Shutdown> {
+        self.executor
+            .block_on(self.channel.set_decode_format(format))
+    }
+
+    /// See [`Channel::set_traffic_observer`]
+    pub fn set_traffic_observer(
+        &mut self,
+        observer: Option<Arc<dyn TrafficObserver>>,
+    ) -> Result<(), Shutdown> {
+        self.executor
+            .block_on(self.channel.set_traffic_observer(observer))
+    }
+
+    /// See [`Channel::read_coils`]
+    pub fn read_coils(
+        &mut self,
+        param: RequestParam,
+        range: AddressRange,
+    ) -> Result<Vec<Indexed<bool>>, RequestError> {
+        self.executor
+            .block_on(self.channel.read_coils(param, range))
+    }
+
+    /// See [`Channel::read_discrete_inputs`]
+    pub fn read_discrete_inputs(
+        &mut self,
+        param: RequestParam,
+        range: AddressRange,
+    ) -> Result<Vec<Indexed<bool>>, RequestError> {
+        self.executor
+            .block_on(self.channel.read_discrete_inputs(param, range))
+    }
+
+    /// See [`Channel::read_holding_registers`]
+    pub fn read_holding_registers(
+        &mut self,
+        param: RequestParam,
+        range: AddressRange,
+    ) -> Result<Vec<Indexed<u16>>, RequestError> {
+        self.executor
+            .block_on(self.channel.read_holding_registers(param, range))
+    }
+
+    /// See [`Channel::read_input_registers`]
+    pub fn read_input_registers(
+        &mut self,
+        param: RequestParam,
+        range: AddressRange,
+    ) -> Result<Vec<Indexed<u16>>, RequestError> {
+        self.executor
+            .block_on(self.channel.read_input_registers(param, range))
+    }
+
+    /// See [`Channel::read_device_identification`]
+    pub fn read_device_identification(
+        &mut self,
+        param: RequestParam,
+        device_params: ReadDeviceInfoBlock,
+    ) -> Result<DeviceIdentification, RequestError> {
+        self.executor.block_on(
+            self.channel
+                .read_device_identification(param, device_params),
+        )
+    }
+
+    /// See [`Channel::write_single_coil`]
+    pub fn write_single_coil(
+        &mut self,
+        param: RequestParam,
+        request: Indexed<bool>,
+    ) -> Result<Indexed<bool>, RequestError> {
+        self.executor
+            .block_on(self.channel.write_single_coil(param, request))
+    }
+
+    /// See [`Channel::write_single_register`]
+    pub fn write_single_register(
+        &mut self,
+        param: RequestParam,
+        request: Indexed<u16>,
+    ) -> Result<Indexed<u16>, RequestError> {
+        self.executor
+            .block_on(self.channel.write_single_register(param, request))
+    }
+
+    /// See [`Channel::write_multiple_coils`]
+    pub fn write_multiple_coils(
+        &mut self,
+        param: RequestParam,
+        request: WriteMultiple<bool>,
+    ) -> Result<AddressRange, RequestError> {
+        self.executor
+            .block_on(self.channel.write_multiple_coils(param, request))
+    }
+
+    /// See [`Channel::write_multiple_registers`]
+    pub fn write_multiple_registers(
+        &mut self,
+        param: RequestParam,
+        request: WriteMultiple<u16>,
+    ) -> Result<AddressRange, RequestError> {
+        self.executor
+            .block_on(self.channel.write_multiple_registers(param, request))
+    }
+
+    /// See [`Channel::write_single_coil_verified`]
+    pub fn write_single_coil_verified(
+        &mut self,
+        param: RequestParam,
+        request: Indexed<bool>,
+    ) -> Result<Indexed<bool>, VerifyError> {
+        self.executor
+            .block_on(self.channel.write_single_coil_verified(param, request))
+    }
+
+    /// See [`Channel::write_single_register_verified`]
+    pub fn write_single_register_verified(
+        &mut self,
+        param: RequestParam,
+        request: Indexed<u16>,
+    ) -> Result<Indexed<u16>, VerifyError> {
+        self.executor
+            .block_on(self.channel.write_single_register_verified(param, request))
+    }
+
+    /// See [`Channel::write_multiple_coils_verified`]
+    pub fn write_multiple_coils_verified(
+        &mut self,
+        param: RequestParam,
+        request: WriteMultiple<bool>,
+    ) -> Result<AddressRange, VerifyError> {
+        self.executor
+            .block_on(self.channel.write_multiple_coils_verified(param, request))
+    }
+
+    /// See [`Channel::write_multiple_registers_verified`]
+    pub fn write_multiple_registers_verified(
+        &mut self,
+        param: RequestParam,
+        request: WriteMultiple<u16>,
+    ) -> Result<AddressRange, VerifyError> {
+        self.executor.block_on(
+            self.channel
+                .write_multiple_registers_verified(param, request),
+        )
+    }
+
+    /// See [`Channel::read_holding_value`]
+    pub fn read_holding_value<T: RegisterCodec>(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        order: WordOrder,
+    ) -> Result<T, RequestError> {
+        self.executor
+            .block_on(self.channel.read_holding_value(param, address, order))
+    }
+
+    /// See [`Channel::read_input_value`]
+    pub fn read_input_value<T: RegisterCodec>(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        order: WordOrder,
+    ) -> Result<T, RequestError> {
+        self.executor
+            .block_on(self.channel.read_input_value(param, address, order))
+    }
+
+    /// See [`Channel::write_value`]
+    pub fn write_value<T: RegisterCodec>(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        value: T,
+        order: WordOrder,
+    ) -> Result<AddressRange, RequestError> {
+        self.executor
+            .block_on(self.channel.write_value(param, address, value, order))
+    }
+
+    /// See [`Channel::read_f32`]
+    pub fn read_f32(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        order: WordOrder,
+    ) -> Result<f32, RequestError> {
+        self.executor
+            .block_on(self.channel.read_f32(param, address, order))
+    }
+
+    /// See [`Channel::write_f32`]
+    pub fn write_f32(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        value: f32,
+        order: WordOrder,
+    ) -> Result<AddressRange, RequestError> {
+        self.executor
+            .block_on(self.channel.write_f32(param, address, value, order))
+    }
+
+    /// See [`Channel::read_u32`]
+    pub fn read_u32(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        order: WordOrder,
+    ) -> Result<u32, RequestError> {
+        self.executor
+            .block_on(self.channel.read_u32(param, address, order))
+    }
+
+    /// See [`Channel::write_u32`]
+    pub fn write_u32(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        value: u32,
+        order: WordOrder,
+    ) -> Result<AddressRange, RequestError> {
+        self.executor
+            .block_on(self.channel.write_u32(param, address, value, order))
+    }
+
+    /// See [`Channel::read_i64`]
+    pub fn read_i64(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        order: WordOrder,
+    ) -> Result<i64, RequestError> {
+        self.executor
+            .block_on(self.channel.read_i64(param, address, order))
+    }
+
+    /// See [`Channel::write_i64`]
+    pub fn write_i64(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        value: i64,
+        order: WordOrder,
+    ) -> Result<AddressRange, RequestError> {
+        self.executor
+            .block_on(self.channel.write_i64(param, address, value, order))
+    }
+
+    /// See [`Channel::read_f64`]
+    pub fn read_f64(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        order: WordOrder,
+    ) -> Result<f64, RequestError> {
+        self.executor
+            .block_on(self.channel.read_f64(param, address, order))
+    }
+
+    /// See [`Channel::write_f64`]
+    pub fn write_f64(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        value: f64,
+        order: WordOrder,
+    ) -> Result<AddressRange, RequestError> {
+        self.executor
+            .block_on(self.channel.write_f64(param, address, value, order))
+    }
+
+    /// See [`Channel::read_ascii`]
+    pub fn read_ascii(
+        &mut self,
+        param: RequestParam,
+        range: AddressRange,
+        order: WordOrder,
+    ) -> Result<String, RequestError> {
+        self.executor
+            .block_on(self.channel.read_ascii(param, range, order))
+    }
+
+    /// See [`Channel::write_ascii`]
+    pub fn write_ascii(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        value: &str,
+        count: u16,
+        order: WordOrder,
+    ) -> Result<AddressRange, RequestError> {
+        self.executor.block_on(
+            self.channel
+                .write_ascii(param, address, value, count, order),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::UnitId;
+    use std::time::Duration;
+
+    #[test]
+    fn requests_fail_with_shutdown_when_the_task_is_gone() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        drop(rx);
+
+        let mut channel =
+            BlockingChannel::with_handle(runtime.handle().clone(), Channel::new(tx)).unwrap();
+        let param = RequestParam::new(UnitId::new(1), Duration::from_secs(1));
+        assert_eq!(
+            channel.read_coils(param, AddressRange::try_from(0, 1).unwrap()),
+            Err(RequestError::Shutdown)
+        );
+        assert!(channel.enable().is_err());
+    }
+
+    #[test]
+    fn rejects_current_thread_runtimes() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let (tx, _rx) = tokio::sync::mpsc::channel(1);
+
+        let err = BlockingChannel::with_handle(runtime.handle().clone(), Channel::new(tx))
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+}
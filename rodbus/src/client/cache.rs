@@ -0,0 +1,514 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::oneshot;
+
+use crate::client::{Channel, RequestParam, WriteMultiple};
+use crate::error::RequestError;
+use crate::types::{AddressRange, Indexed, Table, UnitId};
+
+type PointKey = (UnitId, Table, u16);
+type RangeKey = (UnitId, Table, u16, u16);
+type Waiter<T> = oneshot::Sender<Result<Vec<Indexed<T>>, RequestError>>;
+
+struct InFlight<T> {
+    token: u64,
+    waiters: Vec<Waiter<T>>,
+}
+
+struct Cache<T> {
+    entries: BTreeMap<PointKey, (Instant, T)>,
+    in_flight: BTreeMap<RangeKey, InFlight<T>>,
+}
+
+impl<T> Default for Cache<T> {
+    fn default() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            in_flight: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T: Copy> Cache<T> {
+    fn lookup(&self, key: RangeKey, ttl: Duration, now: Instant) -> Option<Vec<Indexed<T>>> {
+        let (id, table, start, count) = key;
+        AddressRange { start, count }
+            .iter()
+            .map(|address| match self.entries.get(&(id, table, address)) {
+                Some((time, value)) if now.saturating_duration_since(*time) < ttl => {
+                    Some(Indexed::new(address, *value))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn store(&mut self, id: UnitId, table: Table, values: &[Indexed<T>], now: Instant) {
+        for value in values {
+            self.entries
+                .insert((id, table, value.index), (now, value.value));
+        }
+    }
+
+    fn invalidate(&mut self, id: UnitId, table: Table, range: AddressRange) {
+        for address in range.iter() {
+            self.entries.remove(&(id, table, address));
+        }
+
+        // dropping the waiters makes them issue their own read after the write
+        let end = range.start as u32 + range.count as u32;
+        self.in_flight.retain(|(unit, t, start, count), _| {
+            let overlaps =
+                (*start as u32) < end && (range.start as u32) < *start as u32 + *count as u32;
+            !(*unit == id && *t == table && overlaps)
+        });
+    }
+}
+
+#[derive(Default)]
+struct State {
+    bits: Cache<bool>,
+    registers: Cache<u16>,
+    next_token: u64,
+    generation: u64,
+}
+
+trait CachedValue: Copy + Send + 'static {
+    fn cache(state: &mut State) -> &mut Cache<Self>;
+}
+
+impl CachedValue for bool {
+    fn cache(state: &mut State) -> &mut Cache<Self> {
+        &mut state.bits
+    }
+}
+
+impl CachedValue for u16 {
+    fn cache(state: &mut State) -> &mut Cache<Self> {
+        &mut state.registers
+    }
+}
+
+enum Begin<T> {
+    Cached(Vec<Indexed<T>>),
+    Wait(oneshot::Receiver<Result<Vec<Indexed<T>>, RequestError>>),
+    Request(u64, u64), // token and generation
+}
+
+/// Removes the in-flight entry of a read if its future is dropped before completing
+struct InFlightGuard<'a, T: CachedValue> {
+    state: &'a Mutex<State>,
+    key: RangeKey,
+    token: u64,
+    done: bool,
+    _value: std::marker::PhantomData<T>,
+}
+
+impl<T: CachedValue> Drop for InFlightGuard<'_, T> {
+    fn drop(&mut self) {
+        if !self.done {
+            let mut state = self.state.lock().unwrap();
+            let cache = T::cache(&mut state);
+            if cache.in_flight.get(&self.key).map(|x| x.token) == Some(self.token) {
+                cache.in_flight.remove(&self.key);
+            }
+        }
+    }
+}
+
+/// Caching layer on top of a [`Channel`] for components that read the same data
+///
+/// Read results are cached per unit id, table and address. Reads are served from the cache when
+/// every requested address has an entry younger than the configured time-to-live. Concurrent
+/// identical reads are coalesced into a single request, and writes issued through the
+/// `CachedChannel` invalidate the entries of the written range.
+///
+/// Clones share the same cache, so every component should use a clone of the same instance.
+#[derive(Clone)]
+pub struct CachedChannel {
+    channel: Channel,
+    ttl: Duration,
+    state: Arc<Mutex<State>>,
+}
+
+impl CachedChannel {
+    /// Create a caching layer whose entries are valid for `ttl`
+    pub fn new(channel: Channel, ttl: Duration) -> Self {
+        Self {
+            channel,
+            ttl,
+            state: Arc::new(Mutex::new(State::default())),
+        }
+    }
+
+    /// Underlying channel. Writes issued through it do not invalidate the cache.
+    pub fn channel(&mut self) -> &mut Channel {
+        &mut self.channel
+    }
+
+    /// Remove all the cached entries
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        state.bits.entries.clear();
+        state.registers.entries.clear();
+    }
+
+    /// Read coils from the cache or from the server
+    pub async fn read_coils(
+        &mut self,
+        param: RequestParam,
+        range: AddressRange,
+    ) -> Result<Vec<Indexed<bool>>, RequestError> {
+        range.of_read_bits()?;
+        let mut channel = self.channel.clone();
+        self.read(param.id, Table::Coils, range, || async move {
+            channel.read_coils(param, range).await
+        })
+        .await
+    }
+
+    /// Read discrete inputs from the cache or from the server
+    pub async fn read_discrete_inputs(
+        &mut self,
+        param: RequestParam,
+        range: AddressRange,
+    ) -> Result<Vec<Indexed<bool>>, RequestError> {
+        range.of_read_bits()?;
+        let mut channel = self.channel.clone();
+        self.read(param.id, Table::DiscreteInputs, range, || async move {
+            channel.read_discrete_inputs(param, range).await
+        })
+        .await
+    }
+
+    /// Read holding registers from the cache or from the server
+    pub async fn read_holding_registers(
+        &mut self,
+        param: RequestParam,
+        range: AddressRange,
+    ) -> Result<Vec<Indexed<u16>>, RequestError> {
+        range.of_read_registers()?;
+        let mut channel = self.channel.clone();
+        self.read(param.id, Table::HoldingRegisters, range, || async move {
+            channel.read_holding_registers(param, range).await
+        })
+        .await
+    }
+
+    /// Read input registers from the cache or from the server
+    pub async fn read_input_registers(
+        &mut self,
+        param: RequestParam,
+        range: AddressRange,
+    ) -> Result<Vec<Indexed<u16>>, RequestError> {
+        range.of_read_registers()?;
+        let mut channel = self.channel.clone();
+        self.read(param.id, Table::InputRegisters, range, || async move {
+            channel.read_input_registers(param, range).await
+        })
+        .await
+    }
+
+    /// Write a single coil on the server and invalidate its cache entry
+    pub async fn write_single_coil(
+        &mut self,
+        param: RequestParam,
+        request: Indexed<bool>,
+    ) -> Result<Indexed<bool>, RequestError> {
+        let range = AddressRange::try_from(request.index, 1)?;
+        let mut channel = self.channel.clone();
+        self.write::<bool, _>(param.id, Table::Coils, range, async move {
+            channel.write_single_coil(param, request).await
+        })
+        .await
+    }
+
+    /// Write a single register on the server and invalidate its cache entry
+    pub async fn write_single_register(
+        &mut self,
+        param: RequestParam,
+        request: Indexed<u16>,
+    ) -> Result<Indexed<u16>, RequestError> {
+        let range = AddressRange::try_from(request.index, 1)?;
+        let mut channel = self.channel.clone();
+        self.write::<u16, _>(param.id, Table::HoldingRegisters, range, async move {
+            channel.write_single_register(param, request).await
+        })
+        .await
+    }
+
+    /// Write multiple contiguous coils on the server and invalidate their cache entries
+    pub async fn write_multiple_coils(
+        &mut self,
+        param: RequestParam,
+        request: WriteMultiple<bool>,
+    ) -> Result<AddressRange, RequestError> {
+        let mut channel = self.channel.clone();
+        self.write::<bool, _>(param.id, Table::Coils, request.range, async move {
+            channel.write_multiple_coils(param, request).await
+        })
+        .await
+    }
+
+    /// Write multiple contiguous registers on the server and invalidate their cache entries
+    pub async fn write_multiple_registers(
+        &mut self,
+        param: RequestParam,
+        request: WriteMultiple<u16>,
+    ) -> Result<AddressRange, RequestError> {
+        let mut channel = self.channel.clone();
+        self.write::<u16, _>(
+            param.id,
+            Table::HoldingRegisters,
+            request.range,
+            async move { channel.write_multiple_registers(param, request).await },
+        )
+        .await
+    }
+
+    async fn write<T: CachedValue, R>(
+        &self,
+        id: UnitId,
+        table: Table,
+        range: AddressRange,
+        write: impl Future<Output = R>,
+    ) -> R {
+        self.invalidate::<T>(id, table, range);
+        let result = write.await;
+        // a read queued on the channel ahead of the write may have stored the old values
+        self.invalidate::<T>(id, table, range);
+        result
+    }
+
+    fn invalidate<T: CachedValue>(&self, id: UnitId, table: Table, range: AddressRange) {
+        let mut state = self.state.lock().unwrap();
+        // reads that are already in progress may return the old values and must not be stored
+        state.generation += 1;
+        T::cache(&mut state).invalidate(id, table, range);
+    }
+
+    fn begin_read<T: CachedValue>(&self, key: RangeKey) -> Begin<T> {
+        let mut state = self.state.lock().unwrap();
+        let generation = state.generation;
+        state.next_token += 1;
+        let token = state.next_token;
+        let cache = T::cache(&mut state);
+
+        if let Some(values) = cache.lookup(key, self.ttl, Instant::now()) {
+            return Begin::Cached(values);
+        }
+
+        if let Some(in_flight) = cache.in_flight.get_mut(&key) {
+            let (tx, rx) = oneshot::channel();
+            in_flight.waiters.push(tx);
+            return Begin::Wait(rx);
+        }
+
+        cache.in_flight.insert(
+            key,
+            InFlight {
+                token,
+                waiters: Vec::new(),
+            },
+        );
+        Begin::Request(token, generation)
+    }
+
+    async fn read<T, F, R>(
+        &self,
+        id: UnitId,
+        table: Table,
+        range: AddressRange,
+        request: F,
+    ) -> Result<Vec<Indexed<T>>, RequestError>
+    where
+        T: CachedValue,
+        F: FnOnce() -> R,
+        R: Future<Output = Result<Vec<Indexed<T>>, RequestError>>,
+    {
+        // reject empty ranges that would always be found in the cache
+        AddressRange::try_from(range.start, range.count)?;
+        let key = (id, table, range.start, range.count);
+
+        let (token, generation) = loop {
+            match self.begin_read::<T>(key) {
+                Begin::Cached(values) => return Ok(values),
+                Begin::Wait(rx) => {
+                    // the read is retried if the other read was cancelled or invalidated by a write
+                    if let Ok(result) = rx.await {
+                        return result;
+                    }
+                }
+                Begin::Request(token, generation) => break (token, generation),
+            }
+        };
+
+        let mut guard = InFlightGuard::<T> {
+            state: &self.state,
+            key,
+            token,
+            done: false,
+            _value: std::marker::PhantomData,
+        };
+
+        let result = request().await;
+
+        let waiters = {
+            let mut state = self.state.lock().unwrap();
+            let unchanged = state.generation == generation;
+            let cache = T::cache(&mut state);
+
+            if let (Ok(values), true) = (&result, unchanged) {
+                cache.store(id, table, values, Instant::now());
+            }
+
+            match cache.in_flight.get(&key) {
+                Some(x) if x.token == token => cache
+                    .in_flight
+                    .remove(&key)
+                    .map(|x| x.waiters)
+                    .unwrap_or_default(),
+                _ => Vec::new(),
+            }
+        };
+        guard.done = true;
+
+        for waiter in waiters {
+            let _ = waiter.send(result.clone());
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn cached(ttl: Duration) -> CachedChannel {
+        let (tx, _rx) = tokio::sync::mpsc::channel(1);
+        CachedChannel::new(Channel::new(tx), ttl)
+    }
+
+    fn range(start: u16, count: u16) -> AddressRange {
+        AddressRange::try_from(start, count).unwrap()
+    }
+
+    fn registers(range: AddressRange, value: u16) -> Vec<Indexed<u16>> {
+        range.iter().map(|x| Indexed::new(x, value)).collect()
+    }
+
+    #[test]
+    fn entries_expire_after_the_ttl() {
+        let mut cache = Cache::default();
+        let id = UnitId::new(1);
+        let key = (id, Table::HoldingRegisters, 0, 2);
+        let ttl = Duration::from_millis(100);
+        let now = Instant::now();
+
+        cache.store(id, Table::HoldingRegisters, &registers(range(0, 2), 1), now);
+        assert_eq!(cache.lookup(key, ttl, now), Some(registers(range(0, 2), 1)));
+        assert_eq!(cache.lookup(key, ttl, now + ttl), None);
+
+        // a range that is only partially cached is a miss
+        assert_eq!(
+            cache.lookup((id, Table::HoldingRegisters, 1, 2), ttl, now),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn coalesces_concurrent_identical_reads() {
+        let cache = cached(Duration::ZERO);
+        let count = AtomicUsize::new(0);
+        let response = tokio::sync::Notify::new();
+
+        let read = || {
+            let count = &count;
+            let response = &response;
+            cache.read(
+                UnitId::new(1),
+                Table::InputRegisters,
+                range(0, 1),
+                move || {
+                    count.fetch_add(1, Ordering::SeqCst);
+                    async move {
+                        response.notified().await;
+                        Ok(registers(range(0, 1), 7))
+                    }
+                },
+            )
+        };
+
+        let (first, second, _) = tokio::join!(read(), read(), async {
+            tokio::task::yield_now().await;
+            response.notify_one();
+        });
+
+        assert_eq!(first, Ok(registers(range(0, 1), 7)));
+        assert_eq!(second, first);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn writes_invalidate_the_written_range() {
+        let cache = cached(Duration::from_secs(60));
+        let id = UnitId::new(1);
+
+        let read = |value| {
+            cache.read(
+                id,
+                Table::HoldingRegisters,
+                range(0, 3),
+                move || async move { Ok(registers(range(0, 3), value)) },
+            )
+        };
+
+        read(1).await.unwrap();
+        cache.invalidate::<u16>(id, Table::HoldingRegisters, range(2, 1));
+        assert_eq!(read(2).await, Ok(registers(range(0, 3), 2)));
+
+        // other units and tables are not affected
+        cache.invalidate::<u16>(UnitId::new(2), Table::HoldingRegisters, range(0, 3));
+        cache.invalidate::<u16>(id, Table::InputRegisters, range(0, 3));
+        assert_eq!(read(3).await, Ok(registers(range(0, 3), 2)));
+    }
+
+    #[tokio::test]
+    async fn reads_that_race_a_write_are_not_cached() {
+        let cache = cached(Duration::from_secs(60));
+        let id = UnitId::new(1);
+        let written = tokio::sync::Notify::new();
+
+        let read = |value| {
+            cache.read(
+                id,
+                Table::HoldingRegisters,
+                range(0, 1),
+                move || async move { Ok(registers(range(0, 1), value)) },
+            )
+        };
+
+        // the read starts after the write is issued, but the device answers it first
+        let (write, stale, _) = tokio::join!(
+            cache.write::<u16, _>(id, Table::HoldingRegisters, range(0, 1), async {
+                written.notified().await;
+                Ok::<(), RequestError>(())
+            }),
+            read(1),
+            async {
+                tokio::task::yield_now().await;
+                written.notify_one();
+            }
+        );
+
+        assert_eq!(write, Ok(()));
+        assert_eq!(stale, Ok(registers(range(0, 1), 1)));
+        assert_eq!(read(2).await, Ok(registers(range(0, 1), 2)));
+    }
+}
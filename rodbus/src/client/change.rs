@@ -0,0 +1,401 @@
+use std::collections::BTreeMap;
+
+use crate::client::{Channel, Listener, RequestParam};
+use crate::error::RequestError;
+use crate::types::{AddressRange, Indexed, Table, UnitId};
+
+/// Previous and current value of a point that changed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Change<T> {
+    /// Last reported value or `None` if the point is reported for the first time
+    pub old: Option<T>,
+    /// Value that was just read from the device
+    pub new: T,
+}
+
+/// Communication quality of a polled range
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quality {
+    /// The last poll of the range succeeded
+    Good,
+    /// The last poll of the range failed with [`RequestError::ResponseTimeout`]
+    Timeout,
+}
+
+/// Event reported by a [`ChangeDetector`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeEvent {
+    /// A coil or discrete input changed value
+    Bit(UnitId, Table, Indexed<Change<bool>>),
+    /// A holding or input register changed by more than its deadband
+    Register(UnitId, Table, Indexed<Change<u16>>),
+    /// The communication quality of a polled range changed
+    Quality(UnitId, Table, AddressRange, Quality),
+}
+
+type PointKey = (UnitId, Table, u16);
+type RangeKey = (UnitId, Table, u16, u16);
+
+/// Report-by-exception layer on top of a [`Channel`]
+///
+/// Keeps the last reported value of every polled point, keyed by unit id, table and address,
+/// and only forwards values that changed to the supplied [`Listener`]. Registers are only
+/// reported when they differ from the last reported value by more than their deadband.
+///
+/// Polls that fail with [`RequestError::ResponseTimeout`] mark the polled range with
+/// [`Quality::Timeout`]. The next successful poll of the same range reports [`Quality::Good`].
+pub struct ChangeDetector {
+    channel: Channel,
+    listener: Box<dyn Listener<ChangeEvent>>,
+    default_deadband: u16,
+    deadbands: BTreeMap<PointKey, u16>,
+    bits: BTreeMap<PointKey, bool>,
+    registers: BTreeMap<PointKey, u16>,
+    quality: BTreeMap<RangeKey, Quality>,
+}
+
+impl ChangeDetector {
+    /// Create a detector that polls using `channel` and reports events to `listener`
+    pub fn new(channel: Channel, listener: Box<dyn Listener<ChangeEvent>>) -> Self {
+        Self {
+            channel,
+            listener,
+            default_deadband: 0,
+            deadbands: BTreeMap::new(),
+            bits: BTreeMap::new(),
+            registers: BTreeMap::new(),
+            quality: BTreeMap::new(),
+        }
+    }
+
+    /// Set the deadband used for registers without a specific deadband. Defaults to zero.
+    pub fn set_default_deadband(&mut self, deadband: u16) {
+        self.default_deadband = deadband;
+    }
+
+    /// Set the deadband of a range of registers
+    ///
+    /// Deadbands only apply to [`Table::HoldingRegisters`] and [`Table::InputRegisters`].
+    pub fn set_deadband(&mut self, id: UnitId, table: Table, range: AddressRange, deadband: u16) {
+        for address in range.iter() {
+            self.deadbands.insert((id, table, address), deadband);
+        }
+    }
+
+    /// Forget all the stored values and qualities so that the next poll reports every point
+    pub fn clear(&mut self) {
+        self.bits.clear();
+        self.registers.clear();
+        self.quality.clear();
+    }
+
+    /// Read coils from the server and report the changes
+    pub async fn poll_coils(
+        &mut self,
+        param: RequestParam,
+        range: AddressRange,
+    ) -> Result<(), RequestError> {
+        let result = self.channel.read_coils(param, range).await;
+        self.report_bits(param.id, Table::Coils, range, result)
+            .await
+    }
+
+    /// Read discrete inputs from the server and report the changes
+    pub async fn poll_discrete_inputs(
+        &mut self,
+        param: RequestParam,
+        range: AddressRange,
+    ) -> Result<(), RequestError> {
+        let result = self.channel.read_discrete_inputs(param, range).await;
+        self.report_bits(param.id, Table::DiscreteInputs, range, result)
+            .await
+    }
+
+    /// Read holding registers from the server and report the changes
+    pub async fn poll_holding_registers(
+        &mut self,
+        param: RequestParam,
+        range: AddressRange,
+    ) -> Result<(), RequestError> {
+        let result = self.channel.read_holding_registers(param, range).await;
+        self.report_registers(param.id, Table::HoldingRegisters, range, result)
+            .await
+    }
+
+    /// Read input registers from the server and report the changes
+    pub async fn poll_input_registers(
+        &mut self,
+        param: RequestParam,
+        range: AddressRange,
+    ) -> Result<(), RequestError> {
+        let result = self.channel.read_input_registers(param, range).await;
+        self.report_registers(param.id, Table::InputRegisters, range, result)
+            .await
+    }
+
+    async fn report_bits(
+        &mut self,
+        id: UnitId,
+        table: Table,
+        range: AddressRange,
+        result: Result<Vec<Indexed<bool>>, RequestError>,
+    ) -> Result<(), RequestError> {
+        let mut events = Vec::new();
+        let result = self.detect_bits(id, table, range, result, &mut events);
+        self.report(events).await;
+        result
+    }
+
+    async fn report_registers(
+        &mut self,
+        id: UnitId,
+        table: Table,
+        range: AddressRange,
+        result: Result<Vec<Indexed<u16>>, RequestError>,
+    ) -> Result<(), RequestError> {
+        let mut events = Vec::new();
+        let result = self.detect_registers(id, table, range, result, &mut events);
+        self.report(events).await;
+        result
+    }
+
+    async fn report(&mut self, events: Vec<ChangeEvent>) {
+        for event in events {
+            self.listener.update(event).get().await;
+        }
+    }
+
+    fn detect_bits(
+        &mut self,
+        id: UnitId,
+        table: Table,
+        range: AddressRange,
+        result: Result<Vec<Indexed<bool>>, RequestError>,
+        events: &mut Vec<ChangeEvent>,
+    ) -> Result<(), RequestError> {
+        let values = self.check_quality(id, table, range, result, events)?;
+
+        for value in values {
+            let old = self.bits.insert((id, table, value.index), value.value);
+            if old != Some(value.value) {
+                let change = Change {
+                    old,
+                    new: value.value,
+                };
+                events.push(ChangeEvent::Bit(
+                    id,
+                    table,
+                    Indexed::new(value.index, change),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn detect_registers(
+        &mut self,
+        id: UnitId,
+        table: Table,
+        range: AddressRange,
+        result: Result<Vec<Indexed<u16>>, RequestError>,
+        events: &mut Vec<ChangeEvent>,
+    ) -> Result<(), RequestError> {
+        let values = self.check_quality(id, table, range, result, events)?;
+
+        for value in values {
+            let key = (id, table, value.index);
+            let deadband = self
+                .deadbands
+                .get(&key)
+                .copied()
+                .unwrap_or(self.default_deadband);
+
+            let old = self.registers.get(&key).copied();
+            if let Some(old) = old {
+                // the baseline stays at the last reported value so that slow drifts are still reported
+                if old.abs_diff(value.value) <= deadband {
+                    continue;
+                }
+            }
+
+            self.registers.insert(key, value.value);
+            let change = Change {
+                old,
+                new: value.value,
+            };
+            events.push(ChangeEvent::Register(
+                id,
+                table,
+                Indexed::new(value.index, change),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn check_quality<T>(
+        &mut self,
+        id: UnitId,
+        table: Table,
+        range: AddressRange,
+        result: Result<T, RequestError>,
+        events: &mut Vec<ChangeEvent>,
+    ) -> Result<T, RequestError> {
+        let quality = match &result {
+            Ok(_) => Quality::Good,
+            Err(RequestError::ResponseTimeout) => Quality::Timeout,
+            // other errors don't say anything about the quality of the data
+            Err(_) => return result,
+        };
+
+        let previous = self
+            .quality
+            .insert((id, table, range.start, range.count), quality)
+            .unwrap_or(Quality::Good);
+
+        if previous != quality {
+            events.push(ChangeEvent::Quality(id, table, range, quality));
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::NullListener;
+
+    fn detector() -> ChangeDetector {
+        let (tx, _rx) = tokio::sync::mpsc::channel(1);
+        ChangeDetector::new(Channel::new(tx), NullListener::create())
+    }
+
+    fn range(start: u16, count: u16) -> AddressRange {
+        AddressRange::try_from(start, count).unwrap()
+    }
+
+    fn bit(index: u16, old: Option<bool>, new: bool) -> ChangeEvent {
+        ChangeEvent::Bit(
+            UnitId::new(1),
+            Table::Coils,
+            Indexed::new(index, Change { old, new }),
+        )
+    }
+
+    fn register(index: u16, old: Option<u16>, new: u16) -> ChangeEvent {
+        ChangeEvent::Register(
+            UnitId::new(1),
+            Table::HoldingRegisters,
+            Indexed::new(index, Change { old, new }),
+        )
+    }
+
+    #[test]
+    fn reports_all_bits_on_first_poll_and_only_changes_afterwards() {
+        let mut detector = detector();
+        let mut events = Vec::new();
+
+        detector
+            .detect_bits(
+                UnitId::new(1),
+                Table::Coils,
+                range(0, 2),
+                Ok(vec![Indexed::new(0, true), Indexed::new(1, false)]),
+                &mut events,
+            )
+            .unwrap();
+        assert_eq!(events, vec![bit(0, None, true), bit(1, None, false)]);
+
+        events.clear();
+        detector
+            .detect_bits(
+                UnitId::new(1),
+                Table::Coils,
+                range(0, 2),
+                Ok(vec![Indexed::new(0, true), Indexed::new(1, true)]),
+                &mut events,
+            )
+            .unwrap();
+        assert_eq!(events, vec![bit(1, Some(false), true)]);
+    }
+
+    #[test]
+    fn registers_within_deadband_are_not_reported() {
+        let mut detector = detector();
+        detector.set_deadband(UnitId::new(1), Table::HoldingRegisters, range(0, 1), 5);
+        let mut events = Vec::new();
+
+        for value in [100, 105, 95, 94] {
+            detector
+                .detect_registers(
+                    UnitId::new(1),
+                    Table::HoldingRegisters,
+                    range(0, 1),
+                    Ok(vec![Indexed::new(0, value)]),
+                    &mut events,
+                )
+                .unwrap();
+        }
+
+        assert_eq!(
+            events,
+            vec![register(0, None, 100), register(0, Some(100), 94)]
+        );
+    }
+
+    #[test]
+    fn reports_quality_transitions_on_timeout_and_recovery() {
+        let mut detector = detector();
+        let mut events = Vec::new();
+        let id = UnitId::new(1);
+
+        for _ in 0..2 {
+            assert_eq!(
+                detector.detect_registers(
+                    id,
+                    Table::InputRegisters,
+                    range(0, 1),
+                    Err(RequestError::ResponseTimeout),
+                    &mut events,
+                ),
+                Err(RequestError::ResponseTimeout)
+            );
+        }
+
+        // other errors don't change the quality
+        assert!(detector
+            .detect_registers(
+                id,
+                Table::InputRegisters,
+                range(0, 1),
+                Err(RequestError::NoConnection),
+                &mut events,
+            )
+            .is_err());
+
+        detector
+            .detect_registers(
+                id,
+                Table::InputRegisters,
+                range(0, 1),
+                Ok(vec![Indexed::new(0, 7)]),
+                &mut events,
+            )
+            .unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                ChangeEvent::Quality(id, Table::InputRegisters, range(0, 1), Quality::Timeout),
+                ChangeEvent::Quality(id, Table::InputRegisters, range(0, 1), Quality::Good),
+                ChangeEvent::Register(
+                    id,
+                    Table::InputRegisters,
+                    Indexed::new(0, Change { old: None, new: 7 })
+                ),
+            ]
+        );
+    }
+}
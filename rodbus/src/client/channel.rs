@@ -1,3 +1,4 @@
+use std::sync::Arc;
 use std::thread::available_parallelism;
 use std::time::Duration;
 
@@ -6,9 +7,15 @@ use crate::client::requests::read_bits::ReadBits;
 use crate::client::requests::read_registers::ReadRegisters;
 use crate::client::requests::write_multiple::{MultipleWriteRequest, WriteMultiple};
 use crate::client::requests::write_single::SingleWrite;
+use crate::client::statistics::ChannelStatistics;
+use crate::codec::{
+    decode_ascii, decode_values, encode_ascii, encode_values, RegisterCodec, WordOrder,
+};
 use crate::{error::*, ReadDeviceInfoBlock, DeviceIdentification};
 use crate::types::{AddressRange, BitIterator, Indexed, RegisterIterator, UnitId};
-use crate::DecodeLevel;
+use crate::observer::TrafficObserver;
+use crate::retry::RetryPolicy;
+use crate::{DecodeFormat, DecodeLevel};
 
 use super::requests::read_device_identification::ReadDeviceIdentification;
 
@@ -16,6 +23,7 @@ use super::requests::read_device_identification::ReadDeviceIdentification;
 #[derive(Debug, Clone)]
 pub struct Channel {
     pub(crate) tx: tokio::sync::mpsc::Sender<Command>,
+    retry: RetryPolicy,
 }
 
 /// Request parameters to dispatch the request to the proper device
@@ -38,6 +46,23 @@ impl RequestParam {
 }
 
 impl Channel {
+    pub(crate) fn new(tx: tokio::sync::mpsc::Sender<Command>) -> Self {
+        Self {
+            tx,
+            retry: RetryPolicy::none(),
+        }
+    }
+
+    /// Create a handle to the same channel whose requests are retried according to `policy`
+    ///
+    /// Other handles to the channel, including this one, keep their own policy.
+    pub fn with_retry_policy(&self, policy: RetryPolicy) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            retry: policy,
+        }
+    }
+
     #[cfg(feature = "serial")]
     pub(crate) fn spawn_rtu(
         path: &str,
@@ -85,7 +110,7 @@ impl Channel {
             .instrument(tracing::info_span!("Modbus-Client-RTU", "port" = ?path))
             .await;
         };
-        (Channel { tx }, task)
+        (Channel::new(tx), task)
     }
 
     /// Enable communications
@@ -100,6 +125,21 @@ impl Channel {
         Ok(())
     }
 
+    /// Retrieve a snapshot of the communication statistics
+    pub async fn statistics(&self) -> Result<ChannelStatistics, Shutdown> {
+        let (tx, rx) = tokio::sync::oneshot::channel::<ChannelStatistics>();
+        self.tx.send(Command::Statistics(tx)).await?;
+        rx.await.map_err(|_| Shutdown)
+    }
+
+    /// Reset all the communication statistics to zero
+    pub async fn reset_statistics(&self) -> Result<(), Shutdown> {
+        self.tx
+            .send(Command::Setting(Setting::ResetStatistics))
+            .await?;
+        Ok(())
+    }
+
     /// Read coils from the server
     pub async fn read_coils(
         &mut self,
@@ -109,6 +149,7 @@ impl Channel {
         let (tx, rx) = tokio::sync::oneshot::channel::<Result<Vec<Indexed<bool>>, RequestError>>();
         let request = wrap(
             param,
+            self.retry,
             RequestDetails::ReadCoils(ReadBits::channel(range.of_read_bits()?, tx)),
         );
         self.tx.send(request).await?;
@@ -124,6 +165,7 @@ impl Channel {
         let (tx, rx) = tokio::sync::oneshot::channel::<Result<Vec<Indexed<bool>>, RequestError>>();
         let request = wrap(
             param,
+            self.retry,
             RequestDetails::ReadDiscreteInputs(ReadBits::channel(range.of_read_bits()?, tx)),
         );
         self.tx.send(request).await?;
@@ -139,6 +181,7 @@ impl Channel {
         let (tx, rx) = tokio::sync::oneshot::channel::<Result<Vec<Indexed<u16>>, RequestError>>();
         let request = wrap(
             param,
+            self.retry,
             RequestDetails::ReadHoldingRegisters(ReadRegisters::channel(
                 range.of_read_registers()?,
                 tx,
@@ -157,6 +200,7 @@ impl Channel {
         let (tx, rx) = tokio::sync::oneshot::channel::<Result<Vec<Indexed<u16>>, RequestError>>();
         let request = wrap(
             param,
+            self.retry,
             RequestDetails::ReadInputRegisters(ReadRegisters::channel(
                 range.of_read_registers()?,
                 tx,
@@ -175,6 +219,7 @@ impl Channel {
         let (tx, rx) = tokio::sync::oneshot::channel::<Result<DeviceIdentification, RequestError>>();
         let request = wrap(
             param,
+            self.retry,
             RequestDetails::ReadDeviceIdentification(ReadDeviceIdentification::channel(
                 device_params,
                 tx
@@ -194,6 +239,7 @@ impl Channel {
         let (tx, rx) = tokio::sync::oneshot::channel::<Result<Indexed<bool>, RequestError>>();
         let request = wrap(
             param,
+            self.retry,
             RequestDetails::WriteSingleCoil(SingleWrite::new(request, Promise::channel(tx))),
         );
         self.tx.send(request).await?;
@@ -209,6 +255,7 @@ impl Channel {
         let (tx, rx) = tokio::sync::oneshot::channel::<Result<Indexed<u16>, RequestError>>();
         let request = wrap(
             param,
+            self.retry,
             RequestDetails::WriteSingleRegister(SingleWrite::new(request, Promise::channel(tx))),
         );
         self.tx.send(request).await?;
@@ -224,6 +271,7 @@ impl Channel {
         let (tx, rx) = tokio::sync::oneshot::channel::<Result<AddressRange, RequestError>>();
         let request = wrap(
             param,
+            self.retry,
             RequestDetails::WriteMultipleCoils(MultipleWriteRequest::new(
                 request,
                 Promise::channel(tx),
@@ -242,6 +290,7 @@ impl Channel {
         let (tx, rx) = tokio::sync::oneshot::channel::<Result<AddressRange, RequestError>>();
         let request = wrap(
             param,
+            self.retry,
             RequestDetails::WriteMultipleRegisters(MultipleWriteRequest::new(
                 request,
                 Promise::channel(tx),
@@ -251,6 +300,222 @@ impl Channel {
         rx.await?
     }
 
+    /// Write a single coil on the server and read it back to confirm the value
+    ///
+    /// Fails with [`VerifyError::Mismatch`] if the value read back differs.
+    /// The mismatch carries a list of indices, so it is reported in a [`VerifyError`] instead of a
+    /// [`RequestError`] variant, which must remain `Copy`.
+    pub async fn write_single_coil_verified(
+        &mut self,
+        param: RequestParam,
+        request: Indexed<bool>,
+    ) -> Result<Indexed<bool>, VerifyError> {
+        let response = self.write_single_coil(param, request).await?;
+        let range = AddressRange::try_from(request.index, 1)?;
+        let actual = self.read_coils(param, range).await?;
+        verify([request], actual)?;
+        Ok(response)
+    }
+
+    /// Write a single register on the server and read it back to confirm the value
+    ///
+    /// Fails with [`VerifyError::Mismatch`] if the value read back differs.
+    /// The mismatch carries a list of indices, so it is reported in a [`VerifyError`] instead of a
+    /// [`RequestError`] variant, which must remain `Copy`.
+    pub async fn write_single_register_verified(
+        &mut self,
+        param: RequestParam,
+        request: Indexed<u16>,
+    ) -> Result<Indexed<u16>, VerifyError> {
+        let response = self.write_single_register(param, request).await?;
+        let range = AddressRange::try_from(request.index, 1)?;
+        let actual = self.read_holding_registers(param, range).await?;
+        verify([request], actual)?;
+        Ok(response)
+    }
+
+    /// Write multiple contiguous coils on the server and read them back to confirm the values
+    ///
+    /// Fails with [`VerifyError::Mismatch`] listing the indices of the values read back that differ.
+    /// The mismatch carries a list of indices, so it is reported in a [`VerifyError`] instead of a
+    /// [`RequestError`] variant, which must remain `Copy`.
+    pub async fn write_multiple_coils_verified(
+        &mut self,
+        param: RequestParam,
+        request: WriteMultiple<bool>,
+    ) -> Result<AddressRange, VerifyError> {
+        let expected: Vec<Indexed<bool>> = request.iter().collect();
+        let range = self.write_multiple_coils(param, request).await?;
+        let actual = self.read_coils(param, range).await?;
+        verify(expected, actual)?;
+        Ok(range)
+    }
+
+    /// Write multiple contiguous registers on the server and read them back to confirm the values
+    ///
+    /// Fails with [`VerifyError::Mismatch`] listing the indices of the values read back that differ.
+    /// The mismatch carries a list of indices, so it is reported in a [`VerifyError`] instead of a
+    /// [`RequestError`] variant, which must remain `Copy`.
+    pub async fn write_multiple_registers_verified(
+        &mut self,
+        param: RequestParam,
+        request: WriteMultiple<u16>,
+    ) -> Result<AddressRange, VerifyError> {
+        let expected: Vec<Indexed<u16>> = request.iter().collect();
+        let range = self.write_multiple_registers(param, request).await?;
+        let actual = self.read_holding_registers(param, range).await?;
+        verify(expected, actual)?;
+        Ok(range)
+    }
+
+    /// Read a value stored in consecutive holding registers starting at `address`
+    pub async fn read_holding_value<T: RegisterCodec>(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        order: WordOrder,
+    ) -> Result<T, RequestError> {
+        let range = AddressRange::try_from(address, T::REGISTERS)?;
+        let registers = self.read_holding_registers(param, range).await?;
+        decode_value(registers, order)
+    }
+
+    /// Read a value stored in consecutive input registers starting at `address`
+    pub async fn read_input_value<T: RegisterCodec>(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        order: WordOrder,
+    ) -> Result<T, RequestError> {
+        let range = AddressRange::try_from(address, T::REGISTERS)?;
+        let registers = self.read_input_registers(param, range).await?;
+        decode_value(registers, order)
+    }
+
+    /// Write a value to consecutive holding registers starting at `address`
+    pub async fn write_value<T: RegisterCodec>(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        value: T,
+        order: WordOrder,
+    ) -> Result<AddressRange, RequestError> {
+        let request = encode_values(address, &[value], order)?;
+        self.write_multiple_registers(param, request).await
+    }
+
+    /// Read a `f32` stored in holding registers starting at `address`
+    pub async fn read_f32(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        order: WordOrder,
+    ) -> Result<f32, RequestError> {
+        self.read_holding_value(param, address, order).await
+    }
+
+    /// Write a `f32` to holding registers starting at `address`
+    pub async fn write_f32(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        value: f32,
+        order: WordOrder,
+    ) -> Result<AddressRange, RequestError> {
+        self.write_value(param, address, value, order).await
+    }
+
+    /// Read a `u32` stored in holding registers starting at `address`
+    pub async fn read_u32(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        order: WordOrder,
+    ) -> Result<u32, RequestError> {
+        self.read_holding_value(param, address, order).await
+    }
+
+    /// Write a `u32` to holding registers starting at `address`
+    pub async fn write_u32(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        value: u32,
+        order: WordOrder,
+    ) -> Result<AddressRange, RequestError> {
+        self.write_value(param, address, value, order).await
+    }
+
+    /// Read a `i64` stored in holding registers starting at `address`
+    pub async fn read_i64(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        order: WordOrder,
+    ) -> Result<i64, RequestError> {
+        self.read_holding_value(param, address, order).await
+    }
+
+    /// Write a `i64` to holding registers starting at `address`
+    pub async fn write_i64(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        value: i64,
+        order: WordOrder,
+    ) -> Result<AddressRange, RequestError> {
+        self.write_value(param, address, value, order).await
+    }
+
+    /// Read a `f64` stored in holding registers starting at `address`
+    pub async fn read_f64(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        order: WordOrder,
+    ) -> Result<f64, RequestError> {
+        self.read_holding_value(param, address, order).await
+    }
+
+    /// Write a `f64` to holding registers starting at `address`
+    pub async fn write_f64(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        value: f64,
+        order: WordOrder,
+    ) -> Result<AddressRange, RequestError> {
+        self.write_value(param, address, value, order).await
+    }
+
+    /// Read an ASCII string stored in a range of holding registers
+    ///
+    /// See [`decode_ascii`] for how `order` applies to strings.
+    pub async fn read_ascii(
+        &mut self,
+        param: RequestParam,
+        range: AddressRange,
+        order: WordOrder,
+    ) -> Result<String, RequestError> {
+        let registers = self.read_holding_registers(param, range).await?;
+        Ok(decode_ascii(registers, order)?)
+    }
+
+    /// Write an ASCII string to `count` holding registers starting at `address`
+    ///
+    /// See [`encode_ascii`] for how `order` applies to strings.
+    pub async fn write_ascii(
+        &mut self,
+        param: RequestParam,
+        address: u16,
+        value: &str,
+        count: u16,
+        order: WordOrder,
+    ) -> Result<AddressRange, RequestError> {
+        let request = encode_ascii(address, value, count, order)?;
+        self.write_multiple_registers(param, request).await
+    }
+
     /// Dynamically change the protocol decoding level of the channel
     pub async fn set_decode_level(&mut self, level: DecodeLevel) -> Result<(), Shutdown> {
         self.tx
@@ -258,6 +523,25 @@ impl Channel {
             .await?;
         Ok(())
     }
+
+    /// Dynamically change the format of the decoded data of the channel
+    pub async fn set_decode_format(&mut self, format: DecodeFormat) -> Result<(), Shutdown> {
+        self.tx
+            .send(Command::Setting(Setting::DecodeFormat(format)))
+            .await?;
+        Ok(())
+    }
+
+    /// Set or clear the observer that receives all the traffic of the channel
+    pub async fn set_traffic_observer(
+        &mut self,
+        observer: Option<Arc<dyn TrafficObserver>>,
+    ) -> Result<(), Shutdown> {
+        self.tx
+            .send(Command::Setting(Setting::TrafficObserver(observer)))
+            .await?;
+        Ok(())
+    }
 }
 
 /// Callback-based session
@@ -269,6 +553,7 @@ impl Channel {
 pub struct CallbackSession {
     tx: tokio::sync::mpsc::Sender<Command>,
     param: RequestParam,
+    retry: RetryPolicy,
 }
 
 impl CallbackSession {
@@ -277,6 +562,7 @@ impl CallbackSession {
         CallbackSession {
             tx: channel.tx,
             param,
+            retry: channel.retry,
         }
     }
 
@@ -323,6 +609,7 @@ impl CallbackSession {
     {
         self.send(wrap(
             self.param,
+            self.retry,
             RequestDetails::WriteSingleCoil(SingleWrite::new(value, Promise::new(callback))),
         ))
         .await;
@@ -335,6 +622,7 @@ impl CallbackSession {
     {
         self.send(wrap(
             self.param,
+            self.retry,
             RequestDetails::WriteSingleRegister(SingleWrite::new(value, Promise::new(callback))),
         ))
         .await;
@@ -347,6 +635,7 @@ impl CallbackSession {
     {
         self.send(wrap(
             self.param,
+            self.retry,
             RequestDetails::WriteMultipleRegisters(MultipleWriteRequest::new(
                 value,
                 Promise::new(callback),
@@ -362,6 +651,7 @@ impl CallbackSession {
     {
         self.send(wrap(
             self.param,
+            self.retry,
             RequestDetails::WriteMultipleCoils(MultipleWriteRequest::new(
                 value,
                 Promise::new(callback),
@@ -380,8 +670,12 @@ impl CallbackSession {
             Ok(x) => x,
             Err(err) => return promise.failure(err.into()),
         };
-        self.send(wrap(self.param, wrap_req(ReadBits::new(range, promise))))
-            .await;
+        self.send(wrap(
+            self.param,
+            self.retry,
+            wrap_req(ReadBits::new(range, promise)),
+        ))
+        .await;
     }
 
     async fn read_registers<C, W>(&mut self, range: AddressRange, callback: C, wrap_req: W)
@@ -396,6 +690,7 @@ impl CallbackSession {
         };
         self.send(wrap(
             self.param,
+            self.retry,
             wrap_req(ReadRegisters::new(range, promise)),
         ))
         .await;
@@ -407,6 +702,77 @@ impl CallbackSession {
     }
 }
 
-fn wrap(param: RequestParam, details: RequestDetails) -> Command {
-    Command::Request(Request::new(param.id, param.response_timeout, details))
+fn decode_value<T: RegisterCodec>(
+    registers: Vec<Indexed<u16>>,
+    order: WordOrder,
+) -> Result<T, RequestError> {
+    match decode_values(registers, order)?.pop() {
+        Some(x) => Ok(x.value),
+        None => Err(AduParseError::InsufficientBytes.into()),
+    }
+}
+
+fn verify<T, I>(expected: I, actual: Vec<Indexed<T>>) -> Result<(), VerifyError>
+where
+    T: PartialEq,
+    I: IntoIterator<Item = Indexed<T>>,
+{
+    let expected: Vec<Indexed<T>> = expected.into_iter().collect();
+
+    // values missing from, or unexpected in, the read back also count as mismatches
+    let mismatches: Vec<u16> = (0..expected.len().max(actual.len()))
+        .filter_map(|i| match (expected.get(i), actual.get(i)) {
+            (Some(expected), Some(actual)) if expected == actual => None,
+            (Some(expected), _) => Some(expected.index),
+            (None, Some(actual)) => Some(actual.index),
+            (None, None) => None,
+        })
+        .collect();
+
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(VerifyError::Mismatch(mismatches))
+    }
+}
+
+fn wrap(param: RequestParam, retry: RetryPolicy, details: RequestDetails) -> Command {
+    Command::Request(Request::new(param, retry, details))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verification_reports_the_indices_that_differ() {
+        let expected = vec![
+            Indexed::new(10, 1u16),
+            Indexed::new(11, 2),
+            Indexed::new(12, 3),
+        ];
+        assert_eq!(verify(expected.clone(), expected.clone()), Ok(()));
+
+        let actual = vec![
+            Indexed::new(10, 0u16),
+            Indexed::new(11, 2),
+            Indexed::new(12, 0),
+        ];
+        assert_eq!(
+            verify(expected.clone(), actual),
+            Err(VerifyError::Mismatch(vec![10, 12]))
+        );
+
+        assert_eq!(
+            verify(expected.clone(), expected[..1].to_vec()),
+            Err(VerifyError::Mismatch(vec![11, 12]))
+        );
+
+        let mut longer = expected.clone();
+        longer.push(Indexed::new(13, 4));
+        assert_eq!(
+            verify(expected, longer),
+            Err(VerifyError::Mismatch(vec![13]))
+        );
+    }
 }
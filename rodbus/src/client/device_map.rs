@@ -0,0 +1,822 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::client::{Channel, RequestParam, WriteMultiple};
+use crate::codec::{decode_ascii, encode_ascii, RegisterCodec, WordOrder};
+use crate::constants::limits;
+use crate::error::{InvalidRange, InvalidRequest, RequestError};
+use crate::types::{AddressRange, Indexed, Table};
+
+/// Data type of a point in a [`DeviceMap`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataType {
+    /// Single coil or discrete input
+    Bool,
+    /// Unsigned 16-bit register
+    U16,
+    /// Signed 16-bit register
+    I16,
+    /// Unsigned 32-bit integer stored in 2 registers
+    U32,
+    /// Signed 32-bit integer stored in 2 registers
+    I32,
+    /// 32-bit float stored in 2 registers
+    F32,
+    /// Unsigned 64-bit integer stored in 4 registers
+    U64,
+    /// Signed 64-bit integer stored in 4 registers
+    I64,
+    /// 64-bit float stored in 4 registers
+    F64,
+    /// ASCII string stored in the specified number of registers
+    Ascii(u16),
+}
+
+impl DataType {
+    /// Number of coils or registers occupied by the point
+    pub fn count(self) -> u16 {
+        match self {
+            DataType::Bool | DataType::U16 | DataType::I16 => 1,
+            DataType::U32 => u32::REGISTERS,
+            DataType::I32 => i32::REGISTERS,
+            DataType::F32 => f32::REGISTERS,
+            DataType::U64 => u64::REGISTERS,
+            DataType::I64 => i64::REGISTERS,
+            DataType::F64 => f64::REGISTERS,
+            DataType::Ascii(count) => count,
+        }
+    }
+
+    fn parse(value: &str, length: Option<u16>) -> Option<Self> {
+        let data_type = match value {
+            "bool" => DataType::Bool,
+            "u16" => DataType::U16,
+            "i16" => DataType::I16,
+            "u32" => DataType::U32,
+            "i32" => DataType::I32,
+            "f32" => DataType::F32,
+            "u64" => DataType::U64,
+            "i64" => DataType::I64,
+            "f64" => DataType::F64,
+            "ascii" => DataType::Ascii(length?),
+            _ => return None,
+        };
+        Some(data_type)
+    }
+}
+
+/// Operations allowed on a point
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    /// Point may only be read
+    ReadOnly,
+    /// Point may only be written
+    WriteOnly,
+    /// Point may be read and written
+    ReadWrite,
+}
+
+impl Access {
+    /// Returns true if the point may be read
+    pub fn is_readable(self) -> bool {
+        self != Access::WriteOnly
+    }
+
+    /// Returns true if the point may be written
+    pub fn is_writable(self) -> bool {
+        self != Access::ReadOnly
+    }
+}
+
+/// Named value stored in one of the tables of a device
+#[derive(Clone, Debug, PartialEq)]
+pub struct Point {
+    /// Unique name of the point
+    pub name: String,
+    /// Table in which the point is stored
+    pub table: Table,
+    /// Address of the first coil or register of the point
+    pub address: u16,
+    /// Type of the stored value
+    pub data_type: DataType,
+    /// Word order of multi-register values
+    pub word_order: WordOrder,
+    /// Factor applied to numeric raw values to obtain engineering units
+    pub scale: f64,
+    /// Offset added to numeric raw values after scaling
+    pub offset: f64,
+    /// Engineering units, e.g. `V` or `kWh`
+    pub units: Option<String>,
+    /// Operations allowed on the point
+    pub access: Access,
+}
+
+impl Point {
+    /// Range of coils or registers occupied by the point
+    ///
+    /// Fails if the point is empty or extends past the last address. This never happens for the
+    /// points of a [`DeviceMap`], which are validated when the map is created.
+    pub fn range(&self) -> Result<AddressRange, InvalidRange> {
+        AddressRange::try_from(self.address, self.data_type.count())
+    }
+
+    fn validate(&self) -> Result<(), &'static str> {
+        if self.table.is_bit() != (self.data_type == DataType::Bool) {
+            return Err("bool points must be stored in coils or discrete inputs");
+        }
+        if let DataType::Ascii(count) = self.data_type {
+            if count == 0 || count > limits::MAX_READ_REGISTERS_COUNT {
+                return Err("ascii strings must be between 1 and 125 registers long");
+            }
+        }
+        if self.range().is_err() {
+            return Err("point extends past the last address");
+        }
+        if self.access.is_writable()
+            && matches!(self.table, Table::DiscreteInputs | Table::InputRegisters)
+        {
+            return Err("discrete inputs and input registers cannot be written");
+        }
+        if !self.scale.is_finite() || self.scale == 0.0 || !self.offset.is_finite() {
+            return Err("scale must be finite and non-zero and offset must be finite");
+        }
+        Ok(())
+    }
+}
+
+/// Value of a point in engineering units
+#[derive(Clone, Debug, PartialEq)]
+pub enum PointValue {
+    /// Value of a [`DataType::Bool`] point
+    Bool(bool),
+    /// Scaled value of a numeric point
+    Number(f64),
+    /// Value of a [`DataType::Ascii`] point
+    Text(String),
+}
+
+/// Errors that occur while loading a [`DeviceMap`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeviceMapError {
+    /// The description is not valid TOML or JSON or doesn't follow the expected schema
+    Parse(String),
+    /// Two points have the same name
+    DuplicateName(String),
+    /// A point is not valid
+    InvalidPoint(String, String), // name and reason
+}
+
+impl std::error::Error for DeviceMapError {}
+
+impl std::fmt::Display for DeviceMapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DeviceMapError::Parse(err) => write!(f, "unable to parse device map: {err}"),
+            DeviceMapError::DuplicateName(name) => write!(f, "duplicate point name: {name}"),
+            DeviceMapError::InvalidPoint(name, reason) => {
+                write!(f, "invalid point '{name}': {reason}")
+            }
+        }
+    }
+}
+
+/// Errors that occur while reading or writing points by name
+#[derive(Clone, Debug, PartialEq)]
+pub enum PointError {
+    /// No point with this name exists in the map
+    UnknownPoint(String),
+    /// The point is write-only
+    NotReadable(String),
+    /// The point is read-only
+    NotWritable(String),
+    /// The value doesn't match the type of the point or is out of range
+    InvalidValue(String),
+    /// The underlying request failed
+    Request(RequestError),
+}
+
+impl std::error::Error for PointError {}
+
+impl std::fmt::Display for PointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PointError::UnknownPoint(name) => write!(f, "unknown point: {name}"),
+            PointError::NotReadable(name) => write!(f, "point '{name}' is not readable"),
+            PointError::NotWritable(name) => write!(f, "point '{name}' is not writable"),
+            PointError::InvalidValue(name) => write!(f, "invalid value for point '{name}'"),
+            PointError::Request(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl From<RequestError> for PointError {
+    fn from(err: RequestError) -> Self {
+        PointError::Request(err)
+    }
+}
+
+impl From<InvalidRequest> for PointError {
+    fn from(err: InvalidRequest) -> Self {
+        PointError::Request(err.into())
+    }
+}
+
+impl From<InvalidRange> for PointError {
+    fn from(err: InvalidRange) -> Self {
+        InvalidRequest::from(err).into()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawDeviceMap {
+    #[serde(default)]
+    points: Vec<RawPoint>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPoint {
+    name: String,
+    table: String,
+    address: u16,
+    #[serde(rename = "type")]
+    data_type: String,
+    length: Option<u16>,
+    word_order: Option<String>,
+    scale: Option<f64>,
+    offset: Option<f64>,
+    units: Option<String>,
+    access: Option<String>,
+}
+
+impl RawPoint {
+    fn into_point(self) -> Result<Point, DeviceMapError> {
+        let invalid = |reason: &str| DeviceMapError::InvalidPoint(self.name.clone(), reason.into());
+
+        let table = match self.table.as_str() {
+            "coils" => Table::Coils,
+            "discrete_inputs" => Table::DiscreteInputs,
+            "holding_registers" => Table::HoldingRegisters,
+            "input_registers" => Table::InputRegisters,
+            _ => return Err(invalid("unknown table")),
+        };
+
+        let data_type = DataType::parse(&self.data_type, self.length)
+            .ok_or_else(|| invalid("unknown type or missing string length"))?;
+
+        let word_order = match self.word_order.as_deref().unwrap_or("abcd") {
+            "abcd" => WordOrder::Abcd,
+            "badc" => WordOrder::Badc,
+            "cdab" => WordOrder::Cdab,
+            "dcba" => WordOrder::Dcba,
+            _ => return Err(invalid("unknown word order")),
+        };
+
+        let default_access = match table {
+            Table::Coils | Table::HoldingRegisters => "read_write",
+            Table::DiscreteInputs | Table::InputRegisters => "read",
+        };
+        let access = match self.access.as_deref().unwrap_or(default_access) {
+            "read" => Access::ReadOnly,
+            "write" => Access::WriteOnly,
+            "read_write" => Access::ReadWrite,
+            _ => return Err(invalid("unknown access mode")),
+        };
+
+        Ok(Point {
+            name: self.name,
+            table,
+            address: self.address,
+            data_type,
+            word_order,
+            scale: self.scale.unwrap_or(1.0),
+            offset: self.offset.unwrap_or(0.0),
+            units: self.units,
+            access,
+        })
+    }
+}
+
+/// Validated collection of named points of a device
+///
+/// Maps can be loaded from TOML or JSON. Every point is a table entry with the following fields:
+///
+/// * `name` - unique name of the point
+/// * `table` - `coils`, `discrete_inputs`, `holding_registers` or `input_registers`
+/// * `address` - address of the first coil or register
+/// * `type` - `bool`, `u16`, `i16`, `u32`, `i32`, `f32`, `u64`, `i64`, `f64` or `ascii`
+/// * `length` - number of registers of `ascii` points
+/// * `word_order` - optional `abcd` (default), `badc`, `cdab` or `dcba`
+/// * `scale` and `offset` - optional conversion to engineering units, defaults to 1 and 0
+/// * `units` - optional engineering units
+/// * `access` - optional `read`, `write` or `read_write`. Defaults to `read_write` for coils
+///   and holding registers and to `read` otherwise
+///
+/// ```toml
+/// [[points]]
+/// name = "voltage"
+/// table = "input_registers"
+/// address = 100
+/// type = "f32"
+/// word_order = "cdab"
+/// units = "V"
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceMap {
+    points: Vec<Point>,
+    names: BTreeMap<String, usize>,
+}
+
+impl DeviceMap {
+    /// Validate a list of points
+    pub fn new(points: Vec<Point>) -> Result<Self, DeviceMapError> {
+        let mut names = BTreeMap::new();
+        for (i, point) in points.iter().enumerate() {
+            point.validate().map_err(|reason| {
+                DeviceMapError::InvalidPoint(point.name.clone(), reason.into())
+            })?;
+            if names.insert(point.name.clone(), i).is_some() {
+                return Err(DeviceMapError::DuplicateName(point.name.clone()));
+            }
+        }
+        Ok(Self { points, names })
+    }
+
+    /// Parse and validate a TOML description
+    pub fn from_toml(description: &str) -> Result<Self, DeviceMapError> {
+        let raw: RawDeviceMap =
+            toml::from_str(description).map_err(|err| DeviceMapError::Parse(err.to_string()))?;
+        Self::from_raw(raw)
+    }
+
+    /// Parse and validate a JSON description
+    pub fn from_json(description: &str) -> Result<Self, DeviceMapError> {
+        let raw: RawDeviceMap = serde_json::from_str(description)
+            .map_err(|err| DeviceMapError::Parse(err.to_string()))?;
+        Self::from_raw(raw)
+    }
+
+    /// Retrieve a point by name
+    pub fn get(&self, name: &str) -> Option<&Point> {
+        self.names.get(name).map(|i| &self.points[*i])
+    }
+
+    /// All the points in the order of the description
+    pub fn points(&self) -> &[Point] {
+        &self.points
+    }
+
+    fn from_raw(raw: RawDeviceMap) -> Result<Self, DeviceMapError> {
+        let points = raw
+            .points
+            .into_iter()
+            .map(RawPoint::into_point)
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(points)
+    }
+}
+
+/// Plan the reads needed to retrieve a set of points
+///
+/// Points of the same table are merged into a single read when they are separated by no more
+/// than `max_gap` unused addresses and the merged read does not exceed the request size limit.
+pub fn plan_reads<'a, I>(points: I, max_gap: u16) -> Vec<(Table, AddressRange)>
+where
+    I: IntoIterator<Item = &'a Point>,
+{
+    let mut ranges: Vec<(Table, u16, u32)> = points
+        .into_iter()
+        .map(|p| {
+            (
+                p.table,
+                p.address,
+                p.address as u32 + p.data_type.count() as u32,
+            )
+        })
+        .collect();
+    ranges.sort_by_key(|(table, start, _)| (*table, *start));
+
+    let mut reads: Vec<(Table, u16, u32)> = Vec::new();
+    for (table, start, end) in ranges {
+        let limit = if table.is_bit() {
+            limits::MAX_READ_COILS_COUNT
+        } else {
+            limits::MAX_READ_REGISTERS_COUNT
+        } as u32;
+
+        if let Some(last) = reads.last_mut() {
+            let merged_end = std::cmp::max(last.2, end);
+            if last.0 == table
+                && start as u32 <= last.2 + max_gap as u32
+                && merged_end - last.1 as u32 <= limit
+            {
+                last.2 = merged_end;
+                continue;
+            }
+        }
+        reads.push((table, start, end));
+    }
+
+    reads
+        .into_iter()
+        .map(|(table, start, end)| {
+            let range = AddressRange {
+                start,
+                count: (end - start as u32) as u16,
+            };
+            (table, range)
+        })
+        .collect()
+}
+
+/// [`Channel`] wrapper that reads and writes the points of a [`DeviceMap`] by name
+pub struct MappedChannel {
+    channel: Channel,
+    param: RequestParam,
+    map: DeviceMap,
+    max_gap: u16,
+}
+
+impl MappedChannel {
+    /// Create a wrapper that sends requests using `param`
+    pub fn new(channel: Channel, param: RequestParam, map: DeviceMap) -> Self {
+        Self {
+            channel,
+            param,
+            map,
+            max_gap: 0,
+        }
+    }
+
+    /// Allow reads to span up to `max_gap` unused addresses between points. Defaults to zero.
+    pub fn set_max_gap(&mut self, max_gap: u16) {
+        self.max_gap = max_gap;
+    }
+
+    /// Device map used by the wrapper
+    pub fn map(&self) -> &DeviceMap {
+        &self.map
+    }
+
+    /// Read a single point
+    pub async fn read(&mut self, name: &str) -> Result<PointValue, PointError> {
+        let mut values = self.read_many(&[name]).await?;
+        Ok(values.remove(0).1)
+    }
+
+    /// Read all the readable points of the map
+    pub async fn read_all(&mut self) -> Result<Vec<(String, PointValue)>, PointError> {
+        let names: Vec<String> = self
+            .map
+            .points()
+            .iter()
+            .filter(|p| p.access.is_readable())
+            .map(|p| p.name.clone())
+            .collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        self.read_many(&names).await
+    }
+
+    /// Read several points using as few requests as possible
+    pub async fn read_many(
+        &mut self,
+        names: &[&str],
+    ) -> Result<Vec<(String, PointValue)>, PointError> {
+        let mut points = Vec::with_capacity(names.len());
+        for name in names {
+            let point = self.lookup(name)?;
+            if !point.access.is_readable() {
+                return Err(PointError::NotReadable(point.name.clone()));
+            }
+            points.push(point.clone());
+        }
+
+        let mut bits = BTreeMap::new();
+        let mut registers = BTreeMap::new();
+        for (table, range) in plan_reads(points.iter(), self.max_gap) {
+            match table {
+                Table::Coils => {
+                    let values = self.channel.read_coils(self.param, range).await?;
+                    bits.extend(values.into_iter().map(|x| ((table, x.index), x.value)));
+                }
+                Table::DiscreteInputs => {
+                    let values = self.channel.read_discrete_inputs(self.param, range).await?;
+                    bits.extend(values.into_iter().map(|x| ((table, x.index), x.value)));
+                }
+                Table::HoldingRegisters => {
+                    let values = self
+                        .channel
+                        .read_holding_registers(self.param, range)
+                        .await?;
+                    registers.extend(values.into_iter().map(|x| ((table, x.index), x.value)));
+                }
+                Table::InputRegisters => {
+                    let values = self.channel.read_input_registers(self.param, range).await?;
+                    registers.extend(values.into_iter().map(|x| ((table, x.index), x.value)));
+                }
+            }
+        }
+
+        points
+            .into_iter()
+            .map(|point| {
+                let value = decode_point(&point, &bits, &registers)?;
+                Ok((point.name, value))
+            })
+            .collect()
+    }
+
+    /// Write a single point
+    pub async fn write(&mut self, name: &str, value: PointValue) -> Result<(), PointError> {
+        let point = self.lookup(name)?.clone();
+        if !point.access.is_writable() {
+            return Err(PointError::NotWritable(point.name));
+        }
+
+        match point.table {
+            Table::Coils => {
+                let value = match value {
+                    PointValue::Bool(x) => x,
+                    _ => return Err(PointError::InvalidValue(point.name)),
+                };
+                self.channel
+                    .write_single_coil(self.param, Indexed::new(point.address, value))
+                    .await?;
+            }
+            Table::HoldingRegisters => {
+                let registers = encode_point(&point, value)?;
+                if let [register] = registers.as_slice() {
+                    self.channel
+                        .write_single_register(self.param, Indexed::new(point.address, *register))
+                        .await?;
+                } else {
+                    let request = WriteMultiple::from(point.address, registers)?;
+                    self.channel
+                        .write_multiple_registers(self.param, request)
+                        .await?;
+                }
+            }
+            // rejected when the map is validated
+            Table::DiscreteInputs | Table::InputRegisters => {
+                return Err(PointError::NotWritable(point.name))
+            }
+        }
+
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Result<&Point, PointError> {
+        self.map
+            .get(name)
+            .ok_or_else(|| PointError::UnknownPoint(name.to_string()))
+    }
+}
+
+fn decode_point(
+    point: &Point,
+    bits: &BTreeMap<(Table, u16), bool>,
+    registers: &BTreeMap<(Table, u16), u16>,
+) -> Result<PointValue, PointError> {
+    let missing = || {
+        PointError::Request(RequestError::BadResponse(
+            crate::error::AduParseError::InsufficientBytes,
+        ))
+    };
+
+    if point.data_type == DataType::Bool {
+        let value = bits
+            .get(&(point.table, point.address))
+            .ok_or_else(missing)?;
+        return Ok(PointValue::Bool(*value));
+    }
+
+    let values = point
+        .range()?
+        .iter()
+        .map(|address| registers.get(&(point.table, address)).copied())
+        .collect::<Option<Vec<u16>>>()
+        .ok_or_else(missing)?;
+
+    let order = point.word_order;
+    let raw = match point.data_type {
+        DataType::U16 => values[0] as f64,
+        DataType::I16 => values[0] as i16 as f64,
+        DataType::U32 => u32::decode(&values, order).ok_or_else(missing)? as f64,
+        DataType::I32 => i32::decode(&values, order).ok_or_else(missing)? as f64,
+        DataType::F32 => f32::decode(&values, order).ok_or_else(missing)? as f64,
+        DataType::U64 => u64::decode(&values, order).ok_or_else(missing)? as f64,
+        DataType::I64 => i64::decode(&values, order).ok_or_else(missing)? as f64,
+        DataType::F64 => f64::decode(&values, order).ok_or_else(missing)?,
+        DataType::Ascii(_) => {
+            let registers = values
+                .into_iter()
+                .enumerate()
+                .map(|(i, x)| Indexed::new(point.address + i as u16, x));
+            let text = decode_ascii(registers, order).map_err(RequestError::from)?;
+            return Ok(PointValue::Text(text));
+        }
+        DataType::Bool => unreachable!(),
+    };
+
+    Ok(PointValue::Number(raw * point.scale + point.offset))
+}
+
+fn encode_point(point: &Point, value: PointValue) -> Result<Vec<u16>, PointError> {
+    let invalid = || PointError::InvalidValue(point.name.clone());
+    let order = point.word_order;
+
+    let value = match (point.data_type, value) {
+        (DataType::Ascii(count), PointValue::Text(text)) => {
+            return Ok(encode_ascii(point.address, &text, count, order)?.values)
+        }
+        (_, PointValue::Number(x)) => (x - point.offset) / point.scale,
+        _ => return Err(invalid()),
+    };
+
+    fn integer<T: TryFrom<i128>>(value: f64) -> Option<T> {
+        if !value.is_finite() {
+            return None;
+        }
+        T::try_from(value.round() as i128).ok()
+    }
+
+    let registers = match point.data_type {
+        DataType::U16 => vec![integer::<u16>(value).ok_or_else(invalid)?],
+        DataType::I16 => vec![integer::<i16>(value).ok_or_else(invalid)? as u16],
+        DataType::U32 => integer::<u32>(value).ok_or_else(invalid)?.encode(order),
+        DataType::I32 => integer::<i32>(value).ok_or_else(invalid)?.encode(order),
+        DataType::F32 => (value as f32).encode(order),
+        DataType::U64 => integer::<u64>(value).ok_or_else(invalid)?.encode(order),
+        DataType::I64 => integer::<i64>(value).ok_or_else(invalid)?.encode(order),
+        DataType::F64 => value.encode(order),
+        DataType::Bool | DataType::Ascii(_) => return Err(invalid()),
+    };
+
+    Ok(registers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML: &str = r#"
+        [[points]]
+        name = "voltage"
+        table = "input_registers"
+        address = 100
+        type = "f32"
+        word_order = "cdab"
+        units = "V"
+
+        [[points]]
+        name = "current"
+        table = "input_registers"
+        address = 102
+        type = "i16"
+        scale = 0.1
+
+        [[points]]
+        name = "serial"
+        table = "input_registers"
+        address = 110
+        type = "ascii"
+        length = 4
+
+        [[points]]
+        name = "setpoint"
+        table = "holding_registers"
+        address = 0
+        type = "u32"
+
+        [[points]]
+        name = "running"
+        table = "coils"
+        address = 3
+        type = "bool"
+    "#;
+
+    #[test]
+    fn parses_toml_description() {
+        let map = DeviceMap::from_toml(TOML).unwrap();
+        let voltage = map.get("voltage").unwrap();
+        assert_eq!(voltage.table, Table::InputRegisters);
+        assert_eq!(voltage.data_type, DataType::F32);
+        assert_eq!(voltage.word_order, WordOrder::Cdab);
+        assert_eq!(voltage.units.as_deref(), Some("V"));
+        assert_eq!(voltage.access, Access::ReadOnly);
+        assert_eq!(map.get("serial").unwrap().range().unwrap().count, 4);
+        assert_eq!(map.get("setpoint").unwrap().access, Access::ReadWrite);
+    }
+
+    #[test]
+    fn parses_json_description() {
+        let map = DeviceMap::from_json(
+            r#"{"points": [{"name": "a", "table": "coils", "address": 1, "type": "bool"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(map.points().len(), 1);
+    }
+
+    #[test]
+    fn rejects_invalid_maps() {
+        let point = |name: &str, table: &str, ty: &str, access: &str| {
+            format!(
+                "[[points]]\nname = \"{name}\"\ntable = \"{table}\"\naddress = 0\ntype = \"{ty}\"\naccess = \"{access}\"\n"
+            )
+        };
+
+        assert_eq!(
+            DeviceMap::from_toml(
+                &(point("a", "coils", "bool", "read") + &point("a", "coils", "bool", "read"))
+            ),
+            Err(DeviceMapError::DuplicateName("a".to_string()))
+        );
+        assert!(matches!(
+            DeviceMap::from_toml(&point("a", "coils", "u16", "read")),
+            Err(DeviceMapError::InvalidPoint(..))
+        ));
+        assert!(matches!(
+            DeviceMap::from_toml(&point("a", "input_registers", "u16", "read_write")),
+            Err(DeviceMapError::InvalidPoint(..))
+        ));
+        assert_eq!(
+            DeviceMap::from_toml(
+                &(point("a", "holding_registers", "ascii", "read") + "length = 0")
+            ),
+            Err(DeviceMapError::InvalidPoint(
+                "a".to_string(),
+                "ascii strings must be between 1 and 125 registers long".to_string()
+            ))
+        );
+        assert!(matches!(
+            DeviceMap::from_toml("points = 3"),
+            Err(DeviceMapError::Parse(_))
+        ));
+
+        let mut point = DeviceMap::from_toml(TOML)
+            .unwrap()
+            .get("serial")
+            .unwrap()
+            .clone();
+        point.address = u16::MAX;
+        assert!(point.range().is_err());
+    }
+
+    #[test]
+    fn merges_adjacent_points_into_one_read() {
+        let map = DeviceMap::from_toml(TOML).unwrap();
+        let reads = plan_reads(map.points(), 0);
+        assert_eq!(
+            reads,
+            vec![
+                (Table::Coils, AddressRange::try_from(3, 1).unwrap()),
+                (
+                    Table::HoldingRegisters,
+                    AddressRange::try_from(0, 2).unwrap()
+                ),
+                (
+                    Table::InputRegisters,
+                    AddressRange::try_from(100, 3).unwrap()
+                ),
+                (
+                    Table::InputRegisters,
+                    AddressRange::try_from(110, 4).unwrap()
+                ),
+            ]
+        );
+
+        // a large enough gap merges the two input register reads
+        let reads = plan_reads(map.points(), 7);
+        assert_eq!(
+            reads[2],
+            (
+                Table::InputRegisters,
+                AddressRange::try_from(100, 14).unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn converts_to_and_from_engineering_units() {
+        let map = DeviceMap::from_toml(TOML).unwrap();
+        let current = map.get("current").unwrap();
+
+        let registers = BTreeMap::from([((Table::InputRegisters, 102), (-25i16) as u16)]);
+        assert_eq!(
+            decode_point(current, &BTreeMap::new(), &registers),
+            Ok(PointValue::Number(-2.5))
+        );
+
+        let setpoint = map.get("setpoint").unwrap();
+        assert_eq!(
+            encode_point(setpoint, PointValue::Number(70000.0)),
+            Ok(vec![0x0001, 0x1170])
+        );
+        assert_eq!(
+            encode_point(setpoint, PointValue::Number(-1.0)),
+            Err(PointError::InvalidValue("setpoint".to_string()))
+        );
+    }
+}
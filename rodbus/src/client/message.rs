@@ -1,26 +1,36 @@
 use crate::common::function::FunctionCode;
+use crate::common::structured;
 use crate::common::traits::Loggable;
-use crate::decode::AppDecodeLevel;
+use crate::decode::{AppDecodeLevel, DecodeFormat, DecodeSettings};
+use crate::decoder::MessageType;
 use crate::error::AduParseError;
 use crate::error::*;
 use crate::exception::ExceptionCode;
+use crate::observer::{Direction, TrafficObserver};
+use crate::retry::RetryPolicy;
 use crate::DecodeLevel;
 
+use crate::client::channel::RequestParam;
 use crate::client::requests::read_bits::ReadBits;
 use crate::client::requests::read_device_identification::ReadDeviceIdentification;
 use crate::client::requests::read_registers::ReadRegisters;
 use crate::client::requests::write_multiple::MultipleWriteRequest;
 use crate::client::requests::write_single::SingleWrite;
+use crate::client::statistics::ChannelStatistics;
 use crate::common::traits::Serialize;
 use crate::types::{Indexed, UnitId};
 
 use scursor::{ReadCursor, WriteCursor};
+use std::sync::Arc;
 use std::time::Duration;
 
 pub(crate) enum Setting {
     DecodeLevel(DecodeLevel),
+    DecodeFormat(DecodeFormat),
+    TrafficObserver(Option<Arc<dyn TrafficObserver>>),
     Enable,
     Disable,
+    ResetStatistics,
 }
 
 pub(crate) enum Command {
@@ -28,11 +38,14 @@ pub(crate) enum Command {
     Request(Request),
     /// Change a setting
     Setting(Setting),
+    /// Retrieve a snapshot of the statistics
+    Statistics(tokio::sync::oneshot::Sender<ChannelStatistics>),
 }
 
 pub(crate) struct Request {
     pub(crate) id: UnitId,
     pub(crate) timeout: Duration,
+    pub(crate) retry: RetryPolicy,
     pub(crate) details: RequestDetails,
 }
 
@@ -50,10 +63,11 @@ pub(crate) enum RequestDetails {
 }
 
 impl Request {
-    pub(crate) fn new(id: UnitId, timeout: Duration, details: RequestDetails) -> Self {
+    pub(crate) fn new(param: RequestParam, retry: RetryPolicy, details: RequestDetails) -> Self {
         Self {
-            id,
-            timeout,
+            id: param.id,
+            timeout: param.response_timeout,
+            retry,
             details,
         }
     }
@@ -61,8 +75,18 @@ impl Request {
     pub(crate) fn handle_response(
         &mut self,
         payload: &[u8],
-        decode: AppDecodeLevel,
+        decode: DecodeSettings,
     ) -> Result<(), RequestError> {
+        // a structured record also covers exceptions, so the request-specific handlers only log text
+        let decode = if decode.is_structured() {
+            if decode.app.enabled() {
+                structured::pdu(Direction::Rx, MessageType::Response, decode.app, payload);
+            }
+            AppDecodeLevel::Nothing
+        } else {
+            decode.app
+        };
+
         let expected_function = self.details.function();
         let mut cursor = ReadCursor::new(payload);
         let function = match cursor.read_u8() {
@@ -135,6 +159,36 @@ impl RequestDetails {
         }
     }
 
+    /// Writes may trigger actions in the device and are never considered idempotent
+    pub(crate) fn is_idempotent(&self) -> bool {
+        match self {
+            RequestDetails::ReadCoils(_) => true,
+            RequestDetails::ReadDiscreteInputs(_) => true,
+            RequestDetails::ReadHoldingRegisters(_) => true,
+            RequestDetails::ReadInputRegisters(_) => true,
+            RequestDetails::WriteSingleCoil(_) => false,
+            RequestDetails::WriteSingleRegister(_) => false,
+            RequestDetails::WriteMultipleCoils(_) => false,
+            RequestDetails::WriteMultipleRegisters(_) => false,
+            RequestDetails::ReadDeviceIdentification(_) => true,
+        }
+    }
+
+    /// true if the caller awaiting the result is gone, so the request need not be sent
+    pub(crate) fn is_cancelled(&self) -> bool {
+        match self {
+            RequestDetails::ReadCoils(x) => x.is_cancelled(),
+            RequestDetails::ReadDiscreteInputs(x) => x.is_cancelled(),
+            RequestDetails::ReadHoldingRegisters(x) => x.is_cancelled(),
+            RequestDetails::ReadInputRegisters(x) => x.is_cancelled(),
+            RequestDetails::WriteSingleCoil(x) => x.is_cancelled(),
+            RequestDetails::WriteSingleRegister(x) => x.is_cancelled(),
+            RequestDetails::WriteMultipleCoils(x) => x.is_cancelled(),
+            RequestDetails::WriteMultipleRegisters(x) => x.is_cancelled(),
+            RequestDetails::ReadDeviceIdentification(x) => x.is_cancelled(),
+        }
+    }
+
     pub(crate) fn fail(&mut self, err: RequestError) {
         match self {
             RequestDetails::ReadCoils(x) => x.failure(err),
@@ -264,11 +318,29 @@ pub(crate) trait Callback<T>:
 
 impl<F, T> Callback<T> for F where F: FnOnce(Result<T, RequestError>) + Send + Sync + 'static {}
 
+/// How the result of a request reaches the caller
+pub(crate) enum Completion<C: ?Sized, T> {
+    /// Invoke a user callback
+    Callback(Box<C>),
+    /// Send the result to a caller awaiting it
+    Channel(tokio::sync::oneshot::Sender<Result<T, RequestError>>),
+}
+
+impl<C: ?Sized, T> Completion<C, T> {
+    /// true if the result can no longer be delivered because the awaiting caller is gone
+    pub(crate) fn is_cancelled(&self) -> bool {
+        match self {
+            Completion::Callback(_) => false,
+            Completion::Channel(tx) => tx.is_closed(),
+        }
+    }
+}
+
 pub(crate) struct Promise<T>
 where
     T: Send + 'static,
 {
-    callback: Option<Box<dyn Callback<T>>>,
+    callback: Option<Completion<dyn Callback<T>, T>>,
 }
 
 impl<T> Promise<T>
@@ -280,14 +352,18 @@ where
         F: Callback<T>,
     {
         Self {
-            callback: Some(Box::new(callback)),
+            callback: Some(Completion::Callback(Box::new(callback))),
         }
     }
 
     pub(crate) fn channel(tx: tokio::sync::oneshot::Sender<Result<T, RequestError>>) -> Self {
-        Self::new(|x: Result<T, RequestError>| {
-            let _ = tx.send(x);
-        })
+        Self {
+            callback: Some(Completion::Channel(tx)),
+        }
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.callback.as_ref().is_some_and(Completion::is_cancelled)
     }
 
     pub(crate) fn failure(&mut self, err: RequestError) {
@@ -299,8 +375,12 @@ where
     }
 
     fn complete(&mut self, result: Result<T, RequestError>) {
-        if let Some(callback) = self.callback.take() {
-            callback(result)
+        match self.callback.take() {
+            Some(Completion::Callback(callback)) => callback(result),
+            Some(Completion::Channel(tx)) => {
+                let _ = tx.send(result);
+            }
+            None => {}
         }
     }
 }
@@ -2,16 +2,30 @@ use std::net::{IpAddr, SocketAddr};
 
 use crate::decode::DecodeLevel;
 
+/// Synchronous client API
+pub mod blocking;
+pub(crate) mod cache;
+pub(crate) mod change;
 /// persistent communication channel such as a TCP connection
 pub(crate) mod channel;
+#[cfg(feature = "device-map")]
+pub(crate) mod device_map;
 pub(crate) mod listener;
 pub(crate) mod message;
 pub(crate) mod requests;
+pub(crate) mod scan;
+pub(crate) mod statistics;
 pub(crate) mod task;
 
+pub use crate::client::cache::*;
+pub use crate::client::change::*;
 pub use crate::client::channel::*;
+#[cfg(feature = "device-map")]
+pub use crate::client::device_map::*;
 pub use crate::client::listener::*;
 pub use crate::client::requests::write_multiple::WriteMultiple;
+pub use crate::client::scan::*;
+pub use crate::client::statistics::*;
 pub use crate::retry::*;
 
 #[cfg(feature = "tls")]
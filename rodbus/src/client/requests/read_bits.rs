@@ -1,3 +1,4 @@
+use crate::client::message::Completion;
 use crate::common::function::FunctionCode;
 use crate::common::traits::Serialize;
 use crate::decode::AppDecodeLevel;
@@ -15,7 +16,7 @@ impl<T> BitsCallback for T where T: FnOnce(Result<BitIterator, RequestError>) +
 {}
 
 pub(crate) struct Promise {
-    callback: Option<Box<dyn BitsCallback>>,
+    callback: Option<Completion<dyn BitsCallback, Vec<Indexed<bool>>>>,
 }
 
 impl Drop for Promise {
@@ -30,10 +31,22 @@ impl Promise {
         T: BitsCallback,
     {
         Self {
-            callback: Some(Box::new(callback)),
+            callback: Some(Completion::Callback(Box::new(callback))),
         }
     }
 
+    pub(crate) fn channel(
+        tx: tokio::sync::oneshot::Sender<Result<Vec<Indexed<bool>>, RequestError>>,
+    ) -> Self {
+        Self {
+            callback: Some(Completion::Channel(tx)),
+        }
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.callback.as_ref().is_some_and(Completion::is_cancelled)
+    }
+
     pub(crate) fn failure(&mut self, err: RequestError) {
         self.complete(Err(err))
     }
@@ -43,8 +56,12 @@ impl Promise {
     }
 
     fn complete(&mut self, result: Result<BitIterator, RequestError>) {
-        if let Some(callback) = self.callback.take() {
-            callback(result)
+        match self.callback.take() {
+            Some(Completion::Callback(callback)) => callback(result),
+            Some(Completion::Channel(tx)) => {
+                let _ = tx.send(result.map(|x| x.collect()));
+            }
+            None => {}
         }
     }
 }
@@ -63,18 +80,17 @@ impl ReadBits {
         request: ReadBitsRange,
         tx: tokio::sync::oneshot::Sender<Result<Vec<Indexed<bool>>, RequestError>>,
     ) -> Self {
-        Self::new(
-            request,
-            Promise::new(|x: Result<BitIterator, RequestError>| {
-                let _ = tx.send(x.map(|x| x.collect()));
-            }),
-        )
+        Self::new(request, Promise::channel(tx))
     }
 
     pub(crate) fn serialize(&self, cursor: &mut WriteCursor) -> Result<(), RequestError> {
         self.request.get().serialize(cursor)
     }
 
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.promise.is_cancelled()
+    }
+
     pub(crate) fn failure(&mut self, err: RequestError) {
         self.promise.failure(err)
     }
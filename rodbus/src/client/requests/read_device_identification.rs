@@ -1,6 +1,7 @@
 use scursor::{WriteCursor, ReadCursor};
 
 use crate::{RequestError, DeviceIdentification, ReadDeviceInfoBlock, common::{traits::Serialize, function::FunctionCode}, AppDecodeLevel};
+use crate::client::message::Completion;
 
 
 pub(crate) struct ReadDeviceIdentification {
@@ -18,7 +19,7 @@ impl<T> DeviceIdentificationCallback for T where
 }
 
 pub(crate) struct Promise {
-    callback: Option<Box<dyn DeviceIdentificationCallback>>,
+    callback: Option<Completion<dyn DeviceIdentificationCallback, DeviceIdentification>>,
 }
 
 impl Drop for Promise {
@@ -28,12 +29,16 @@ impl Drop for Promise {
 }
 
 impl Promise {
-    pub(crate) fn new<T>(callback: T) -> Self where T: DeviceIdentificationCallback {
+    pub(crate) fn channel(tx: tokio::sync::oneshot::Sender<Result<DeviceIdentification, RequestError>>) -> Self {
         Self {
-            callback: Some(Box::new(callback)),
+            callback: Some(Completion::Channel(tx)),
         }
     }
 
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.callback.as_ref().is_some_and(Completion::is_cancelled)
+    }
+
     pub(crate) fn failure(&mut self, err: RequestError) {
         self.complete(Err(err));
     }
@@ -43,8 +48,12 @@ impl Promise {
     }
 
     fn complete(&mut self, x: Result<DeviceIdentification, RequestError>) {
-        if let Some(callback) = self.callback.take() {
-            callback(x);
+        match self.callback.take() {
+            Some(Completion::Callback(callback)) => callback(x),
+            Some(Completion::Channel(tx)) => {
+                let _ = tx.send(x);
+            }
+            None => {}
         }
     }
 }
@@ -59,15 +68,17 @@ impl ReadDeviceIdentification {
 
     pub(crate) fn channel(request: ReadDeviceInfoBlock,
         tx: tokio::sync::oneshot::Sender<Result<DeviceIdentification, RequestError>>) -> Self {
-            Self::new(request, Promise::new(|x: Result<DeviceIdentification, RequestError>| {
-                let _ = tx.send(x);
-            }))
+            Self::new(request, Promise::channel(tx))
     }
 
     pub(crate) fn serialize(&self, cursor: &mut WriteCursor) -> Result<(), RequestError> {
         self.request.serialize(cursor)
     }
 
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.promise.is_cancelled()
+    }
+
     pub(crate) fn failure(&mut self, err: RequestError) {
         self.promise.failure(err);
     }
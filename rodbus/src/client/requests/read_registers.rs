@@ -1,3 +1,4 @@
+use crate::client::message::Completion;
 use crate::common::function::FunctionCode;
 use crate::common::traits::Serialize;
 use crate::decode::AppDecodeLevel;
@@ -18,7 +19,7 @@ impl<T> RegistersCallback for T where
 }
 
 pub(crate) struct Promise {
-    callback: Option<Box<dyn RegistersCallback>>,
+    callback: Option<Completion<dyn RegistersCallback, Vec<Indexed<u16>>>>,
 }
 
 impl Drop for Promise {
@@ -33,10 +34,22 @@ impl Promise {
         T: RegistersCallback,
     {
         Self {
-            callback: Some(Box::new(callback)),
+            callback: Some(Completion::Callback(Box::new(callback))),
         }
     }
 
+    pub(crate) fn channel(
+        tx: tokio::sync::oneshot::Sender<Result<Vec<Indexed<u16>>, RequestError>>,
+    ) -> Self {
+        Self {
+            callback: Some(Completion::Channel(tx)),
+        }
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.callback.as_ref().is_some_and(Completion::is_cancelled)
+    }
+
     pub(crate) fn failure(&mut self, err: RequestError) {
         self.complete(Err(err))
     }
@@ -46,8 +59,12 @@ impl Promise {
     }
 
     fn complete(&mut self, x: Result<RegisterIterator, RequestError>) {
-        if let Some(callback) = self.callback.take() {
-            callback(x)
+        match self.callback.take() {
+            Some(Completion::Callback(callback)) => callback(x),
+            Some(Completion::Channel(tx)) => {
+                let _ = tx.send(x.map(|x| x.collect()));
+            }
+            None => {}
         }
     }
 }
@@ -66,18 +83,17 @@ impl ReadRegisters {
         request: ReadRegistersRange,
         tx: tokio::sync::oneshot::Sender<Result<Vec<Indexed<u16>>, RequestError>>,
     ) -> Self {
-        Self::new(
-            request,
-            Promise::new(|x: Result<RegisterIterator, RequestError>| {
-                let _ = tx.send(x.map(|x| x.collect()));
-            }),
-        )
+        Self::new(request, Promise::channel(tx))
     }
 
     pub(crate) fn serialize(&self, cursor: &mut WriteCursor) -> Result<(), RequestError> {
         self.request.get().serialize(cursor)
     }
 
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.promise.is_cancelled()
+    }
+
     pub(crate) fn failure(&mut self, err: RequestError) {
         self.promise.failure(err)
     }
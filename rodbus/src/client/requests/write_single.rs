@@ -34,6 +34,10 @@ where
         self.request.serialize(cursor)
     }
 
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.promise.is_cancelled()
+    }
+
     pub(crate) fn failure(&mut self, err: RequestError) {
         self.promise.failure(err)
     }
@@ -0,0 +1,329 @@
+use std::future::Future;
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+use crate::client::{Channel, RequestParam};
+use crate::error::RequestError;
+use crate::exception::ExceptionCode;
+use crate::types::{AddressRange, Table, UnitId};
+
+/// Settings of a [`Scanner`]
+#[derive(Clone, Copy, Debug)]
+pub struct ScanConfig {
+    /// First unit id to scan
+    pub first_unit: u8,
+    /// Last unit id to scan
+    pub last_unit: u8,
+    /// Response timeout of every probe request
+    pub timeout: Duration,
+    /// Table read to check if a unit id responds
+    pub probe_table: Table,
+    /// Address read to check if a unit id responds
+    ///
+    /// When capabilities are probed, the readable block of every table is searched around this address.
+    pub probe_address: u16,
+    /// Whether to probe the supported functions and address boundaries of responding units
+    pub probe_capabilities: bool,
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        Self {
+            first_unit: 1,
+            last_unit: 247,
+            timeout: Duration::from_millis(100),
+            probe_table: Table::HoldingRegisters,
+            probe_address: 0,
+            probe_capabilities: true,
+        }
+    }
+}
+
+/// How a unit id answered the probe request
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnitStatus {
+    /// No response was received before the timeout
+    NoResponse,
+    /// The unit answered with an exception
+    Exception(ExceptionCode),
+    /// The unit answered with a valid response
+    Response,
+}
+
+/// Whether a unit supports the read function of a table
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FunctionSupport {
+    /// The function is supported
+    Supported,
+    /// The unit answered with [`ExceptionCode::IllegalFunction`]
+    NotSupported,
+    /// The unit did not answer or answered with another exception
+    Unknown,
+}
+
+/// Capabilities of a unit for a single table
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TableReport {
+    /// Probed table
+    pub table: Table,
+    /// Support of the read function of the table
+    pub support: FunctionSupport,
+    /// Contiguous block of readable addresses containing [`ScanConfig::probe_address`], if any
+    pub readable: Option<RangeInclusive<u16>>,
+}
+
+/// Scan result of a single unit id
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnitReport {
+    /// Scanned unit id
+    pub id: UnitId,
+    /// Answer to the probe request
+    pub status: UnitStatus,
+    /// Capabilities of the unit, empty if it did not respond or capabilities were not probed
+    pub tables: Vec<TableReport>,
+}
+
+/// Result of a scan
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScanReport {
+    /// Report of every scanned unit id
+    pub units: Vec<UnitReport>,
+}
+
+impl ScanReport {
+    /// Iterate over the units that answered the probe request
+    pub fn responding(&self) -> impl Iterator<Item = &UnitReport> {
+        self.units
+            .iter()
+            .filter(|x| x.status != UnitStatus::NoResponse)
+    }
+}
+
+/// Discovers the unit ids that respond on a [`Channel`] and their capabilities
+///
+/// Only read functions are probed because probing writes could modify the state of a device.
+/// Address boundaries are found with binary searches that assume the readable addresses
+/// of a table form a single block containing [`ScanConfig::probe_address`].
+pub struct Scanner {
+    channel: Channel,
+    config: ScanConfig,
+}
+
+const TABLES: [Table; 4] = [
+    Table::Coils,
+    Table::DiscreteInputs,
+    Table::HoldingRegisters,
+    Table::InputRegisters,
+];
+
+impl Scanner {
+    /// Create a scanner that sends requests using `channel`
+    pub fn new(channel: Channel, config: ScanConfig) -> Self {
+        Self { channel, config }
+    }
+
+    /// Scan all the configured unit ids
+    ///
+    /// Fails if the channel is shut down or the connection is lost.
+    pub async fn scan(&mut self) -> Result<ScanReport, RequestError> {
+        let mut report = ScanReport::default();
+        for id in self.config.first_unit..=self.config.last_unit {
+            report.units.push(self.scan_unit(UnitId::new(id)).await?);
+        }
+        Ok(report)
+    }
+
+    /// Scan a single unit id
+    pub async fn scan_unit(&mut self, id: UnitId) -> Result<UnitReport, RequestError> {
+        let status = match self
+            .read_one(id, self.config.probe_table, self.config.probe_address)
+            .await?
+        {
+            Probe::Response => UnitStatus::Response,
+            Probe::Exception(ex) => UnitStatus::Exception(ex),
+            Probe::NoResponse => UnitStatus::NoResponse,
+        };
+
+        let mut tables = Vec::new();
+        if status != UnitStatus::NoResponse && self.config.probe_capabilities {
+            for table in TABLES {
+                tables.push(self.probe_table(id, table).await?);
+            }
+        }
+
+        Ok(UnitReport { id, status, tables })
+    }
+
+    async fn probe_table(&mut self, id: UnitId, table: Table) -> Result<TableReport, RequestError> {
+        let address = self.config.probe_address;
+        let (support, readable) = match self.read_one(id, table, address).await? {
+            Probe::Response => {
+                let channel = self.channel.clone();
+                let param = self.param(id);
+                let readable = |address| {
+                    let mut channel = channel.clone();
+                    async move {
+                        let probe = read_one(&mut channel, param, table, address).await?;
+                        Ok::<_, RequestError>(probe == Probe::Response)
+                    }
+                };
+                let first = find_first_readable(address, readable).await?;
+                let last = find_last_readable(address, readable).await?;
+                (FunctionSupport::Supported, Some(first..=last))
+            }
+            Probe::Exception(ExceptionCode::IllegalFunction) => {
+                (FunctionSupport::NotSupported, None)
+            }
+            Probe::Exception(ExceptionCode::IllegalDataAddress) => {
+                (FunctionSupport::Supported, None)
+            }
+            Probe::Exception(_) | Probe::NoResponse => (FunctionSupport::Unknown, None),
+        };
+
+        Ok(TableReport {
+            table,
+            support,
+            readable,
+        })
+    }
+
+    async fn read_one(
+        &mut self,
+        id: UnitId,
+        table: Table,
+        address: u16,
+    ) -> Result<Probe, RequestError> {
+        let param = self.param(id);
+        read_one(&mut self.channel, param, table, address).await
+    }
+
+    fn param(&self, id: UnitId) -> RequestParam {
+        RequestParam::new(id, self.config.timeout)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Probe {
+    NoResponse,
+    Exception(ExceptionCode),
+    Response,
+}
+
+impl Probe {
+    fn classify<T>(result: Result<T, RequestError>) -> Result<Self, RequestError> {
+        match result {
+            // a malformed response still shows that a device answered
+            Ok(_) | Err(RequestError::BadResponse(_)) => Ok(Probe::Response),
+            Err(RequestError::Exception(ex)) => Ok(Probe::Exception(ex)),
+            Err(RequestError::ResponseTimeout) | Err(RequestError::BadFrame(_)) => {
+                Ok(Probe::NoResponse)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+async fn read_one(
+    channel: &mut Channel,
+    param: RequestParam,
+    table: Table,
+    address: u16,
+) -> Result<Probe, RequestError> {
+    let range = AddressRange::try_from(address, 1)?;
+    match table {
+        Table::Coils => Probe::classify(channel.read_coils(param, range).await),
+        Table::DiscreteInputs => Probe::classify(channel.read_discrete_inputs(param, range).await),
+        Table::HoldingRegisters => {
+            Probe::classify(channel.read_holding_registers(param, range).await)
+        }
+        Table::InputRegisters => Probe::classify(channel.read_input_registers(param, range).await),
+    }
+}
+
+/// Binary search for the first readable address given that `last` is readable
+async fn find_first_readable<F, R, E>(last: u16, mut readable: F) -> Result<u16, E>
+where
+    F: FnMut(u16) -> R,
+    R: Future<Output = Result<bool, E>>,
+{
+    let (mut low, mut high) = (0, last);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if readable(mid).await? {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    Ok(high)
+}
+
+/// Binary search for the last readable address given that `first` is readable
+async fn find_last_readable<F, R, E>(first: u16, mut readable: F) -> Result<u16, E>
+where
+    F: FnMut(u16) -> R,
+    R: Future<Output = Result<bool, E>>,
+{
+    let (mut low, mut high) = (first, u16::MAX);
+    while low < high {
+        let mid = low + (high - low) / 2 + (high - low) % 2;
+        if readable(mid).await? {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    Ok(low)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_probe_results() {
+        assert_eq!(Probe::classify(Ok(())), Ok(Probe::Response));
+        assert_eq!(
+            Probe::classify::<()>(Err(RequestError::Exception(
+                ExceptionCode::IllegalDataAddress
+            ))),
+            Ok(Probe::Exception(ExceptionCode::IllegalDataAddress))
+        );
+        assert_eq!(
+            Probe::classify::<()>(Err(RequestError::ResponseTimeout)),
+            Ok(Probe::NoResponse)
+        );
+        assert_eq!(
+            Probe::classify::<()>(Err(RequestError::Shutdown)),
+            Err(RequestError::Shutdown)
+        );
+    }
+
+    #[tokio::test]
+    async fn finds_the_last_readable_address() {
+        for last in [0, 1, 99, 1000, u16::MAX - 1, u16::MAX] {
+            let mut requests = 0;
+            let found = find_last_readable(0, |address| {
+                requests += 1;
+                async move { Ok::<_, RequestError>(address <= last) }
+            })
+            .await;
+            assert_eq!(found, Ok(last));
+            assert!(requests <= 16);
+        }
+    }
+
+    #[tokio::test]
+    async fn finds_the_first_readable_address() {
+        for first in [0, 1, 99, 1000, u16::MAX - 1, u16::MAX] {
+            let mut requests = 0;
+            let found = find_first_readable(u16::MAX, |address| {
+                requests += 1;
+                async move { Ok::<_, RequestError>(address >= first) }
+            })
+            .await;
+            assert_eq!(found, Ok(first));
+            assert!(requests <= 16);
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::error::RequestError;
+use crate::exception::ExceptionCode;
+use crate::types::UnitId;
+
+/// Upper bounds of the latency histogram buckets in milliseconds
+const LATENCY_BOUNDS_MS: [u64; 12] = [1, 2, 5, 10, 20, 50, 100, 200, 500, 1000, 2000, 5000];
+
+/// Bucket of a [`LatencyHistogram`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LatencyBucket {
+    /// Inclusive upper bound of the bucket or `None` for the last bucket
+    pub upper_bound: Option<Duration>,
+    /// Number of responses whose round-trip time fell into the bucket
+    pub count: u64,
+}
+
+/// Histogram of the round-trip time between sending a request and receiving its response
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    counts: [u64; LATENCY_BOUNDS_MS.len() + 1],
+    total: Duration,
+    max: Duration,
+}
+
+impl LatencyHistogram {
+    /// Iterate over the buckets from the shortest to the longest latency
+    pub fn buckets(&self) -> impl Iterator<Item = LatencyBucket> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(i, count)| LatencyBucket {
+                upper_bound: LATENCY_BOUNDS_MS
+                    .get(i)
+                    .map(|ms| Duration::from_millis(*ms)),
+                count: *count,
+            })
+    }
+
+    /// Number of recorded round trips
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Average round-trip time or `None` if nothing was recorded
+    pub fn mean(&self) -> Option<Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        Some(Duration::from_nanos(
+            (self.total.as_nanos() / count as u128) as u64,
+        ))
+    }
+
+    /// Longest recorded round-trip time
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    pub(crate) fn record(&mut self, latency: Duration) {
+        let bucket = LATENCY_BOUNDS_MS
+            .iter()
+            .position(|ms| latency <= Duration::from_millis(*ms))
+            .unwrap_or(LATENCY_BOUNDS_MS.len());
+        self.counts[bucket] += 1;
+        self.total = self.total.saturating_add(latency);
+        self.max = std::cmp::max(self.max, latency);
+    }
+}
+
+/// Communication statistics of a single unit id
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnitStatistics {
+    /// Number of requests sent, including retries
+    pub requests: u64,
+    /// Number of responses received, including exception responses
+    pub responses: u64,
+    /// Number of requests that timed out
+    pub timeouts: u64,
+    /// Number of exception responses by exception code
+    pub exceptions: BTreeMap<ExceptionCode, u64>,
+    /// Number of malformed frames received while waiting for a response
+    pub bad_frames: u64,
+    /// Round-trip time of the received responses
+    pub latency: LatencyHistogram,
+}
+
+/// Snapshot of the communication statistics of a channel
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChannelStatistics {
+    /// Number of connections (TCP/TLS) or port openings (serial) after the first one
+    pub reconnects: u64,
+    /// Statistics of every unit id the channel communicated with
+    pub units: BTreeMap<UnitId, UnitStatistics>,
+}
+
+impl ChannelStatistics {
+    pub(crate) fn record(
+        &mut self,
+        id: UnitId,
+        result: &Result<(), RequestError>,
+        elapsed: Duration,
+    ) {
+        let unit = self.units.entry(id).or_default();
+
+        let response = match result {
+            Ok(()) | Err(RequestError::BadResponse(_)) => true,
+            Err(RequestError::Exception(ex)) => {
+                *unit.exceptions.entry(*ex).or_default() += 1;
+                true
+            }
+            Err(RequestError::ResponseTimeout) => {
+                unit.timeouts += 1;
+                false
+            }
+            Err(RequestError::BadFrame(_)) => {
+                unit.bad_frames += 1;
+                false
+            }
+            // the request was never sent
+            Err(RequestError::BadRequest(_)) | Err(RequestError::Internal(_)) => return,
+            Err(_) => false,
+        };
+
+        unit.requests += 1;
+        if response {
+            unit.responses += 1;
+            unit.latency.record(elapsed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latency_is_recorded_in_the_matching_bucket() {
+        let mut histogram = LatencyHistogram::default();
+        histogram.record(Duration::from_millis(1));
+        histogram.record(Duration::from_millis(3));
+        histogram.record(Duration::from_secs(10));
+
+        let counts: Vec<u64> = histogram.buckets().map(|x| x.count).collect();
+        assert_eq!(counts, vec![1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(histogram.buckets().last().unwrap().upper_bound, None);
+        assert_eq!(histogram.count(), 3);
+        assert_eq!(histogram.max(), Duration::from_secs(10));
+        assert_eq!(histogram.mean(), Some(Duration::from_nanos(3_334_666_666)));
+    }
+
+    #[test]
+    fn counts_results_per_unit() {
+        let mut stats = ChannelStatistics::default();
+        let id = UnitId::new(3);
+        let elapsed = Duration::from_millis(15);
+
+        stats.record(id, &Ok(()), elapsed);
+        stats.record(id, &Err(RequestError::ResponseTimeout), elapsed);
+        stats.record(
+            id,
+            &Err(RequestError::Exception(ExceptionCode::ServerDeviceBusy)),
+            elapsed,
+        );
+        stats.record(
+            id,
+            &Err(RequestError::Exception(ExceptionCode::ServerDeviceBusy)),
+            elapsed,
+        );
+
+        let unit = &stats.units[&id];
+        assert_eq!(unit.requests, 4);
+        assert_eq!(unit.responses, 3);
+        assert_eq!(unit.timeouts, 1);
+        assert_eq!(unit.exceptions[&ExceptionCode::ServerDeviceBusy], 2);
+        assert_eq!(unit.latency.count(), 3);
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
 use std::time::Duration;
 
 use tracing::Instrument;
@@ -6,8 +8,11 @@ use crate::common::phys::PhysLayer;
 use tokio::time::Instant;
 
 use crate::client::message::{Command, Request, Setting};
+use crate::client::statistics::ChannelStatistics;
 use crate::common::frame::{FrameHeader, FrameWriter, FramedReader, TxId};
+use crate::decode::DecodeSettings;
 use crate::error::*;
+use crate::observer::{Tap, TrafficObserver};
 use crate::DecodeLevel;
 
 /**
@@ -61,13 +66,35 @@ impl SessionError {
     }
 }
 
+/// Request waiting for the delay before its next attempt
+struct PendingRetry {
+    request: Request,
+    attempt: usize,
+    deadline: Instant,
+    /// error of the last attempt, the request fails with it if the session ends first
+    error: RequestError,
+}
+
+/// sleep until the deadline, or forever if there is none
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(x) => tokio::time::sleep_until(x).await,
+        None => std::future::pending().await,
+    }
+}
+
 pub(crate) struct ClientLoop {
     rx: tokio::sync::mpsc::Receiver<Command>,
+    /// requests waiting to be retried, ordered by deadline
+    retries: VecDeque<PendingRetry>,
     writer: FrameWriter,
     reader: FramedReader,
     tx_id: TxId,
-    decode: DecodeLevel,
+    decode: DecodeSettings,
+    observer: Option<Arc<dyn TrafficObserver>>,
     enabled: bool,
+    sessions: u64,
+    statistics: ChannelStatistics,
 }
 
 impl ClientLoop {
@@ -79,11 +106,15 @@ impl ClientLoop {
     ) -> Self {
         Self {
             rx,
+            retries: VecDeque::new(),
             writer,
             reader,
             tx_id: TxId::default(),
-            decode,
+            decode: decode.into(),
+            observer: None,
             enabled: false,
+            sessions: 0,
+            statistics: ChannelStatistics::default(),
         }
     }
 
@@ -95,12 +126,18 @@ impl ClientLoop {
         match cmd {
             Command::Setting(setting) => {
                 self.change_setting(setting);
+                // the observer may have changed
+                self.set_tap(io);
                 if !self.enabled {
                     return Err(SessionError::Disabled);
                 }
                 Ok(())
             }
-            Command::Request(mut request) => self.run_one_request(io, &mut request).await,
+            Command::Request(request) => self.run_one_request(io, request, 1).await,
+            Command::Statistics(reply) => {
+                let _ = reply.send(self.statistics.clone());
+                Ok(())
+            }
         }
     }
 
@@ -117,8 +154,34 @@ impl ClientLoop {
     }
 
     pub(crate) async fn run(&mut self, io: &mut PhysLayer) -> SessionError {
+        if self.sessions > 0 {
+            self.statistics.reconnects += 1;
+        }
+        self.sessions += 1;
+        self.set_tap(io);
+
+        let err = self.run_session(io).await;
+
+        // requests waiting to be retried cannot be retried on this session
+        for mut retry in self.retries.drain(..) {
+            tracing::warn!("request error: {}", retry.error);
+            retry.request.details.fail(retry.error);
+        }
+
+        err
+    }
+
+    async fn run_session(&mut self, io: &mut PhysLayer) -> SessionError {
         loop {
+            let next_retry = self.retries.front().map(|x| x.deadline);
             tokio::select! {
+                _ = sleep_until(next_retry) => {
+                    if let Some(retry) = self.retries.pop_front() {
+                        if let Err(err) = self.run_one_request(io, retry.request, retry.attempt).await {
+                            return err;
+                        }
+                    }
+                }
                 frame = self.reader.next_frame(io, self.decode) => {
                     match frame {
                         Ok(frame) => {
@@ -147,18 +210,59 @@ impl ClientLoop {
         }
     }
 
+    fn set_tap(&mut self, io: &mut PhysLayer) {
+        let tap = Tap::new(self.observer.clone(), self.sessions, io.endpoints());
+        self.writer.set_tap(tap.clone());
+        self.reader.set_tap(tap.clone());
+        io.set_tap(tap);
+    }
+
+    /// Execute one attempt of a request, scheduling a retry if the policy allows one
+    ///
+    /// Other commands are served while a request waits to be retried
     async fn run_one_request(
         &mut self,
         io: &mut PhysLayer,
-        request: &mut Request,
+        mut request: Request,
+        attempt: usize,
     ) -> Result<(), SessionError> {
+        // e.g. a gateway that already answered its own client, there is nobody to respond to
+        if request.details.is_cancelled() {
+            tracing::debug!("skipping request whose caller is gone");
+            return Ok(());
+        }
+
         let tx_id = self.tx_id.next();
+        let start = Instant::now();
         let result = self
-            .execute_request(io, request, tx_id)
+            .execute_request(io, &mut request, tx_id)
             .instrument(tracing::info_span!("Transaction", tx_id = %tx_id))
             .await;
+        self.statistics.record(request.id, &result, start.elapsed());
 
         if let Err(err) = result {
+            if SessionError::from(&err).is_none()
+                && request
+                    .retry
+                    .should_retry(attempt, request.details.is_idempotent(), &err)
+            {
+                let delay = request.retry.delay(attempt);
+                tracing::warn!(
+                    "request error: {} - retrying in {:?} (attempt {} of {})",
+                    err,
+                    delay,
+                    attempt + 1,
+                    request.retry.max_attempts
+                );
+                self.schedule_retry(PendingRetry {
+                    request,
+                    attempt: attempt + 1,
+                    deadline: Instant::now() + delay,
+                    error: err,
+                });
+                return Ok(());
+            }
+
             // Fail the request in ONE place. If the whole future
             // gets dropped, then the request gets failed with Shutdown
             tracing::warn!("request error: {}", err);
@@ -174,6 +278,13 @@ impl ClientLoop {
         Ok(())
     }
 
+    fn schedule_retry(&mut self, retry: PendingRetry) {
+        let index = self
+            .retries
+            .partition_point(|x| x.deadline <= retry.deadline);
+        self.retries.insert(index, retry);
+    }
+
     async fn execute_request(
         &mut self,
         io: &mut PhysLayer,
@@ -187,7 +298,7 @@ impl ClientLoop {
             self.decode,
         )?;
 
-        io.write(bytes, self.decode.physical).await?;
+        io.write(bytes, self.decode).await?;
 
         let deadline = Instant::now() + request.timeout;
 
@@ -215,14 +326,21 @@ impl ClientLoop {
 
         // once we have a response, handle it. This may complete a promise
         // successfully or bubble up an error
-        request.handle_response(response.payload(), self.decode.app)
+        request.handle_response(response.payload(), self.decode)
     }
 
     pub(crate) fn change_setting(&mut self, setting: Setting) {
         match setting {
             Setting::DecodeLevel(level) => {
                 tracing::info!("Decode level changed: {:?}", level);
-                self.decode = level;
+                self.decode.set_level(level);
+            }
+            Setting::DecodeFormat(format) => {
+                tracing::info!("Decode format changed: {:?}", format);
+                self.decode.format = format;
+            }
+            Setting::TrafficObserver(observer) => {
+                self.observer = observer;
             }
             Setting::Enable => {
                 if !self.enabled {
@@ -236,6 +354,9 @@ impl ClientLoop {
                     tracing::info!("channel disabled");
                 }
             }
+            Setting::ResetStatistics => {
+                self.statistics = ChannelStatistics::default();
+            }
         }
     }
 
@@ -246,6 +367,9 @@ impl ClientLoop {
                 Command::Request(mut req) => {
                     req.details.fail(RequestError::NoConnection);
                 }
+                Command::Statistics(reply) => {
+                    let _ = reply.send(self.statistics.clone());
+                }
                 Command::Setting(x) => {
                     self.change_setting(x);
                     if !self.enabled {
@@ -282,7 +406,8 @@ mod tests {
     use std::io::ErrorKind;
 
     use super::*;
-    use crate::client::{Channel, RequestParam};
+    use crate::client::{CallbackSession, Channel, RequestParam, RetryPolicy};
+    use crate::common::frame::FunctionField;
     use crate::common::function::FunctionCode;
     use crate::common::traits::{Loggable, Serialize};
     use crate::decode::*;
@@ -291,6 +416,7 @@ mod tests {
     use crate::{ExceptionCode, Indexed, ReadBitsRange};
 
     use sfio_tokio_mock_io::Event;
+    use tokio::sync::oneshot::error::TryRecvError;
 
     fn spawn_client_loop() -> (
         Channel,
@@ -309,18 +435,38 @@ mod tests {
             let mut phys = PhysLayer::new_mock(mock);
             client_loop.run(&mut phys).await
         });
-        let channel = Channel { tx };
+        let channel = Channel::new(tx);
         (channel, join_handle, io_handle)
     }
 
+    fn get_busy_response(tx_id: TxId, function: FunctionCode) -> Vec<u8> {
+        let mut fmt = FrameWriter::tcp();
+        let bytes = fmt
+            .format_ex(
+                FrameHeader::new_tcp_header(UnitId::new(1), tx_id),
+                FunctionField::Valid(function),
+                ExceptionCode::ServerDeviceBusy,
+                DecodeSettings::default(),
+            )
+            .unwrap();
+        bytes.to_vec()
+    }
+
     fn get_framed_adu<T>(function: FunctionCode, payload: &T) -> Vec<u8>
+    where
+        T: Serialize + Loggable + Sized,
+    {
+        get_framed_adu_with_tx_id(TxId::new(0), function, payload)
+    }
+
+    fn get_framed_adu_with_tx_id<T>(tx_id: TxId, function: FunctionCode, payload: &T) -> Vec<u8>
     where
         T: Serialize + Loggable + Sized,
     {
         let mut fmt = FrameWriter::tcp();
-        let header = FrameHeader::new_tcp_header(UnitId::new(1), TxId::new(0));
+        let header = FrameHeader::new_tcp_header(UnitId::new(1), tx_id);
         let bytes = fmt
-            .format_request(header, function, payload, DecodeLevel::nothing())
+            .format_request(header, function, payload, DecodeSettings::default())
             .unwrap();
         Vec::from(bytes)
     }
@@ -379,6 +525,64 @@ mod tests {
         assert_eq!(result, Err(RequestError::ResponseTimeout));
     }
 
+    #[tokio::test]
+    async fn skips_queued_requests_whose_caller_is_gone() {
+        let (channel, _task, mut io) = spawn_client_loop();
+        let param = RequestParam::new(UnitId::new(1), Duration::from_secs(1));
+
+        let first = AddressRange::try_from(1, 1).unwrap();
+        let abandoned = AddressRange::try_from(2, 1).unwrap();
+        let last = AddressRange::try_from(3, 1).unwrap();
+
+        let mut first_channel = channel.clone();
+        let first_task = tokio::spawn(async move { first_channel.read_coils(param, first).await });
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_adu(FunctionCode::ReadCoils, &first))
+        );
+
+        // queue a request behind the first one and then drop its caller
+        let mut abandoned_channel = channel.clone();
+        let abandoned_task =
+            tokio::spawn(async move { abandoned_channel.read_coils(param, abandoned).await });
+        tokio::task::yield_now().await;
+        abandoned_task.abort();
+        assert!(abandoned_task.await.unwrap_err().is_cancelled());
+
+        let mut last_channel = channel.clone();
+        let last_task = tokio::spawn(async move { last_channel.read_coils(param, last).await });
+        tokio::task::yield_now().await;
+
+        io.read(&get_framed_adu(
+            FunctionCode::ReadCoils,
+            &BitWriter::new(ReadBitsRange { inner: first }, |_| Ok(true)),
+        ));
+        assert_eq!(
+            first_task.await.unwrap().unwrap(),
+            vec![Indexed::new(1, true)]
+        );
+
+        // the abandoned request is never written
+        assert_eq!(io.next_event().await, Event::Read);
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_adu_with_tx_id(
+                TxId::new(1),
+                FunctionCode::ReadCoils,
+                &last
+            ))
+        );
+        io.read(&get_framed_adu_with_tx_id(
+            TxId::new(1),
+            FunctionCode::ReadCoils,
+            &BitWriter::new(ReadBitsRange { inner: last }, |_| Ok(false)),
+        ));
+        assert_eq!(
+            last_task.await.unwrap().unwrap(),
+            vec![Indexed::new(3, false)]
+        );
+    }
+
     #[tokio::test]
     async fn returns_shutdown_when_task_dropped() {
         let (mut channel, task, mut io) = spawn_client_loop();
@@ -449,4 +653,349 @@ mod tests {
             vec![Indexed::new(7, true), Indexed::new(8, false)]
         );
     }
+
+    type Record = (&'static str, crate::Direction, Option<u8>, Vec<u8>);
+
+    #[derive(Default)]
+    struct Recorder {
+        events: std::sync::Mutex<Vec<Record>>,
+    }
+
+    impl Recorder {
+        fn record(&self, layer: &'static str, event: crate::TrafficEvent) {
+            assert_eq!(event.session, 1);
+            self.events.lock().unwrap().push((
+                layer,
+                event.direction,
+                event.function,
+                event.bytes.to_vec(),
+            ));
+        }
+    }
+
+    impl TrafficObserver for Recorder {
+        fn on_physical(&self, event: crate::TrafficEvent) {
+            self.record("phys", event);
+        }
+
+        fn on_frame(&self, event: crate::TrafficEvent) {
+            self.record("frame", event);
+        }
+
+        fn on_pdu(&self, event: crate::TrafficEvent) {
+            self.record("pdu", event);
+        }
+    }
+
+    #[tokio::test]
+    async fn observer_receives_transmitted_and_received_traffic() {
+        use crate::Direction::*;
+
+        let (mut channel, _task, mut io) = spawn_client_loop();
+        let recorder = Arc::new(Recorder::default());
+        channel.enable().await.unwrap();
+        channel
+            .set_traffic_observer(Some(recorder.clone()))
+            .await
+            .unwrap();
+
+        let range = AddressRange::try_from(7, 2).unwrap();
+        let request = get_framed_adu(FunctionCode::ReadCoils, &range);
+        let response = get_framed_adu(
+            FunctionCode::ReadCoils,
+            &BitWriter::new(ReadBitsRange { inner: range }, |_| Ok(true)),
+        );
+
+        let coils = tokio::spawn(async move {
+            channel
+                .read_coils(
+                    RequestParam::new(UnitId::new(1), Duration::from_secs(1)),
+                    range,
+                )
+                .await
+        });
+
+        assert_eq!(io.next_event().await, Event::Write(request.clone()));
+        io.read(&response);
+        coils.await.unwrap().unwrap();
+
+        let function = Some(0x01);
+        assert_eq!(
+            *recorder.events.lock().unwrap(),
+            vec![
+                ("frame", Tx, function, request.clone()),
+                ("pdu", Tx, function, request[7..].to_vec()),
+                ("phys", Tx, None, request),
+                ("phys", Rx, None, response.clone()),
+                ("frame", Rx, function, response.clone()),
+                ("pdu", Rx, function, response[7..].to_vec()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn retries_read_after_timeout_according_to_policy() {
+        let (channel, _task, mut io) = spawn_client_loop();
+
+        let range = AddressRange::try_from(7, 2).unwrap();
+        let retry = RetryPolicy::new(2, Duration::from_millis(100), Duration::from_secs(1));
+
+        let coils = tokio::spawn(async move {
+            channel
+                .with_retry_policy(retry)
+                .read_coils(
+                    RequestParam::new(UnitId::new(1), Duration::from_secs(1)),
+                    range,
+                )
+                .await
+        });
+
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_adu(FunctionCode::ReadCoils, &range))
+        );
+
+        // the first attempt times out, the retry uses the next transaction id
+        tokio::time::pause();
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_adu_with_tx_id(
+                TxId::new(1),
+                FunctionCode::ReadCoils,
+                &range
+            ))
+        );
+        tokio::time::resume();
+
+        io.read(&get_framed_adu_with_tx_id(
+            TxId::new(1),
+            FunctionCode::ReadCoils,
+            &BitWriter::new(ReadBitsRange { inner: range }, |_| Ok(true)),
+        ));
+
+        assert_eq!(
+            coils.await.unwrap().unwrap(),
+            vec![Indexed::new(7, true), Indexed::new(8, true)]
+        );
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_writes_unless_allowed() {
+        let (channel, _task, mut io) = spawn_client_loop();
+
+        let value = Indexed::new(1, 0xCAFE);
+        let retry = RetryPolicy::new(3, Duration::ZERO, Duration::ZERO);
+
+        let write = tokio::spawn(async move {
+            channel
+                .with_retry_policy(retry)
+                .write_single_register(
+                    RequestParam::new(UnitId::new(1), Duration::from_secs(1)),
+                    value,
+                )
+                .await
+        });
+
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_adu(FunctionCode::WriteSingleRegister, &value))
+        );
+        let mut fmt = FrameWriter::tcp();
+        let response = fmt
+            .format_ex(
+                FrameHeader::new_tcp_header(UnitId::new(1), TxId::new(0)),
+                FunctionField::Valid(FunctionCode::WriteSingleRegister),
+                ExceptionCode::ServerDeviceBusy,
+                DecodeSettings::default(),
+            )
+            .unwrap();
+        io.read(response);
+
+        assert_eq!(
+            write.await.unwrap(),
+            Err(RequestError::Exception(ExceptionCode::ServerDeviceBusy))
+        );
+        // the request was not written again
+        while let Some(event) = io.pop_event() {
+            assert_eq!(event, Event::Read);
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_requests_in_the_order_of_their_deadlines() {
+        let (channel, _task, mut io) = spawn_client_loop();
+        tokio::time::pause();
+
+        let value = Indexed::new(1, 0xCAFE);
+        let range = AddressRange::try_from(7, 1).unwrap();
+        let param = RequestParam::new(UnitId::new(1), Duration::from_secs(1));
+
+        let retry =
+            RetryPolicy::new(2, Duration::from_secs(1), Duration::from_secs(1)).allow_writes();
+        let mut write_channel = channel.with_retry_policy(retry);
+        let write =
+            tokio::spawn(async move { write_channel.write_single_register(param, value).await });
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_adu(FunctionCode::WriteSingleRegister, &value))
+        );
+        io.read(&get_busy_response(
+            TxId::new(0),
+            FunctionCode::WriteSingleRegister,
+        ));
+
+        // the read fails while the write waits and is retried much later than the write
+        let retry = RetryPolicy::new(2, Duration::from_secs(5), Duration::from_secs(5));
+        let mut read_channel = channel.with_retry_policy(retry);
+        let read = tokio::spawn(async move { read_channel.read_coils(param, range).await });
+        assert_eq!(io.next_event().await, Event::Read);
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_adu_with_tx_id(
+                TxId::new(1),
+                FunctionCode::ReadCoils,
+                &range
+            ))
+        );
+        io.read(&get_busy_response(TxId::new(1), FunctionCode::ReadCoils));
+
+        // waiting for the retry of the read does not hold back the retry of the write
+        assert_eq!(io.next_event().await, Event::Read);
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_adu_with_tx_id(
+                TxId::new(2),
+                FunctionCode::WriteSingleRegister,
+                &value
+            ))
+        );
+        io.read(&get_framed_adu_with_tx_id(
+            TxId::new(2),
+            FunctionCode::WriteSingleRegister,
+            &value,
+        ));
+        assert_eq!(write.await.unwrap(), Ok(value));
+
+        assert_eq!(io.next_event().await, Event::Read);
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_adu_with_tx_id(
+                TxId::new(3),
+                FunctionCode::ReadCoils,
+                &range
+            ))
+        );
+        io.read(&get_framed_adu_with_tx_id(
+            TxId::new(3),
+            FunctionCode::ReadCoils,
+            &BitWriter::new(ReadBitsRange { inner: range }, |_| Ok(true)),
+        ));
+        assert_eq!(read.await.unwrap(), Ok(vec![Indexed::new(7, true)]));
+    }
+
+    #[tokio::test]
+    async fn serves_other_requests_while_waiting_to_retry() {
+        let (channel, _task, mut io) = spawn_client_loop();
+
+        let value = Indexed::new(1, 0xCAFE);
+        let range = AddressRange::try_from(7, 2).unwrap();
+        let retry = RetryPolicy::new(2, Duration::from_secs(3600), Duration::from_secs(3600))
+            .allow_writes();
+
+        // the callback session does not keep the channel open while the write is queued
+        let (tx, mut write) = tokio::sync::oneshot::channel();
+        CallbackSession::new(
+            channel.with_retry_policy(retry),
+            RequestParam::new(UnitId::new(1), Duration::from_secs(1)),
+        )
+        .write_single_register(value, |result| tx.send(result).unwrap())
+        .await;
+
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_adu(FunctionCode::WriteSingleRegister, &value))
+        );
+        io.read(&get_busy_response(
+            TxId::new(0),
+            FunctionCode::WriteSingleRegister,
+        ));
+
+        // the write now waits an hour before it is retried
+        let mut read_channel = channel.clone();
+        let coils = tokio::spawn(async move {
+            read_channel
+                .read_coils(
+                    RequestParam::new(UnitId::new(1), Duration::from_secs(1)),
+                    range,
+                )
+                .await
+        });
+        assert_eq!(io.next_event().await, Event::Read);
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_adu_with_tx_id(
+                TxId::new(1),
+                FunctionCode::ReadCoils,
+                &range
+            ))
+        );
+        io.read(&get_framed_adu_with_tx_id(
+            TxId::new(1),
+            FunctionCode::ReadCoils,
+            &BitWriter::new(ReadBitsRange { inner: range }, |_| Ok(true)),
+        ));
+        assert_eq!(
+            coils.await.unwrap().unwrap(),
+            vec![Indexed::new(7, true), Indexed::new(8, true)]
+        );
+
+        let stats = channel.statistics().await.unwrap();
+        assert_eq!(stats.units[&UnitId::new(1)].requests, 2);
+        assert_eq!(write.try_recv(), Err(TryRecvError::Empty));
+
+        // shutting down the channel fails the waiting request with the error of the last attempt
+        drop(channel);
+        assert_eq!(
+            write.await.unwrap(),
+            Err(RequestError::Exception(ExceptionCode::ServerDeviceBusy))
+        );
+    }
+
+    #[tokio::test]
+    async fn statistics_count_requests_and_responses_per_unit() {
+        let (channel, _task, mut io) = spawn_client_loop();
+        // settings received by a disabled session terminate it
+        channel.enable().await.unwrap();
+
+        let range = AddressRange::try_from(7, 2).unwrap();
+        let request = get_framed_adu(FunctionCode::ReadCoils, &range);
+        let response = get_framed_adu(
+            FunctionCode::ReadCoils,
+            &BitWriter::new(ReadBitsRange { inner: range }, |_| Ok(false)),
+        );
+
+        let mut request_channel = channel.clone();
+        let coils = tokio::spawn(async move {
+            request_channel
+                .read_coils(
+                    RequestParam::new(UnitId::new(1), Duration::from_secs(1)),
+                    range,
+                )
+                .await
+        });
+
+        assert_eq!(io.next_event().await, Event::Write(request));
+        io.read(&response);
+        assert!(coils.await.unwrap().is_ok());
+
+        let stats = channel.statistics().await.unwrap();
+        let unit = &stats.units[&UnitId::new(1)];
+        assert_eq!(stats.reconnects, 0);
+        assert_eq!(unit.requests, 1);
+        assert_eq!(unit.responses, 1);
+        assert_eq!(unit.latency.count(), 1);
+
+        channel.reset_statistics().await.unwrap();
+        assert!(channel.statistics().await.unwrap().units.is_empty());
+    }
 }
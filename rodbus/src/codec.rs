@@ -0,0 +1,305 @@
+use crate::client::WriteMultiple;
+use crate::error::{AduParseError, InvalidRequest};
+use crate::types::Indexed;
+
+/// Order in which the bytes of a multi-register value are stored
+///
+/// The letters name the bytes of the value from the most significant (`A`) to the least
+/// significant, in the order they appear when the registers are read from the lowest address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WordOrder {
+    /// Big-endian words and bytes, e.g. `0x11223344` is stored as `[0x1122, 0x3344]`
+    Abcd,
+    /// Big-endian words with swapped bytes, e.g. `0x11223344` is stored as `[0x2211, 0x4433]`
+    Badc,
+    /// Little-endian words with big-endian bytes, e.g. `0x11223344` is stored as `[0x3344, 0x1122]`
+    Cdab,
+    /// Little-endian words and bytes, e.g. `0x11223344` is stored as `[0x4433, 0x2211]`
+    Dcba,
+}
+
+impl WordOrder {
+    fn swaps_words(self) -> bool {
+        matches!(self, WordOrder::Cdab | WordOrder::Dcba)
+    }
+
+    fn swaps_bytes(self) -> bool {
+        matches!(self, WordOrder::Badc | WordOrder::Dcba)
+    }
+
+    // strings are not a single value, so their characters always start at the lowest address
+    fn of_string(self) -> Self {
+        if self.swaps_bytes() {
+            WordOrder::Badc
+        } else {
+            WordOrder::Abcd
+        }
+    }
+
+    fn to_bytes(self, registers: &[u16]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(2 * registers.len());
+        let mut push = |register: &u16| {
+            let [high, low] = register.to_be_bytes();
+            if self.swaps_bytes() {
+                bytes.extend([low, high]);
+            } else {
+                bytes.extend([high, low]);
+            }
+        };
+
+        if self.swaps_words() {
+            registers.iter().rev().for_each(&mut push);
+        } else {
+            registers.iter().for_each(&mut push);
+        }
+
+        bytes
+    }
+
+    fn to_registers(self, bytes: &[u8]) -> Vec<u16> {
+        let mut registers: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| {
+                let pair = [pair[0], pair[1]];
+                if self.swaps_bytes() {
+                    u16::from_le_bytes(pair)
+                } else {
+                    u16::from_be_bytes(pair)
+                }
+            })
+            .collect();
+
+        if self.swaps_words() {
+            registers.reverse();
+        }
+
+        registers
+    }
+}
+
+/// Value that is stored across a fixed number of consecutive registers
+pub trait RegisterCodec: Sized {
+    /// Number of registers occupied by a value
+    const REGISTERS: u16;
+
+    /// Decode a value from its registers or return `None` if the slice has the wrong length
+    fn decode(registers: &[u16], order: WordOrder) -> Option<Self>;
+
+    /// Encode the value into [`RegisterCodec::REGISTERS`] registers
+    fn encode(&self, order: WordOrder) -> Vec<u16>;
+}
+
+macro_rules! impl_register_codec {
+    ($type:ty) => {
+        impl RegisterCodec for $type {
+            const REGISTERS: u16 = (std::mem::size_of::<$type>() / 2) as u16;
+
+            fn decode(registers: &[u16], order: WordOrder) -> Option<Self> {
+                let bytes = order.to_bytes(registers).try_into().ok()?;
+                Some(<$type>::from_be_bytes(bytes))
+            }
+
+            fn encode(&self, order: WordOrder) -> Vec<u16> {
+                order.to_registers(&self.to_be_bytes())
+            }
+        }
+    };
+}
+
+impl_register_codec!(u32);
+impl_register_codec!(i32);
+impl_register_codec!(f32);
+impl_register_codec!(u64);
+impl_register_codec!(i64);
+impl_register_codec!(f64);
+
+/// Decode consecutive values from registers, e.g. the [`crate::RegisterIterator`] of a read
+///
+/// The index of every value is the address of its first register. Fails if the number of
+/// registers is not a multiple of [`RegisterCodec::REGISTERS`].
+pub fn decode_values<T, I>(registers: I, order: WordOrder) -> Result<Vec<Indexed<T>>, AduParseError>
+where
+    T: RegisterCodec,
+    I: IntoIterator<Item = Indexed<u16>>,
+{
+    let registers: Vec<Indexed<u16>> = registers.into_iter().collect();
+    let values: Vec<u16> = registers.iter().map(|x| x.value).collect();
+
+    let size = T::REGISTERS as usize;
+    let chunks = values.chunks_exact(size);
+    if !chunks.remainder().is_empty() {
+        return Err(AduParseError::InsufficientBytes);
+    }
+
+    Ok(chunks
+        .zip(registers.iter().step_by(size))
+        .filter_map(|(chunk, first)| T::decode(chunk, order).map(|x| Indexed::new(first.index, x)))
+        .collect())
+}
+
+/// Encode values into a [`WriteMultiple`] request starting at `start`
+pub fn encode_values<T>(
+    start: u16,
+    values: &[T],
+    order: WordOrder,
+) -> Result<WriteMultiple<u16>, InvalidRequest>
+where
+    T: RegisterCodec,
+{
+    let registers = values.iter().flat_map(|x| x.encode(order)).collect();
+    WriteMultiple::from(start, registers)
+}
+
+/// Decode an ASCII string stored two characters per register
+///
+/// Only the byte order of `order` applies: the first characters are always stored in the
+/// lowest register, so [`WordOrder::Cdab`] reads like [`WordOrder::Abcd`] and
+/// [`WordOrder::Dcba`] like [`WordOrder::Badc`]. Trailing NUL characters used as padding are
+/// removed.
+pub fn decode_ascii<I>(registers: I, order: WordOrder) -> Result<String, AduParseError>
+where
+    I: IntoIterator<Item = Indexed<u16>>,
+{
+    let registers: Vec<u16> = registers.into_iter().map(|x| x.value).collect();
+    let mut bytes = order.of_string().to_bytes(&registers);
+
+    while bytes.last() == Some(&0) {
+        bytes.pop();
+    }
+
+    if !bytes.is_ascii() {
+        return Err(AduParseError::NonAsciiString);
+    }
+
+    // ASCII is always valid UTF-8
+    Ok(bytes.into_iter().map(char::from).collect())
+}
+
+/// Encode an ASCII string into a [`WriteMultiple`] request of `count` registers
+///
+/// The string is padded with NUL characters to fill all the registers. As in [`decode_ascii`],
+/// only the byte order of `order` applies.
+pub fn encode_ascii(
+    start: u16,
+    value: &str,
+    count: u16,
+    order: WordOrder,
+) -> Result<WriteMultiple<u16>, InvalidRequest> {
+    if !value.is_ascii() {
+        return Err(InvalidRequest::NonAsciiString);
+    }
+
+    let capacity = 2 * count as usize;
+    if value.len() > capacity {
+        return Err(InvalidRequest::StringTooLong(value.len(), capacity));
+    }
+
+    let mut bytes = value.as_bytes().to_vec();
+    bytes.resize(capacity, 0);
+    WriteMultiple::from(start, order.of_string().to_registers(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORDERS: [WordOrder; 4] = [
+        WordOrder::Abcd,
+        WordOrder::Badc,
+        WordOrder::Cdab,
+        WordOrder::Dcba,
+    ];
+
+    fn indexed(start: u16, values: &[u16]) -> Vec<Indexed<u16>> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, x)| Indexed::new(start + i as u16, *x))
+            .collect()
+    }
+
+    #[test]
+    fn encodes_u32_in_all_word_orders() {
+        assert_eq!(0x11223344u32.encode(WordOrder::Abcd), vec![0x1122, 0x3344]);
+        assert_eq!(0x11223344u32.encode(WordOrder::Badc), vec![0x2211, 0x4433]);
+        assert_eq!(0x11223344u32.encode(WordOrder::Cdab), vec![0x3344, 0x1122]);
+        assert_eq!(0x11223344u32.encode(WordOrder::Dcba), vec![0x4433, 0x2211]);
+    }
+
+    #[test]
+    fn encodes_f64_in_little_endian_word_order() {
+        // 1.0 == 0x3FF0_0000_0000_0000
+        assert_eq!(1.0f64.encode(WordOrder::Cdab), vec![0, 0, 0, 0x3FF0]);
+    }
+
+    #[test]
+    fn values_round_trip_in_all_word_orders() {
+        for order in ORDERS {
+            assert_eq!(f32::decode(&1.5f32.encode(order), order), Some(1.5));
+            assert_eq!(i64::decode(&(-7i64).encode(order), order), Some(-7));
+            assert_eq!(f64::decode(&1e-3f64.encode(order), order), Some(1e-3));
+        }
+    }
+
+    #[test]
+    fn decodes_consecutive_values_with_the_address_of_their_first_register() {
+        let registers = indexed(10, &[0x3FC0, 0x0000, 0x4000, 0x0000]);
+        assert_eq!(
+            decode_values::<f32, _>(registers, WordOrder::Abcd),
+            Ok(vec![Indexed::new(10, 1.5), Indexed::new(12, 2.0)])
+        );
+    }
+
+    #[test]
+    fn rejects_incomplete_values() {
+        let registers = indexed(0, &[0, 0, 0]);
+        assert_eq!(
+            decode_values::<u32, _>(registers, WordOrder::Abcd),
+            Err(AduParseError::InsufficientBytes)
+        );
+    }
+
+    #[test]
+    fn strings_are_padded_and_trimmed() {
+        let request = encode_ascii(5, "abc", 3, WordOrder::Badc).unwrap();
+        assert_eq!(request.values, vec![0x6261, 0x0063, 0x0000]);
+        assert_eq!(
+            decode_ascii(indexed(5, &request.values), WordOrder::Badc),
+            Ok("abc".to_string())
+        );
+    }
+
+    #[test]
+    fn strings_keep_their_characters_in_register_order() {
+        let expected = [
+            (WordOrder::Abcd, [0x6162, 0x6364, 0x6566]),
+            (WordOrder::Badc, [0x6261, 0x6463, 0x6665]),
+            (WordOrder::Cdab, [0x6162, 0x6364, 0x6566]),
+            (WordOrder::Dcba, [0x6261, 0x6463, 0x6665]),
+        ];
+        for (order, registers) in expected {
+            let request = encode_ascii(0, "abcdef", 3, order).unwrap();
+            assert_eq!(request.values, registers, "{order:?}");
+            assert_eq!(
+                decode_ascii(indexed(0, &registers), order),
+                Ok("abcdef".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_strings() {
+        assert_eq!(
+            encode_ascii(0, "abcde", 2, WordOrder::Abcd).unwrap_err(),
+            InvalidRequest::StringTooLong(5, 4)
+        );
+        assert_eq!(
+            encode_ascii(0, "é", 2, WordOrder::Abcd).unwrap_err(),
+            InvalidRequest::NonAsciiString
+        );
+        assert_eq!(
+            decode_ascii(indexed(0, &[0xFF41]), WordOrder::Abcd),
+            Err(AduParseError::NonAsciiString)
+        );
+    }
+}
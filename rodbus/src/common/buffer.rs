@@ -1,7 +1,7 @@
 use crate::common::phys::PhysLayer;
 
+use crate::decode::DecodeSettings;
 use crate::error::InternalError;
-use crate::PhysDecodeLevel;
 
 pub(crate) struct ReadBuffer {
     buffer: [u8; crate::common::frame::constants::MAX_FRAME_LENGTH],
@@ -78,10 +78,24 @@ impl ReadBuffer {
         Ok((b2 << 8) | b1)
     }
 
+    /// Copy bytes into the free space of the buffer, returning how many were copied
+    pub(crate) fn extend(&mut self, bytes: &[u8]) -> usize {
+        // shift any unread bytes to the front to make the most room possible
+        let length = self.len();
+        self.buffer.copy_within(self.begin..self.end, 0);
+        self.begin = 0;
+        self.end = length;
+
+        let count = std::cmp::min(bytes.len(), self.buffer.len() - self.end);
+        self.buffer[self.end..self.end + count].copy_from_slice(&bytes[..count]);
+        self.end += count;
+        count
+    }
+
     pub(crate) async fn read_some(
         &mut self,
         io: &mut PhysLayer,
-        decode_level: PhysDecodeLevel,
+        decode_level: DecodeSettings,
     ) -> Result<usize, std::io::Error> {
         // before we read any data, check to see if the buffer is empty and adjust the indices
         // this allows use to make the biggest read possible, and avoids subsequent buffer shifting later
@@ -111,7 +125,7 @@ impl ReadBuffer {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::decode::PhysDecodeLevel;
+    use crate::decode::DecodeSettings;
     use tokio_test::*;
 
     #[test]
@@ -136,14 +150,14 @@ mod tests {
 
         {
             let mut task =
-                tokio_test::task::spawn(buffer.read_some(&mut phys, PhysDecodeLevel::Nothing));
+                tokio_test::task::spawn(buffer.read_some(&mut phys, DecodeSettings::default()));
             tokio_test::assert_pending!(task.poll());
         }
 
         {
             let mut task = task::spawn(async {
                 buffer
-                    .read_some(&mut phys, PhysDecodeLevel::Nothing)
+                    .read_some(&mut phys, DecodeSettings::default())
                     .await
                     .unwrap()
             });
@@ -156,7 +170,7 @@ mod tests {
         {
             let mut task = task::spawn(async {
                 buffer
-                    .read_some(&mut phys, PhysDecodeLevel::Nothing)
+                    .read_some(&mut phys, DecodeSettings::default())
                     .await
                     .unwrap()
             });
@@ -3,11 +3,15 @@ use std::ops::Range;
 
 use crate::common::buffer::ReadBuffer;
 use crate::common::function::FunctionCode;
+use crate::common::structured;
 use crate::common::traits::{Loggable, LoggableDisplay, Serialize};
+use crate::decode::DecodeSettings;
+use crate::decoder::MessageType;
 use crate::error::RequestError;
+use crate::observer::{Direction, Tap};
 use crate::tcp::frame::{MbapDisplay, MbapHeader, MbapParser};
 use crate::types::UnitId;
-use crate::{DecodeLevel, ExceptionCode, FrameDecodeLevel};
+use crate::ExceptionCode;
 
 use scursor::WriteCursor;
 
@@ -168,6 +172,28 @@ impl Frame {
     pub(crate) fn payload(&self) -> &[u8] {
         &self.pdu[0..self.length]
     }
+
+    /// Reassemble the complete frame, i.e. the header followed by the PDU and the CRC in RTU
+    pub(crate) fn to_adu(&self) -> Vec<u8> {
+        let unit_id = self.header.destination.value();
+        let mut adu = Vec::with_capacity(self.length + 7);
+        match self.header.tx_id {
+            Some(tx_id) => {
+                adu.extend(tx_id.to_u16().to_be_bytes());
+                adu.extend(0u16.to_be_bytes()); // protocol id
+                adu.extend((self.length as u16 + 1).to_be_bytes());
+                adu.push(unit_id);
+                adu.extend_from_slice(self.payload());
+            }
+            None => {
+                adu.push(unit_id);
+                adu.extend_from_slice(self.payload());
+                #[cfg(feature = "serial")]
+                adu.extend(crate::serial::frame::CRC.checksum(&adu).to_le_bytes());
+            }
+        }
+        adu
+    }
 }
 
 ///  Defines an interface for parsing frames (TCP or RTU)
@@ -187,7 +213,7 @@ impl FrameParser {
     pub(crate) fn parse(
         &mut self,
         cursor: &mut ReadBuffer,
-        decode_level: FrameDecodeLevel,
+        decode_level: DecodeSettings,
     ) -> Result<Option<Frame>, RequestError> {
         match self {
             #[cfg(feature = "serial")]
@@ -254,6 +280,7 @@ impl FormatType {
 pub(crate) struct FrameWriter {
     format_type: FormatType,
     buffer: [u8; constants::MAX_FRAME_LENGTH],
+    tap: Tap,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -299,20 +326,31 @@ impl FrameWriter {
         Self {
             format_type,
             buffer: [0; constants::MAX_FRAME_LENGTH],
+            tap: Tap::default(),
         }
     }
 
+    pub(crate) fn set_tap(&mut self, tap: Tap) {
+        self.tap = tap;
+    }
+
     pub(crate) fn format_reply<T>(
         &mut self,
         header: FrameHeader,
         function: FunctionCode,
         body: &T,
-        decode_level: DecodeLevel,
+        decode_level: DecodeSettings,
     ) -> Result<&[u8], RequestError>
     where
         T: Serialize + Loggable,
     {
-        match self.format_generic(header, FunctionField::Valid(function), body, decode_level) {
+        match self.format_generic(
+            header,
+            MessageType::Response,
+            FunctionField::Valid(function),
+            body,
+            decode_level,
+        ) {
             Ok(x) => Ok(&self.buffer[x]),
             Err(RequestError::Exception(ex)) => {
                 self.format_ex(header, FunctionField::Exception(function), ex, decode_level)
@@ -326,13 +364,18 @@ impl FrameWriter {
         header: FrameHeader,
         function: FunctionCode,
         body: &T,
-        decode_level: DecodeLevel,
+        decode_level: DecodeSettings,
     ) -> Result<&[u8], RequestError>
     where
         T: Serialize + Loggable,
     {
-        let range =
-            self.format_generic(header, FunctionField::Valid(function), body, decode_level)?;
+        let range = self.format_generic(
+            header,
+            MessageType::Request,
+            FunctionField::Valid(function),
+            body,
+            decode_level,
+        )?;
         Ok(&self.buffer[range])
     }
 
@@ -341,7 +384,7 @@ impl FrameWriter {
         header: FrameHeader,
         function: FunctionField,
         ex: ExceptionCode,
-        decode_level: DecodeLevel,
+        decode_level: DecodeSettings,
     ) -> Result<&[u8], RequestError> {
         let function = match function {
             FunctionField::Valid(x) => FunctionField::Exception(x),
@@ -349,7 +392,8 @@ impl FrameWriter {
             FunctionField::UnknownFunction(x) => FunctionField::UnknownFunction(x),
         };
 
-        let range = self.format_generic(header, function, &ex, decode_level)?;
+        let range =
+            self.format_generic(header, MessageType::Response, function, &ex, decode_level)?;
 
         Ok(&self.buffer[range])
     }
@@ -357,9 +401,10 @@ impl FrameWriter {
     fn format_generic<T>(
         &mut self,
         header: FrameHeader,
+        message: MessageType,
         function: FunctionField,
         body: &T,
-        decode_level: DecodeLevel,
+        decode_level: DecodeSettings,
     ) -> Result<Range<usize>, RequestError>
     where
         T: Serialize + Loggable,
@@ -370,20 +415,42 @@ impl FrameWriter {
                 .format_type
                 .format(&mut cursor, header, function, body)?;
             let end = cursor.position();
-            (info.frame_type, 0..end, &self.buffer[info.pdu_body])
+            (info.frame_type, 0..end, info.pdu_body)
         };
 
+        // the function code immediately precedes the body
+        let pdu = pdu_body.start - 1..pdu_body.end;
+        self.tap.tx_frame(
+            header.destination.into_unit_id(),
+            &self.buffer[frame_bytes.clone()],
+            &self.buffer[pdu.clone()],
+        );
+
         if decode_level.app.enabled() {
-            tracing::info!(
-                "PDU TX - {} {}",
-                function,
-                LoggableDisplay::new(body, pdu_body, decode_level.app)
-            );
+            if decode_level.is_structured() {
+                structured::pdu(
+                    Direction::Tx,
+                    message,
+                    decode_level.app,
+                    &self.buffer[pdu.clone()],
+                );
+            } else {
+                tracing::info!(
+                    "PDU TX - {} {}",
+                    function,
+                    LoggableDisplay::new(body, &self.buffer[pdu_body], decode_level.app)
+                );
+            }
         }
 
         if decode_level.frame.enabled() {
+            // like received frames, the payload of structured events is only the PDU
+            let pdu = &self.buffer[pdu];
             let frame_bytes = &self.buffer[frame_bytes.clone()];
             match frame_type {
+                FrameType::Mbap(header) if decode_level.is_structured() => {
+                    structured::mbap(Direction::Tx, decode_level.frame, header, pdu);
+                }
                 FrameType::Mbap(header) => {
                     tracing::info!(
                         "MBAP TX - {}",
@@ -391,6 +458,10 @@ impl FrameWriter {
                     );
                 }
                 #[cfg(feature = "serial")]
+                FrameType::Rtu(dest, crc) if decode_level.is_structured() => {
+                    structured::rtu(Direction::Tx, decode_level.frame, dest, pdu, crc);
+                }
+                #[cfg(feature = "serial")]
                 FrameType::Rtu(dest, crc) => {
                     tracing::info!(
                         "RTU TX - {}",
@@ -421,6 +492,7 @@ impl FrameWriter {
 pub(crate) struct FramedReader {
     parser: FrameParser,
     buffer: ReadBuffer,
+    tap: Tap,
 }
 
 impl FramedReader {
@@ -446,19 +518,27 @@ impl FramedReader {
         Self {
             parser,
             buffer: ReadBuffer::new(),
+            tap: Tap::default(),
         }
     }
 
+    pub(crate) fn set_tap(&mut self, tap: Tap) {
+        self.tap = tap;
+    }
+
     pub(crate) async fn next_frame(
         &mut self,
         io: &mut PhysLayer,
-        decode_level: DecodeLevel,
+        decode_level: DecodeSettings,
     ) -> Result<Frame, RequestError> {
         loop {
-            match self.parser.parse(&mut self.buffer, decode_level.frame) {
-                Ok(Some(frame)) => return Ok(frame),
+            match self.parser.parse(&mut self.buffer, decode_level) {
+                Ok(Some(frame)) => {
+                    self.tap.rx_frame(&frame);
+                    return Ok(frame);
+                }
                 Ok(None) => {
-                    self.buffer.read_some(io, decode_level.physical).await?;
+                    self.buffer.read_some(io, decode_level).await?;
                 }
                 Err(err) => {
                     self.parser.reset();
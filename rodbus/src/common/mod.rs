@@ -7,3 +7,4 @@ pub(crate) mod frame;
 mod parse;
 pub(crate) mod phys;
 mod serialize;
+pub(crate) mod structured;
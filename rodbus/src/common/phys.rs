@@ -1,9 +1,12 @@
-use crate::decode::PhysDecodeLevel;
+use crate::common::structured;
+use crate::decode::{DecodeSettings, PhysDecodeLevel};
+use crate::observer::{Direction, Endpoints, Tap};
 use std::fmt::Write;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
 pub(crate) struct PhysLayer {
     layer: PhysLayerImpl,
+    tap: Tap,
 }
 
 // encapsulates all possible physical layers as an enum
@@ -40,6 +43,7 @@ impl PhysLayer {
     pub(crate) fn new_tcp(socket: tokio::net::TcpStream) -> Self {
         Self {
             layer: PhysLayerImpl::Tcp(socket),
+            tap: Tap::default(),
         }
     }
 
@@ -48,6 +52,7 @@ impl PhysLayer {
         let calculate_inter_character_delay = calculate_inter_character_delay(&stream);
         Self {
             layer: PhysLayerImpl::Serial(stream, calculate_inter_character_delay, None),
+            tap: Tap::default(),
         }
     }
 
@@ -55,6 +60,7 @@ impl PhysLayer {
     pub(crate) fn new_tls(socket: tokio_rustls::TlsStream<tokio::net::TcpStream>) -> Self {
         Self {
             layer: PhysLayerImpl::Tls(Box::new(socket)),
+            tap: Tap::default(),
         }
     }
 
@@ -62,13 +68,35 @@ impl PhysLayer {
     pub(crate) fn new_mock(mock: sfio_tokio_mock_io::Mock) -> Self {
         Self {
             layer: PhysLayerImpl::Mock(mock),
+            tap: Tap::default(),
         }
     }
 
+    pub(crate) fn set_tap(&mut self, tap: Tap) {
+        self.tap = tap;
+    }
+
+    pub(crate) fn endpoints(&self) -> Option<Endpoints> {
+        let socket = match &self.layer {
+            PhysLayerImpl::Tcp(x) => x,
+            #[cfg(feature = "serial")]
+            PhysLayerImpl::Serial(_, _, _) => return None,
+            #[cfg(feature = "tls")]
+            PhysLayerImpl::Tls(x) => x.get_ref().0,
+            #[cfg(test)]
+            PhysLayerImpl::Mock(_) => return None,
+        };
+
+        Some(Endpoints {
+            local: socket.local_addr().ok()?,
+            remote: socket.peer_addr().ok()?,
+        })
+    }
+
     pub(crate) async fn read(
         &mut self,
         buffer: &mut [u8],
-        decode_level: PhysDecodeLevel,
+        decode_level: DecodeSettings,
     ) -> Result<usize, std::io::Error> {
         let length = match &mut self.layer {
             PhysLayerImpl::Tcp(x) => x.read(buffer).await?,
@@ -80,9 +108,14 @@ impl PhysLayer {
             PhysLayerImpl::Mock(x) => x.read(buffer).await?,
         };
 
-        if decode_level.enabled() {
-            if let Some(x) = buffer.get(0..length) {
-                tracing::info!("PHYS RX - {}", PhysDisplay::new(decode_level, x))
+        if let Some(x) = buffer.get(0..length) {
+            self.tap.physical(Direction::Rx, x);
+            if decode_level.physical.enabled() {
+                if decode_level.is_structured() {
+                    structured::physical(Direction::Rx, decode_level.physical, x);
+                } else {
+                    tracing::info!("PHYS RX - {}", PhysDisplay::new(decode_level.physical, x))
+                }
             }
         }
 
@@ -92,12 +125,21 @@ impl PhysLayer {
     pub(crate) async fn write(
         &mut self,
         data: &[u8],
-        decode_level: PhysDecodeLevel,
+        decode_level: DecodeSettings,
     ) -> Result<(), std::io::Error> {
-        if decode_level.enabled() {
-            tracing::info!("PHYS TX - {}", PhysDisplay::new(decode_level, data));
+        if decode_level.physical.enabled() {
+            if decode_level.is_structured() {
+                structured::physical(Direction::Tx, decode_level.physical, data);
+            } else {
+                tracing::info!(
+                    "PHYS TX - {}",
+                    PhysDisplay::new(decode_level.physical, data)
+                );
+            }
         }
 
+        self.tap.physical(Direction::Tx, data);
+
         match &mut self.layer {
             PhysLayerImpl::Tcp(x) => x.write_all(data).await,
             #[cfg(feature = "serial")]
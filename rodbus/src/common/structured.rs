@@ -0,0 +1,249 @@
+use std::fmt::Write;
+
+#[cfg(feature = "serial")]
+use crate::common::frame::FrameDestination;
+use crate::common::function::FunctionCode;
+use crate::decode::{AppDecodeLevel, FrameDecodeLevel, PhysDecodeLevel};
+use crate::decoder::{decode_pdu, MessageType, Pdu, RequestPdu, ResponsePdu};
+use crate::observer::Direction;
+use crate::tcp::frame::MbapHeader;
+use crate::types::{AddressRange, Indexed};
+
+/// Fields of a PDU, each of which is only recorded if present
+#[derive(Default)]
+struct PduFields {
+    exception: Option<String>,
+    start: Option<u16>,
+    count: Option<u16>,
+    index: Option<u16>,
+    value: Option<u16>,
+    values: Option<String>,
+    length: Option<usize>,
+    error: Option<String>,
+}
+
+impl PduFields {
+    fn range(range: AddressRange) -> Self {
+        Self {
+            start: Some(range.start),
+            count: Some(range.count),
+            ..Default::default()
+        }
+    }
+
+    fn indexed(index: u16, value: u16) -> Self {
+        Self {
+            index: Some(index),
+            value: Some(value),
+            ..Default::default()
+        }
+    }
+
+    fn values<T: std::fmt::Display>(values: impl Iterator<Item = T>) -> Self {
+        Self {
+            values: Some(join(values)),
+            ..Default::default()
+        }
+    }
+
+    fn indexed_values<T: Copy + std::fmt::Display>(values: &[Indexed<T>]) -> Self {
+        let start = values.first().map(|x| x.index);
+        Self {
+            start,
+            count: Some(values.len() as u16),
+            values: Some(join(values.iter().map(|x| x.value))),
+            ..Default::default()
+        }
+    }
+
+    fn new(pdu: Pdu) -> Self {
+        match pdu {
+            Pdu::Request(request) => match request {
+                RequestPdu::ReadCoils(x)
+                | RequestPdu::ReadDiscreteInputs(x)
+                | RequestPdu::ReadHoldingRegisters(x)
+                | RequestPdu::ReadInputRegisters(x) => Self::range(x),
+                RequestPdu::WriteSingleCoil(x) => Self::indexed(x.index, x.value as u16),
+                RequestPdu::WriteSingleRegister(x) => Self::indexed(x.index, x.value),
+                RequestPdu::WriteMultipleCoils(x) => Self::indexed_values(&x),
+                RequestPdu::WriteMultipleRegisters(x) => Self::indexed_values(&x),
+            },
+            Pdu::Response(response) => match response {
+                ResponsePdu::ReadCoils(x) | ResponsePdu::ReadDiscreteInputs(x) => {
+                    Self::values(x.into_iter().map(u8::from))
+                }
+                ResponsePdu::ReadHoldingRegisters(x) | ResponsePdu::ReadInputRegisters(x) => {
+                    Self::values(x.into_iter())
+                }
+                ResponsePdu::WriteSingleCoil(x) => Self::indexed(x.index, x.value as u16),
+                ResponsePdu::WriteSingleRegister(x) => Self::indexed(x.index, x.value),
+                ResponsePdu::WriteMultipleCoils(x) | ResponsePdu::WriteMultipleRegisters(x) => {
+                    Self::range(x)
+                }
+            },
+            Pdu::Exception { exception, .. } => Self {
+                exception: Some(format!("{exception:?}")),
+                ..Default::default()
+            },
+            Pdu::Raw { data, .. } => Self {
+                length: Some(data.len()),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// remove the fields that are not enabled by the decode level
+    fn filter(mut self, level: AppDecodeLevel) -> Self {
+        if !level.data_values() {
+            self.values = None;
+            self.value = None;
+        }
+        if !level.data_headers() {
+            self.start = None;
+            self.count = None;
+            self.index = None;
+            self.length = None;
+        }
+        self
+    }
+}
+
+fn join<T: std::fmt::Display>(values: impl Iterator<Item = T>) -> String {
+    let mut output = String::new();
+    for (i, value) in values.enumerate() {
+        if i > 0 {
+            output.push(',');
+        }
+        let _ = write!(output, "{value}");
+    }
+    output
+}
+
+fn direction(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Tx => "tx",
+        Direction::Rx => "rx",
+    }
+}
+
+/// single line of space separated hex
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(3 * bytes.len());
+    for (i, byte) in bytes.iter().enumerate() {
+        if i > 0 {
+            output.push(' ');
+        }
+        let _ = write!(output, "{byte:02X}");
+    }
+    output
+}
+
+pub(crate) fn physical(dir: Direction, level: PhysDecodeLevel, bytes: &[u8]) {
+    let data = level.data_enabled().then(|| hex(bytes));
+    tracing::info!(
+        layer = "phys",
+        direction = direction(dir),
+        length = bytes.len(),
+        data = data.as_deref(),
+    );
+}
+
+pub(crate) fn mbap(dir: Direction, level: FrameDecodeLevel, header: MbapHeader, payload: &[u8]) {
+    let payload = level.payload_enabled().then(|| hex(payload));
+    tracing::info!(
+        layer = "mbap",
+        direction = direction(dir),
+        tx_id = header.tx_id.to_u16(),
+        unit_id = header.unit_id.value,
+        len = header.len_field,
+        payload = payload.as_deref(),
+    );
+}
+
+#[cfg(feature = "serial")]
+pub(crate) fn rtu(
+    dir: Direction,
+    level: FrameDecodeLevel,
+    destination: FrameDestination,
+    payload: &[u8],
+    crc: u16,
+) {
+    let payload = level.payload_enabled().then(|| hex(payload));
+    tracing::info!(
+        layer = "rtu",
+        direction = direction(dir),
+        unit_id = destination.value(),
+        crc,
+        payload = payload.as_deref(),
+    );
+}
+
+/// `pdu` is the function code followed by the body of the PDU
+pub(crate) fn pdu(dir: Direction, message: MessageType, level: AppDecodeLevel, pdu: &[u8]) {
+    let code = match pdu.first() {
+        Some(x) => *x,
+        None => return,
+    };
+
+    let fields = match decode_pdu(message, pdu) {
+        Ok(x) => PduFields::new(x).filter(level),
+        Err(err) => PduFields {
+            error: Some(err.to_string()),
+            ..Default::default()
+        },
+    };
+
+    let function = FunctionCode::get(code & 0x7F).map(|x| x.to_string());
+
+    tracing::info!(
+        layer = "pdu",
+        direction = direction(dir),
+        kind = match message {
+            MessageType::Request => "request",
+            MessageType::Response => "response",
+        },
+        function_code = code,
+        function = function.as_deref(),
+        exception = fields.exception.as_deref(),
+        start = fields.start,
+        count = fields.count,
+        index = fields.index,
+        value = fields.value,
+        values = fields.values.as_deref(),
+        length = fields.length,
+        error = fields.error.as_deref(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_hex_on_a_single_line() {
+        assert_eq!(hex(&[]), "");
+        assert_eq!(hex(&[0x01, 0xCA, 0xFE]), "01 CA FE");
+    }
+
+    #[test]
+    fn filters_pdu_fields_by_level() {
+        let pdu = Pdu::Request(RequestPdu::WriteSingleRegister(Indexed::new(7, 42)));
+
+        let fields = PduFields::new(pdu.clone()).filter(AppDecodeLevel::DataValues);
+        assert_eq!((fields.index, fields.value), (Some(7), Some(42)));
+
+        let fields = PduFields::new(pdu.clone()).filter(AppDecodeLevel::DataHeaders);
+        assert_eq!((fields.index, fields.value), (Some(7), None));
+
+        let fields = PduFields::new(pdu).filter(AppDecodeLevel::FunctionCode);
+        assert_eq!((fields.index, fields.value), (None, None));
+    }
+
+    #[test]
+    fn joins_read_values() {
+        let fields = PduFields::new(Pdu::Response(ResponsePdu::ReadCoils(vec![
+            true, false, true,
+        ])));
+        assert_eq!(fields.values.as_deref(), Some("1,0,1"));
+    }
+}
@@ -9,6 +9,22 @@ pub struct DecodeLevel {
     pub physical: PhysDecodeLevel,
 }
 
+/// Controls how decoded data is emitted at each layer
+///
+/// The format is a setting of each channel or server, separate from its [`DecodeLevel`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DecodeFormat {
+    /// Emit human-readable messages with multi-line hex dumps
+    #[default]
+    Text,
+    /// Emit a single event per message with the decoded data as structured `tracing` fields
+    ///
+    /// Fields are only present if enabled by the decode level of the layer, e.g. `values`
+    /// requires [`AppDecodeLevel::DataValues`] and `payload` requires [`FrameDecodeLevel::Payload`].
+    /// Raw bytes are formatted as a single line of space separated hex.
+    Structured,
+}
+
 /// Controls how transmitted and received message at the application layer are decoded at the INFO log level
 ///
 /// Application-layer messages are referred to as Protocol Data Units (PDUs) in the specification.
@@ -105,6 +121,45 @@ impl From<AppDecodeLevel> for DecodeLevel {
     }
 }
 
+/// Decode level of a channel or server along with the format of the decoded data
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct DecodeSettings {
+    pub(crate) app: AppDecodeLevel,
+    pub(crate) frame: FrameDecodeLevel,
+    pub(crate) physical: PhysDecodeLevel,
+    pub(crate) format: DecodeFormat,
+}
+
+impl DecodeSettings {
+    /// change the levels, keeping the format
+    pub(crate) fn set_level(&mut self, level: DecodeLevel) {
+        self.app = level.app;
+        self.frame = level.frame;
+        self.physical = level.physical;
+    }
+
+    pub(crate) fn is_structured(&self) -> bool {
+        self.format == DecodeFormat::Structured
+    }
+}
+
+impl Default for DecodeSettings {
+    fn default() -> Self {
+        DecodeLevel::default().into()
+    }
+}
+
+impl From<DecodeLevel> for DecodeSettings {
+    fn from(level: DecodeLevel) -> Self {
+        Self {
+            app: level.app,
+            frame: level.frame,
+            physical: level.physical,
+            format: DecodeFormat::Text,
+        }
+    }
+}
+
 impl AppDecodeLevel {
     pub(crate) fn enabled(&self) -> bool {
         self.header()
@@ -0,0 +1,532 @@
+use scursor::ReadCursor;
+
+use crate::common::buffer::ReadBuffer;
+use crate::common::frame::{FrameParser, FunctionField};
+use crate::common::function::FunctionCode;
+use crate::decode::DecodeSettings;
+use crate::error::{AduParseError, FrameParseError, RequestError};
+use crate::exception::ExceptionCode;
+use crate::server::request::Request;
+use crate::tcp::frame::MbapParser;
+use crate::types::{AddressRange, Indexed, UnitId};
+
+/// Framing of the bytes to decode
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Framing {
+    /// MBAP header used on TCP and TLS
+    Tcp,
+    /// Serial line framing with a CRC
+    #[cfg(feature = "serial")]
+    Rtu,
+}
+
+/// Whether the bytes were sent by a client or a server
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MessageType {
+    /// Sent by a client to a server
+    Request,
+    /// Sent by a server to a client
+    Response,
+}
+
+/// Decoded request PDU
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RequestPdu {
+    /// Read coils
+    ReadCoils(AddressRange),
+    /// Read discrete inputs
+    ReadDiscreteInputs(AddressRange),
+    /// Read holding registers
+    ReadHoldingRegisters(AddressRange),
+    /// Read input registers
+    ReadInputRegisters(AddressRange),
+    /// Write a single coil
+    WriteSingleCoil(Indexed<bool>),
+    /// Write a single register
+    WriteSingleRegister(Indexed<u16>),
+    /// Write multiple coils
+    WriteMultipleCoils(Vec<Indexed<bool>>),
+    /// Write multiple registers
+    WriteMultipleRegisters(Vec<Indexed<u16>>),
+}
+
+/// Decoded response PDU
+///
+/// Read responses do not contain the addresses of the values. Bit values also include
+/// the padding bits of the last byte because the requested count is only known from the request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResponsePdu {
+    /// Values of the coils that were read
+    ReadCoils(Vec<bool>),
+    /// Values of the discrete inputs that were read
+    ReadDiscreteInputs(Vec<bool>),
+    /// Values of the holding registers that were read
+    ReadHoldingRegisters(Vec<u16>),
+    /// Values of the input registers that were read
+    ReadInputRegisters(Vec<u16>),
+    /// Echo of a single coil write
+    WriteSingleCoil(Indexed<bool>),
+    /// Echo of a single register write
+    WriteSingleRegister(Indexed<u16>),
+    /// Range of coils that were written
+    WriteMultipleCoils(AddressRange),
+    /// Range of registers that were written
+    WriteMultipleRegisters(AddressRange),
+}
+
+/// Decoded protocol data unit
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Pdu {
+    /// Request sent by a client
+    Request(RequestPdu),
+    /// Response sent by a server
+    Response(ResponsePdu),
+    /// Exception response sent by a server
+    Exception {
+        /// Function code of the request, without the exception bit
+        function: u8,
+        /// Exception code
+        exception: ExceptionCode,
+    },
+    /// PDU whose function code is unknown or whose contents are not decoded
+    Raw {
+        /// Raw function code
+        function: u8,
+        /// Bytes that follow the function code
+        data: Vec<u8>,
+    },
+}
+
+/// Decoded frame (ADU)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedFrame {
+    /// Transaction id of the MBAP header, `None` on serial lines
+    pub tx_id: Option<u16>,
+    /// Unit id of the frame
+    pub unit_id: UnitId,
+    /// Decoded PDU
+    pub pdu: Pdu,
+}
+
+/// Sans-IO decoder that extracts frames from a stream of bytes
+///
+/// Bytes are added with [`FrameDecoder::push`] in chunks of any size and complete frames
+/// are retrieved with [`FrameDecoder::next_frame`].
+pub struct FrameDecoder {
+    message: MessageType,
+    parser: FrameParser,
+    buffer: ReadBuffer,
+    pending: Vec<u8>,
+}
+
+impl FrameDecoder {
+    /// Create a decoder for a stream of requests or responses
+    pub fn new(framing: Framing, message: MessageType) -> Self {
+        let parser = match framing {
+            Framing::Tcp => FrameParser::Tcp(MbapParser::new()),
+            #[cfg(feature = "serial")]
+            Framing::Rtu => FrameParser::Rtu(match message {
+                MessageType::Request => crate::serial::frame::RtuParser::new_request_parser(),
+                MessageType::Response => crate::serial::frame::RtuParser::new_response_parser(),
+            }),
+        };
+
+        Self {
+            message,
+            parser,
+            buffer: ReadBuffer::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Add bytes to decode
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+    }
+
+    /// Number of bytes that were pushed but are not part of a decoded frame yet
+    pub fn remaining(&self) -> usize {
+        self.buffer.len() + self.pending.len()
+    }
+
+    /// Decode the next frame, or return `None` if more bytes are required
+    ///
+    /// After an error, the parser is reset and decoding continues with the remaining bytes.
+    pub fn next_frame(&mut self) -> Option<Result<DecodedFrame, RequestError>> {
+        loop {
+            match self
+                .parser
+                .parse(&mut self.buffer, DecodeSettings::default())
+            {
+                Ok(Some(frame)) => {
+                    let pdu = decode_pdu(self.message, frame.payload());
+                    return Some(pdu.map(|pdu| DecodedFrame {
+                        tx_id: frame.header.tx_id.map(|x| x.to_u16()),
+                        unit_id: frame.header.destination.into_unit_id(),
+                        pdu,
+                    }));
+                }
+                Ok(None) => {
+                    if self.pending.is_empty() {
+                        return None;
+                    }
+                    let count = self.buffer.extend(&self.pending);
+                    self.pending.drain(..count);
+                }
+                Err(err) => {
+                    self.parser.reset();
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+/// Decode a buffer that contains exactly one frame
+pub fn decode_frame(
+    framing: Framing,
+    message: MessageType,
+    bytes: &[u8],
+) -> Result<DecodedFrame, RequestError> {
+    let mut decoder = FrameDecoder::new(framing, message);
+    decoder.push(bytes);
+    let frame = decoder
+        .next_frame()
+        .unwrap_or(Err(AduParseError::InsufficientBytes.into()))?;
+    match decoder.remaining() {
+        0 => Ok(frame),
+        count => Err(AduParseError::TrailingBytes(count).into()),
+    }
+}
+
+/// Decode a PDU, i.e. the function code followed by the PDU body
+pub fn decode_pdu(message: MessageType, bytes: &[u8]) -> Result<Pdu, RequestError> {
+    let mut cursor = ReadCursor::new(bytes);
+    let value = cursor.read_u8()?;
+
+    if value & 0x80 != 0 {
+        let exception = ExceptionCode::from(cursor.read_u8()?);
+        cursor.expect_empty()?;
+        return Ok(Pdu::Exception {
+            function: value & 0x7F,
+            exception,
+        });
+    }
+
+    let function = match FunctionCode::get(value) {
+        // device identification is not decoded yet
+        None | Some(FunctionCode::ReadDeviceIdentification) => {
+            return Ok(Pdu::Raw {
+                function: value,
+                data: cursor.read_all().to_vec(),
+            })
+        }
+        Some(x) => x,
+    };
+
+    match message {
+        MessageType::Request => decode_request(function, &mut cursor),
+        MessageType::Response => decode_response(function, &mut cursor),
+    }
+}
+
+fn decode_request(function: FunctionCode, cursor: &mut ReadCursor) -> Result<Pdu, RequestError> {
+    let request = match Request::parse(function, cursor)? {
+        Request::ReadCoils(x) => RequestPdu::ReadCoils(x.get()),
+        Request::ReadDiscreteInputs(x) => RequestPdu::ReadDiscreteInputs(x.get()),
+        Request::ReadHoldingRegisters(x) => RequestPdu::ReadHoldingRegisters(x.get()),
+        Request::ReadInputRegisters(x) => RequestPdu::ReadInputRegisters(x.get()),
+        Request::WriteSingleCoil(x) => RequestPdu::WriteSingleCoil(x),
+        Request::WriteSingleRegister(x) => RequestPdu::WriteSingleRegister(x),
+        Request::WriteMultipleCoils(x) => RequestPdu::WriteMultipleCoils(x.iterator.collect()),
+        Request::WriteMultipleRegisters(x) => {
+            RequestPdu::WriteMultipleRegisters(x.iterator.collect())
+        }
+        Request::ReadDeviceIdentification(_) => return Err(unexpected(function)),
+    };
+    Ok(Pdu::Request(request))
+}
+
+fn decode_response(function: FunctionCode, cursor: &mut ReadCursor) -> Result<Pdu, RequestError> {
+    let response = match function {
+        FunctionCode::ReadCoils => ResponsePdu::ReadCoils(decode_bits(cursor)?),
+        FunctionCode::ReadDiscreteInputs => ResponsePdu::ReadDiscreteInputs(decode_bits(cursor)?),
+        FunctionCode::ReadHoldingRegisters => {
+            ResponsePdu::ReadHoldingRegisters(decode_registers(cursor)?)
+        }
+        FunctionCode::ReadInputRegisters => {
+            ResponsePdu::ReadInputRegisters(decode_registers(cursor)?)
+        }
+        FunctionCode::WriteSingleCoil => {
+            let index = cursor.read_u16_be()?;
+            let value = crate::types::coil_from_u16(cursor.read_u16_be()?)?;
+            ResponsePdu::WriteSingleCoil(Indexed::new(index, value))
+        }
+        FunctionCode::WriteSingleRegister => {
+            let index = cursor.read_u16_be()?;
+            ResponsePdu::WriteSingleRegister(Indexed::new(index, cursor.read_u16_be()?))
+        }
+        FunctionCode::WriteMultipleCoils => ResponsePdu::WriteMultipleCoils(decode_range(cursor)?),
+        FunctionCode::WriteMultipleRegisters => {
+            ResponsePdu::WriteMultipleRegisters(decode_range(cursor)?)
+        }
+        FunctionCode::ReadDeviceIdentification => return Err(unexpected(function)),
+    };
+    cursor.expect_empty()?;
+    Ok(Pdu::Response(response))
+}
+
+// device identification PDUs are returned as raw bytes before reaching the decoders
+fn unexpected(function: FunctionCode) -> RequestError {
+    FrameParseError::UnknownFunctionCode(function.get_value()).into()
+}
+
+fn decode_byte_count<'a>(cursor: &mut ReadCursor<'a>) -> Result<&'a [u8], RequestError> {
+    let count = cursor.read_u8()? as usize;
+    if count > cursor.remaining() {
+        return Err(AduParseError::InsufficientBytesForByteCount(count, cursor.remaining()).into());
+    }
+    Ok(cursor.read_bytes(count)?)
+}
+
+fn decode_bits(cursor: &mut ReadCursor) -> Result<Vec<bool>, RequestError> {
+    let bytes = decode_byte_count(cursor)?;
+    Ok(bytes
+        .iter()
+        .flat_map(|byte| (0..8).map(move |bit| byte & (1 << bit) != 0))
+        .collect())
+}
+
+fn decode_registers(cursor: &mut ReadCursor) -> Result<Vec<u16>, RequestError> {
+    let bytes = decode_byte_count(cursor)?;
+    if bytes.len() % 2 != 0 {
+        return Err(AduParseError::InsufficientBytes.into());
+    }
+    Ok(bytes
+        .chunks(2)
+        .map(|x| u16::from_be_bytes([x[0], x[1]]))
+        .collect())
+}
+
+fn decode_range(cursor: &mut ReadCursor) -> Result<AddressRange, RequestError> {
+    let start = cursor.read_u16_be()?;
+    let count = cursor.read_u16_be()?;
+    Ok(AddressRange::try_from(start, count)?)
+}
+
+impl Pdu {
+    /// Raw function code of the PDU including the exception bit
+    pub fn function(&self) -> u8 {
+        match self {
+            Pdu::Request(x) => x.function().get_value(),
+            Pdu::Response(x) => x.function().get_value(),
+            Pdu::Exception { function, .. } => function | 0x80,
+            Pdu::Raw { function, .. } => *function,
+        }
+    }
+}
+
+impl RequestPdu {
+    fn function(&self) -> FunctionCode {
+        match self {
+            RequestPdu::ReadCoils(_) => FunctionCode::ReadCoils,
+            RequestPdu::ReadDiscreteInputs(_) => FunctionCode::ReadDiscreteInputs,
+            RequestPdu::ReadHoldingRegisters(_) => FunctionCode::ReadHoldingRegisters,
+            RequestPdu::ReadInputRegisters(_) => FunctionCode::ReadInputRegisters,
+            RequestPdu::WriteSingleCoil(_) => FunctionCode::WriteSingleCoil,
+            RequestPdu::WriteSingleRegister(_) => FunctionCode::WriteSingleRegister,
+            RequestPdu::WriteMultipleCoils(_) => FunctionCode::WriteMultipleCoils,
+            RequestPdu::WriteMultipleRegisters(_) => FunctionCode::WriteMultipleRegisters,
+        }
+    }
+}
+
+impl ResponsePdu {
+    fn function(&self) -> FunctionCode {
+        match self {
+            ResponsePdu::ReadCoils(_) => FunctionCode::ReadCoils,
+            ResponsePdu::ReadDiscreteInputs(_) => FunctionCode::ReadDiscreteInputs,
+            ResponsePdu::ReadHoldingRegisters(_) => FunctionCode::ReadHoldingRegisters,
+            ResponsePdu::ReadInputRegisters(_) => FunctionCode::ReadInputRegisters,
+            ResponsePdu::WriteSingleCoil(_) => FunctionCode::WriteSingleCoil,
+            ResponsePdu::WriteSingleRegister(_) => FunctionCode::WriteSingleRegister,
+            ResponsePdu::WriteMultipleCoils(_) => FunctionCode::WriteMultipleCoils,
+            ResponsePdu::WriteMultipleRegisters(_) => FunctionCode::WriteMultipleRegisters,
+        }
+    }
+}
+
+fn write_values<T: std::fmt::Display>(
+    f: &mut std::fmt::Formatter,
+    values: impl Iterator<Item = T>,
+) -> std::fmt::Result {
+    for value in values {
+        write!(f, "\n{value}")?;
+    }
+    Ok(())
+}
+
+impl std::fmt::Display for RequestPdu {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.function())?;
+        match self {
+            RequestPdu::ReadCoils(range)
+            | RequestPdu::ReadDiscreteInputs(range)
+            | RequestPdu::ReadHoldingRegisters(range)
+            | RequestPdu::ReadInputRegisters(range) => write!(f, " {range}"),
+            RequestPdu::WriteSingleCoil(x) => write!(f, " {x}"),
+            RequestPdu::WriteSingleRegister(x) => write!(f, " {x}"),
+            RequestPdu::WriteMultipleCoils(x) => write_values(f, x.iter()),
+            RequestPdu::WriteMultipleRegisters(x) => write_values(f, x.iter()),
+        }
+    }
+}
+
+impl std::fmt::Display for ResponsePdu {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.function())?;
+        match self {
+            ResponsePdu::ReadCoils(x) | ResponsePdu::ReadDiscreteInputs(x) => {
+                write_values(f, x.iter().enumerate().map(|(i, x)| format!("[{i}]: {x}")))
+            }
+            ResponsePdu::ReadHoldingRegisters(x) | ResponsePdu::ReadInputRegisters(x) => {
+                write_values(f, x.iter().enumerate().map(|(i, x)| format!("[{i}]: {x}")))
+            }
+            ResponsePdu::WriteSingleCoil(x) => write!(f, " {x}"),
+            ResponsePdu::WriteSingleRegister(x) => write!(f, " {x}"),
+            ResponsePdu::WriteMultipleCoils(range) | ResponsePdu::WriteMultipleRegisters(range) => {
+                write!(f, " {range}")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Pdu {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Pdu::Request(x) => write!(f, "{x}"),
+            Pdu::Response(x) => write!(f, "{x}"),
+            Pdu::Exception {
+                function,
+                exception,
+            } => match FunctionCode::get(*function) {
+                Some(x) => write!(f, "{} - {exception}", FunctionField::Exception(x)),
+                None => write!(
+                    f,
+                    "{} - {exception}",
+                    FunctionField::UnknownFunction(*function)
+                ),
+            },
+            Pdu::Raw { function, data } => {
+                write!(f, "FUNCTION ({function:#04X}) {}", data.len())?;
+                f.write_str(" bytes:")?;
+                for byte in data {
+                    write!(f, " {byte:02X}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for DecodedFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(tx_id) = self.tx_id {
+            write!(f, "tx_id: {tx_id:#06X} ")?;
+        }
+        write!(f, "unit: {} - {}", self.unit_id, self.pdu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_tcp_request_frames_from_a_stream() {
+        let mut decoder = FrameDecoder::new(Framing::Tcp, MessageType::Request);
+        let frames = [
+            // read holding registers 7..9
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0x2A, 0x03, 0x00, 0x07, 0x00, 0x02,
+            // write multiple coils 1..3 = [true, false]
+            0x00, 0x02, 0x00, 0x00, 0x00, 0x08, 0x2A, 0x0F, 0x00, 0x01, 0x00, 0x02, 0x01, 0x01,
+        ];
+
+        let (first, second) = frames.split_at(5);
+        decoder.push(first);
+        assert!(decoder.next_frame().is_none());
+        decoder.push(second);
+
+        assert_eq!(
+            decoder.next_frame().unwrap().unwrap(),
+            DecodedFrame {
+                tx_id: Some(1),
+                unit_id: UnitId::new(0x2A),
+                pdu: Pdu::Request(RequestPdu::ReadHoldingRegisters(
+                    AddressRange::try_from(7, 2).unwrap()
+                )),
+            }
+        );
+        assert_eq!(
+            decoder.next_frame().unwrap().unwrap().pdu,
+            Pdu::Request(RequestPdu::WriteMultipleCoils(vec![
+                Indexed::new(1, true),
+                Indexed::new(2, false)
+            ]))
+        );
+        assert!(decoder.next_frame().is_none());
+        assert_eq!(decoder.remaining(), 0);
+    }
+
+    #[test]
+    fn decodes_responses_and_exceptions() {
+        assert_eq!(
+            decode_pdu(MessageType::Response, &[0x04, 0x04, 0x00, 0x01, 0xFF, 0xFF]),
+            Ok(Pdu::Response(ResponsePdu::ReadInputRegisters(vec![
+                1, 0xFFFF
+            ])))
+        );
+        assert_eq!(
+            decode_pdu(MessageType::Response, &[0x01, 0x01, 0x05]),
+            Ok(Pdu::Response(ResponsePdu::ReadCoils(vec![
+                true, false, true, false, false, false, false, false
+            ])))
+        );
+        assert_eq!(
+            decode_pdu(MessageType::Response, &[0x83, 0x02]),
+            Ok(Pdu::Exception {
+                function: 0x03,
+                exception: ExceptionCode::IllegalDataAddress
+            })
+        );
+        assert_eq!(
+            decode_pdu(MessageType::Request, &[0x41, 0xCA, 0xFE]),
+            Ok(Pdu::Raw {
+                function: 0x41,
+                data: vec![0xCA, 0xFE]
+            })
+        );
+        assert_eq!(
+            decode_pdu(MessageType::Response, &[0x03, 0x02, 0x00]),
+            Err(AduParseError::InsufficientBytesForByteCount(2, 1).into())
+        );
+    }
+
+    #[cfg(feature = "serial")]
+    #[test]
+    fn decodes_a_single_rtu_frame() {
+        let frame = [0x01, 0x05, 0x00, 0x01, 0xFF, 0x00, 0xDD, 0xFA];
+        assert_eq!(
+            decode_frame(Framing::Rtu, MessageType::Response, &frame),
+            Ok(DecodedFrame {
+                tx_id: None,
+                unit_id: UnitId::new(1),
+                pdu: Pdu::Response(ResponsePdu::WriteSingleCoil(Indexed::new(1, true))),
+            })
+        );
+        assert_eq!(
+            decode_frame(Framing::Rtu, MessageType::Response, &frame[..7]),
+            Err(AduParseError::InsufficientBytes.into())
+        );
+    }
+}
@@ -55,6 +55,40 @@ impl std::fmt::Display for RequestError {
     }
 }
 
+/// Error returned by the verified write requests of the client API
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The write or the read back failed
+    Request(RequestError),
+    /// The values read back differ, are missing or are unexpected at these indices
+    Mismatch(Vec<u16>),
+}
+
+impl std::error::Error for VerifyError {}
+
+impl std::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            VerifyError::Request(err) => err.fmt(f),
+            VerifyError::Mismatch(indices) => {
+                write!(f, "read back values differ at indices {indices:?}")
+            }
+        }
+    }
+}
+
+impl From<RequestError> for VerifyError {
+    fn from(err: RequestError) -> Self {
+        VerifyError::Request(err)
+    }
+}
+
+impl From<InvalidRange> for VerifyError {
+    fn from(err: InvalidRange) -> Self {
+        VerifyError::Request(InvalidRequest::from(err).into())
+    }
+}
+
 impl From<WriteError> for RequestError {
     fn from(err: WriteError) -> Self {
         match err {
@@ -257,6 +291,8 @@ pub enum AduParseError {
     UnknownResponseFunction(u8, u8, u8), // actual, expected, expected error
     /// Bad value for the coil state
     UnknownCoilState(u16),
+    /// Registers expected to contain an ASCII string contain other characters
+    NonAsciiString,
 }
 
 impl std::error::Error for AduParseError {}
@@ -283,6 +319,9 @@ impl std::fmt::Display for AduParseError {
                 f,
                 "received coil state with unspecified value: 0x{value:04X}"
             ),
+            AduParseError::NonAsciiString => {
+                f.write_str("registers contain characters that are not ASCII")
+            }
         }
     }
 }
@@ -296,6 +335,10 @@ pub enum InvalidRequest {
     CountTooBigForU16(usize),
     /// Count too big for specific request
     CountTooBigForType(u16, u16),
+    /// String to write contains characters that are not ASCII
+    NonAsciiString,
+    /// String to write does not fit in the registers
+    StringTooLong(usize, usize), // length vs capacity
 }
 
 impl std::error::Error for InvalidRequest {}
@@ -313,6 +356,13 @@ impl std::fmt::Display for InvalidRequest {
                 f,
                 "the request count of {count} exceeds maximum allowed count of {max} for this type"
             ),
+            InvalidRequest::NonAsciiString => {
+                f.write_str("the string contains characters that are not ASCII")
+            }
+            InvalidRequest::StringTooLong(len, capacity) => write!(
+                f,
+                "the string length of {len} exceeds the capacity of {capacity} characters"
+            ),
         }
     }
 }
@@ -162,25 +162,35 @@ pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 pub mod client;
 /// Public constant values related to the Modbus specification
 pub mod constants;
+/// Sans-IO decoding of Modbus frames and PDUs
+pub mod decoder;
 
 /// Server API
 pub mod server;
+/// SunSpec model discovery and decoding
+pub mod sunspec;
 
 // modules that are re-exported
+pub(crate) mod capture;
+pub(crate) mod codec;
 pub(crate) mod decode;
 pub(crate) mod error;
 pub(crate) mod exception;
 pub(crate) mod maybe_async;
+pub(crate) mod observer;
 pub(crate) mod retry;
 #[cfg(feature = "serial")]
 mod serial;
 pub(crate) mod types;
 
 // re-exports
+pub use crate::capture::*;
+pub use crate::codec::*;
 pub use crate::decode::*;
 pub use crate::error::*;
 pub use crate::exception::*;
 pub use crate::maybe_async::*;
+pub use crate::observer::*;
 pub use crate::retry::*;
 #[cfg(feature = "serial")]
 pub use crate::serial::*;
@@ -26,6 +26,21 @@ impl<T> MaybeAsync<T> {
         }
     }
 
+    /// Retrieve the value, or `None` if it is not available within the timeout
+    ///
+    /// Values that are immediately available are returned without starting a timer
+    pub(crate) async fn get_within(self, timeout: std::time::Duration) -> Option<T> {
+        match self.inner {
+            Value::Ready(x) => Some(x),
+            Value::Async(x) => tokio::time::timeout(timeout, x).await.ok(),
+        }
+    }
+
+    /// True if the value is available without awaiting
+    pub(crate) fn is_ready(&self) -> bool {
+        matches!(self.inner, Value::Ready(_))
+    }
+
     /// Construct a new `MaybeAsync` from an already available result
     pub fn ready(result: T) -> Self {
         MaybeAsync {
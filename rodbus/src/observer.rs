@@ -0,0 +1,154 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::common::frame::Frame;
+use crate::types::UnitId;
+
+/// Direction of observed traffic
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// Traffic sent to the remote device
+    Tx,
+    /// Traffic received from the remote device
+    Rx,
+}
+
+/// Local and remote address of a TCP or TLS session
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Endpoints {
+    /// Address of the local socket
+    pub local: SocketAddr,
+    /// Address of the remote socket
+    pub remote: SocketAddr,
+}
+
+/// Traffic observed at the physical, frame or PDU level
+///
+/// The same event type is used at every level:
+///
+/// * physical - `bytes` are the bytes written to or read from the stream,
+///   `unit_id` and `function` are always `None`
+/// * frame - `bytes` are the complete MBAP or RTU frame (ADU)
+/// * PDU - `bytes` are the function code followed by the PDU body
+#[derive(Copy, Clone, Debug)]
+pub struct TrafficEvent<'a> {
+    /// Time at which the traffic was observed
+    pub timestamp: SystemTime,
+    /// Direction of the traffic
+    pub direction: Direction,
+    /// Identifier of the session (i.e. connection) on which the traffic occurred
+    pub session: u64,
+    /// Socket addresses of the session, `None` on serial ports
+    pub endpoints: Option<Endpoints>,
+    /// Unit id of the frame, if known at this level
+    pub unit_id: Option<UnitId>,
+    /// Raw function code of the PDU including the exception bit, if known at this level
+    pub function: Option<u8>,
+    /// Raw bytes of the traffic
+    pub bytes: &'a [u8],
+}
+
+/// Callbacks that receive every frame sent or received by a client channel or a server
+///
+/// Unlike [`DecodeLevel`](crate::DecodeLevel), events are delivered directly and do not
+/// go through `tracing`. Callbacks are invoked from the task that performs the IO, so
+/// implementations should return quickly.
+pub trait TrafficObserver: Send + Sync {
+    /// Called when bytes are written to or read from the physical layer
+    fn on_physical(&self, _event: TrafficEvent) {}
+
+    /// Called when a complete frame is sent or received
+    fn on_frame(&self, _event: TrafficEvent) {}
+
+    /// Called when a PDU is sent or received
+    fn on_pdu(&self, _event: TrafficEvent) {}
+}
+
+/// Forwards every event to all the observers in the list, in order
+///
+/// Channels and servers hold a single observer. Use a list to capture traffic
+/// and observe it at the same time.
+impl TrafficObserver for Vec<Arc<dyn TrafficObserver>> {
+    fn on_physical(&self, event: TrafficEvent) {
+        for observer in self {
+            observer.on_physical(event);
+        }
+    }
+
+    fn on_frame(&self, event: TrafficEvent) {
+        for observer in self {
+            observer.on_frame(event);
+        }
+    }
+
+    fn on_pdu(&self, event: TrafficEvent) {
+        for observer in self {
+            observer.on_pdu(event);
+        }
+    }
+}
+
+/// Forwards traffic to an optional observer, tagged with a session id
+#[derive(Clone, Default)]
+pub(crate) struct Tap {
+    observer: Option<Arc<dyn TrafficObserver>>,
+    session: u64,
+    endpoints: Option<Endpoints>,
+}
+
+impl Tap {
+    pub(crate) fn new(
+        observer: Option<Arc<dyn TrafficObserver>>,
+        session: u64,
+        endpoints: Option<Endpoints>,
+    ) -> Self {
+        Self {
+            observer,
+            session,
+            endpoints,
+        }
+    }
+
+    fn event<'a>(
+        &self,
+        direction: Direction,
+        unit_id: Option<UnitId>,
+        function: Option<u8>,
+        bytes: &'a [u8],
+    ) -> TrafficEvent<'a> {
+        TrafficEvent {
+            timestamp: SystemTime::now(),
+            direction,
+            session: self.session,
+            endpoints: self.endpoints,
+            unit_id,
+            function,
+            bytes,
+        }
+    }
+
+    pub(crate) fn physical(&self, direction: Direction, bytes: &[u8]) {
+        if let Some(observer) = &self.observer {
+            observer.on_physical(self.event(direction, None, None, bytes));
+        }
+    }
+
+    pub(crate) fn tx_frame(&self, unit_id: UnitId, frame: &[u8], pdu: &[u8]) {
+        if let Some(observer) = &self.observer {
+            let function = pdu.first().copied();
+            observer.on_frame(self.event(Direction::Tx, Some(unit_id), function, frame));
+            observer.on_pdu(self.event(Direction::Tx, Some(unit_id), function, pdu));
+        }
+    }
+
+    pub(crate) fn rx_frame(&self, frame: &Frame) {
+        if let Some(observer) = &self.observer {
+            let unit_id = Some(frame.header.destination.into_unit_id());
+            let pdu = frame.payload();
+            let function = pdu.first().copied();
+            observer.on_frame(self.event(Direction::Rx, unit_id, function, &frame.to_adu()));
+            observer.on_pdu(self.event(Direction::Rx, unit_id, function, pdu));
+        }
+    }
+}
@@ -1,5 +1,8 @@
 use std::time::Duration;
 
+use crate::error::RequestError;
+use crate::exception::ExceptionCode;
+
 /// Trait that controls how the channel retries failed connect (TCP/TLS) or open (serial) attempts
 pub trait RetryStrategy: Send {
     /// Reset internal state. Called when a connection is successful or a port is opened
@@ -51,3 +54,123 @@ impl RetryStrategy for Doubling {
         self.min
     }
 }
+
+/// Policy that controls how the channel retries individual requests that fail
+///
+/// Unlike [`RetryStrategy`], which controls connection attempts, this policy is applied
+/// to individual requests and is attached to a channel handle with
+/// [`crate::client::Channel::with_retry_policy`].
+/// Errors that close the session (I/O errors and bad frames) are never retried.
+///
+/// The channel executes other requests while a request waits for its next attempt. If the
+/// session ends during the delay, the request fails with the error of its last attempt.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts including the first one. Values of 0 or 1 disable retries
+    pub max_attempts: usize,
+    /// Delay before the first retry, doubled after every subsequent failure
+    pub min_delay: Duration,
+    /// Maximum delay between two attempts
+    pub max_delay: Duration,
+    /// Returns true if a request that failed with the error may be retried
+    pub is_retryable: fn(&RequestError) -> bool,
+    /// Retry write requests. Writes may trigger actions in the device and are not
+    /// considered idempotent, so they are only retried when this is set
+    pub retry_writes: bool,
+}
+
+impl RetryPolicy {
+    /// Policy that never retries requests
+    pub fn none() -> Self {
+        Self::new(1, Duration::ZERO, Duration::ZERO)
+    }
+
+    /// Create a policy that retries reads failing with a [`default_retryable`] error
+    pub fn new(max_attempts: usize, min_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            max_attempts,
+            min_delay,
+            max_delay,
+            is_retryable: default_retryable,
+            retry_writes: false,
+        }
+    }
+
+    /// Replace the function that decides which errors are retryable
+    pub fn retry_on(self, is_retryable: fn(&RequestError) -> bool) -> Self {
+        Self {
+            is_retryable,
+            ..self
+        }
+    }
+
+    /// Allow write requests to be retried
+    pub fn allow_writes(self) -> Self {
+        Self {
+            retry_writes: true,
+            ..self
+        }
+    }
+
+    pub(crate) fn should_retry(
+        &self,
+        attempt: usize,
+        idempotent: bool,
+        err: &RequestError,
+    ) -> bool {
+        attempt < self.max_attempts && (idempotent || self.retry_writes) && (self.is_retryable)(err)
+    }
+
+    pub(crate) fn delay(&self, attempt: usize) -> Duration {
+        // attempt is 1-based, so the first retry waits the minimum delay
+        let factor = 1u32
+            .checked_shl(attempt.saturating_sub(1) as u32)
+            .unwrap_or(u32::MAX);
+        std::cmp::min(self.min_delay.saturating_mul(factor), self.max_delay)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+/// Default retryable errors: response timeouts and the
+/// [`ExceptionCode::ServerDeviceBusy`] and [`ExceptionCode::Acknowledge`] exceptions
+pub fn default_retryable(err: &RequestError) -> bool {
+    matches!(
+        err,
+        RequestError::ResponseTimeout
+            | RequestError::Exception(ExceptionCode::ServerDeviceBusy)
+            | RequestError::Exception(ExceptionCode::Acknowledge)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delay_doubles_up_to_maximum() {
+        let policy = RetryPolicy::new(10, Duration::from_millis(100), Duration::from_millis(500));
+        let delays: Vec<Duration> = (1..6).map(|x| policy.delay(x)).collect();
+        assert_eq!(
+            delays,
+            [100, 200, 400, 500, 500]
+                .map(Duration::from_millis)
+                .to_vec()
+        );
+    }
+
+    #[test]
+    fn writes_are_only_retried_when_allowed() {
+        let policy = RetryPolicy::new(3, Duration::ZERO, Duration::ZERO);
+        let err = RequestError::ResponseTimeout;
+        assert!(policy.should_retry(1, true, &err));
+        assert!(!policy.should_retry(1, false, &err));
+        assert!(policy.allow_writes().should_retry(1, false, &err));
+        assert!(!policy.should_retry(3, true, &err));
+        assert!(!policy.should_retry(1, true, &RequestError::NoConnection));
+    }
+}
@@ -3,9 +3,11 @@ use crate::common::frame::{
     Frame, FrameDestination, FrameHeader, FrameInfo, FrameType, FunctionField,
 };
 use crate::common::function::FunctionCode;
+use crate::common::structured;
 use crate::common::traits::Serialize;
-use crate::decode::FrameDecodeLevel;
+use crate::decode::{DecodeSettings, FrameDecodeLevel};
 use crate::error::{FrameParseError, RequestError};
+use crate::observer::Direction;
 use crate::types::UnitId;
 
 use scursor::WriteCursor;
@@ -19,7 +21,7 @@ pub(crate) mod constants {
 }
 
 /// precomputes the CRC table as a constant!
-const CRC: crc::Crc<u16> = crc::Crc::<u16>::new(&crc::CRC_16_MODBUS);
+pub(crate) const CRC: crc::Crc<u16> = crc::Crc::<u16>::new(&crc::CRC_16_MODBUS);
 
 #[derive(Clone, Copy)]
 enum ParserType {
@@ -106,7 +108,7 @@ impl RtuParser {
     pub(crate) fn parse(
         &mut self,
         cursor: &mut ReadBuffer,
-        decode_level: FrameDecodeLevel,
+        decode_level: DecodeSettings,
     ) -> Result<Option<Frame>, RequestError> {
         match self.state {
             ParseState::Start => {
@@ -191,11 +193,26 @@ impl RtuParser {
                     ));
                 }
 
-                if decode_level.enabled() {
-                    tracing::info!(
-                        "RTU RX - {}",
-                        RtuDisplay::new(decode_level, destination, frame.payload(), received_crc)
-                    );
+                if decode_level.frame.enabled() {
+                    if decode_level.is_structured() {
+                        structured::rtu(
+                            Direction::Rx,
+                            decode_level.frame,
+                            destination,
+                            frame.payload(),
+                            received_crc,
+                        );
+                    } else {
+                        tracing::info!(
+                            "RTU RX - {}",
+                            RtuDisplay::new(
+                                decode_level.frame,
+                                destination,
+                                frame.payload(),
+                                received_crc
+                            )
+                        );
+                    }
                 }
 
                 self.state = ParseState::Start;
@@ -277,7 +294,7 @@ mod tests {
 
     use crate::common::frame::FramedReader;
     use crate::common::phys::PhysLayer;
-    use crate::DecodeLevel;
+    use crate::decode::DecodeSettings;
 
     use super::*;
 
@@ -477,7 +494,7 @@ mod tests {
         let (io, mut io_handle) = sfio_tokio_mock_io::mock();
         let mut layer = PhysLayer::new_mock(io);
         let mut task =
-            tokio_test::task::spawn(reader.next_frame(&mut layer, DecodeLevel::nothing()));
+            tokio_test::task::spawn(reader.next_frame(&mut layer, DecodeSettings::default()));
 
         io_handle.read(frame);
         if let Poll::Ready(received_frame) = task.poll() {
@@ -491,6 +508,7 @@ mod tests {
                 received_frame.payload(),
                 &frame[1..frame.len() - constants::CRC_LENGTH]
             );
+            assert_eq!(received_frame.to_adu(), frame);
         } else {
             panic!("Task not ready");
         }
@@ -560,7 +578,7 @@ mod tests {
         let (io, mut io_handle) = sfio_tokio_mock_io::mock();
         let mut layer = PhysLayer::new_mock(io);
         let mut task =
-            tokio_test::task::spawn(reader.next_frame(&mut layer, DecodeLevel::nothing()));
+            tokio_test::task::spawn(reader.next_frame(&mut layer, DecodeSettings::default()));
 
         // Send bytes to parser byte per byte
         for byte in frame.iter().take(frame.len() - 1) {
@@ -619,7 +637,7 @@ mod tests {
         // First frame
         {
             let mut task =
-                tokio_test::task::spawn(reader.next_frame(&mut layer, DecodeLevel::nothing()));
+                tokio_test::task::spawn(reader.next_frame(&mut layer, DecodeSettings::default()));
             if let Poll::Ready(received_frame) = task.poll() {
                 let received_frame = received_frame.unwrap();
                 assert_eq!(received_frame.header.tx_id, None);
@@ -639,7 +657,7 @@ mod tests {
         // Second frame
         {
             let mut task =
-                tokio_test::task::spawn(reader.next_frame(&mut layer, DecodeLevel::nothing()));
+                tokio_test::task::spawn(reader.next_frame(&mut layer, DecodeSettings::default()));
             if let Poll::Ready(received_frame) = task.poll() {
                 let received_frame = received_frame.unwrap();
                 assert_eq!(received_frame.header.tx_id, None);
@@ -687,7 +705,7 @@ mod tests {
         let (io, mut io_handle) = sfio_tokio_mock_io::mock();
         let mut layer = PhysLayer::new_mock(io);
         let mut task =
-            tokio_test::task::spawn(reader.next_frame(&mut layer, DecodeLevel::nothing()));
+            tokio_test::task::spawn(reader.next_frame(&mut layer, DecodeSettings::default()));
 
         io_handle.read(READ_COILS_REQUEST_WRONG_CRC);
         if let Poll::Ready(received_frame) = task.poll() {
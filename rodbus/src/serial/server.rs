@@ -1,11 +1,11 @@
 use crate::common::phys::PhysLayer;
 use crate::server::task::SessionTask;
-use crate::server::RequestHandler;
+use crate::server::AsyncRequestHandler;
 use crate::{RequestError, RetryStrategy, SerialSettings, Shutdown};
 
 pub(crate) struct RtuServerTask<T>
 where
-    T: RequestHandler,
+    T: AsyncRequestHandler,
 {
     pub(crate) port: String,
     pub(crate) retry: Box<dyn RetryStrategy>,
@@ -15,7 +15,7 @@ where
 
 impl<T> RtuServerTask<T>
 where
-    T: RequestHandler,
+    T: AsyncRequestHandler,
 {
     pub(crate) async fn run(&mut self) -> Shutdown {
         loop {
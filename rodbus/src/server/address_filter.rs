@@ -1,3 +1,4 @@
+use std::net::IpAddr;
 use std::str::FromStr;
 
 /// Represents IPv4 addresses which may contain "*" wildcards
@@ -42,7 +43,7 @@ impl FromStr for WildcardIPv4 {
 }
 
 impl WildcardIPv4 {
-    pub(crate) fn matches(&self, addr: std::net::IpAddr) -> bool {
+    pub(crate) fn matches(&self, addr: IpAddr) -> bool {
         fn bm(b: u8, other: Option<u8>) -> bool {
             match other {
                 Some(x) => b == x,
@@ -51,15 +52,79 @@ impl WildcardIPv4 {
         }
 
         match addr {
-            std::net::IpAddr::V4(x) => {
+            IpAddr::V4(x) => {
                 let [b3, b2, b1, b0] = x.octets();
                 bm(b3, self.b3) && bm(b2, self.b2) && bm(b1, self.b1) && bm(b0, self.b0)
             }
-            std::net::IpAddr::V6(_) => false,
+            IpAddr::V6(_) => false,
         }
     }
 }
 
+/// IPv4 or IPv6 network in CIDR notation, e.g. `192.168.0.0/16` or `fd00::/8`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+/// Error returned when a network is not in CIDR notation or its prefix is too long
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BadCidr;
+
+impl Cidr {
+    /// Create a network from an address and a prefix length
+    ///
+    /// Bits of `address` beyond the prefix are ignored.
+    pub fn new(address: IpAddr, prefix: u8) -> Result<Self, BadCidr> {
+        let max = match address {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix > max {
+            return Err(BadCidr);
+        }
+        Ok(Self {
+            network: address,
+            prefix,
+        })
+    }
+
+    pub(crate) fn matches(&self, addr: IpAddr) -> bool {
+        match (self.network, addr) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(addr)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = BadCidr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix) = s.split_once('/').ok_or(BadCidr)?;
+        let address: IpAddr = address.parse().map_err(|_| BadCidr)?;
+        let prefix: u8 = prefix.parse().map_err(|_| BadCidr)?;
+        Self::new(address, prefix)
+    }
+}
+
+/// Rule of an [`AddressFilter::Rules`] list
+#[derive(Clone, Debug)]
+pub enum FilterRule {
+    /// Allow the addresses that match the filter
+    Allow(AddressFilter),
+    /// Deny the addresses that match the filter
+    Deny(AddressFilter),
+}
+
 /// Address filter used to control which master address(es) may connect to an outstation.
 ///
 /// Note: User code cannot exhaustively match against this enum as new variants may be added in the future.
@@ -69,28 +134,42 @@ pub enum AddressFilter {
     /// Allow any address
     Any,
     /// Allow a specific address
-    Exact(std::net::IpAddr),
+    Exact(IpAddr),
     /// Allow any of set of addresses
-    AnyOf(std::collections::HashSet<std::net::IpAddr>),
+    AnyOf(std::collections::HashSet<IpAddr>),
     /// Matches against an IPv4 address with wildcards
     WildcardIpv4(WildcardIPv4),
+    /// Allow any address of an IPv4 or IPv6 network
+    Cidr(Cidr),
+    /// Rules evaluated in order, the first rule whose filter matches the address decides
+    /// whether it is allowed. Addresses that match no rule are denied.
+    Rules(Vec<FilterRule>),
 }
 
 impl AddressFilter {
-    pub(crate) fn matches(&self, addr: std::net::IpAddr) -> bool {
+    pub(crate) fn matches(&self, addr: IpAddr) -> bool {
+        // IPv4 clients of a dual-stack listener show up as IPv4-mapped IPv6 addresses
+        let addr = addr.to_canonical();
         match self {
             AddressFilter::Any => true,
             AddressFilter::Exact(x) => *x == addr,
             AddressFilter::AnyOf(set) => set.contains(&addr),
             AddressFilter::WildcardIpv4(wc) => wc.matches(addr),
+            AddressFilter::Cidr(cidr) => cidr.matches(addr),
+            AddressFilter::Rules(rules) => rules
+                .iter()
+                .find_map(|rule| match rule {
+                    FilterRule::Allow(filter) => filter.matches(addr).then_some(true),
+                    FilterRule::Deny(filter) => filter.matches(addr).then_some(false),
+                })
+                .unwrap_or(false),
         }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{BadIpv4Wildcard, WildcardIPv4};
-    use std::net::IpAddr;
+    use super::*;
 
     #[test]
     fn parses_address_with_subnet_wildcard() {
@@ -148,4 +227,68 @@ mod test {
         assert!(wc.matches(ip1));
         assert!(!wc.matches(ip2));
     }
+
+    #[test]
+    fn parses_cidr() {
+        let cidr: Cidr = "10.1.0.0/16".parse().unwrap();
+        assert_eq!(cidr, Cidr::new("10.1.0.0".parse().unwrap(), 16).unwrap());
+        assert!("fd00::/8".parse::<Cidr>().is_ok());
+        assert!("0.0.0.0/0".parse::<Cidr>().is_ok());
+
+        for x in [
+            "10.1.0.0",
+            "10.1.0.0/33",
+            "fd00::/129",
+            "10.1.0/16",
+            "10.1.0.0/",
+        ] {
+            assert_eq!(x.parse::<Cidr>(), Err(BadCidr));
+        }
+    }
+
+    #[test]
+    fn cidr_matching_works() {
+        let cidr: Cidr = "192.168.0.0/23".parse().unwrap();
+        assert!(cidr.matches("192.168.1.200".parse().unwrap()));
+        assert!(!cidr.matches("192.168.2.1".parse().unwrap()));
+        assert!(!cidr.matches("fd00::1".parse().unwrap()));
+
+        let cidr: Cidr = "fd00:1::/32".parse().unwrap();
+        assert!(cidr.matches("fd00:1:ffff::1".parse().unwrap()));
+        assert!(!cidr.matches("fd00:2::1".parse().unwrap()));
+
+        let any: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(any.matches("8.8.8.8".parse().unwrap()));
+        let host: Cidr = "10.0.0.1/32".parse().unwrap();
+        assert!(host.matches("10.0.0.1".parse().unwrap()));
+        assert!(!host.matches("10.0.0.2".parse().unwrap()));
+    }
+
+    #[test]
+    fn first_matching_rule_decides() {
+        let filter = AddressFilter::Rules(vec![
+            FilterRule::Deny(AddressFilter::Exact("10.0.0.66".parse().unwrap())),
+            FilterRule::Allow(AddressFilter::Cidr("10.0.0.0/8".parse().unwrap())),
+            FilterRule::Deny(AddressFilter::Any),
+            FilterRule::Allow(AddressFilter::Exact("192.168.0.1".parse().unwrap())),
+        ]);
+
+        assert!(filter.matches("10.0.0.1".parse().unwrap()));
+        assert!(!filter.matches("10.0.0.66".parse().unwrap()));
+        assert!(!filter.matches("192.168.0.1".parse().unwrap()));
+        assert!(!AddressFilter::Rules(Vec::new()).matches("10.0.0.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn rules_match_ipv4_mapped_addresses() {
+        let filter = AddressFilter::Rules(vec![
+            FilterRule::Deny(AddressFilter::Exact("10.0.0.66".parse().unwrap())),
+            FilterRule::Deny(AddressFilter::WildcardIpv4("10.0.1.*".parse().unwrap())),
+            FilterRule::Allow(AddressFilter::Cidr("10.0.0.0/8".parse().unwrap())),
+        ]);
+
+        assert!(filter.matches("::ffff:10.0.0.1".parse().unwrap()));
+        assert!(!filter.matches("::ffff:10.0.0.66".parse().unwrap()));
+        assert!(!filter.matches("::ffff:10.0.1.7".parse().unwrap()));
+    }
 }
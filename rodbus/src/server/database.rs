@@ -0,0 +1,824 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use arc_swap::{ArcSwap, Guard};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{Receiver, Sender};
+
+use crate::exception::ExceptionCode;
+use crate::server::handler::RequestHandler;
+use crate::server::{RequestContext, WriteCoils, WriteRegisters};
+use crate::types::{AddressRange, Indexed, Table};
+
+/// Values of a write before and after it was applied
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WrittenValues {
+    /// Coil values
+    Bits {
+        /// values before the write
+        old: Vec<bool>,
+        /// values after the write
+        new: Vec<bool>,
+    },
+    /// Holding register values
+    Registers {
+        /// values before the write
+        old: Vec<u16>,
+        /// values after the write
+        new: Vec<u16>,
+    },
+}
+
+/// A write to a [`Database`] performed by a Modbus client
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WriteEvent {
+    /// Request that performed the write, `None` if the handler was invoked outside of a server
+    pub origin: Option<RequestContext>,
+    /// Table that was written, either [`Table::Coils`] or [`Table::HoldingRegisters`]
+    pub table: Table,
+    /// Range of addresses that was written
+    pub range: AddressRange,
+    /// Values of the range before and after the write
+    pub values: WrittenValues,
+}
+
+/// Selects the writes reported to a subscriber of a [`Database`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WriteFilter {
+    table: Option<Table>,
+    range: Option<AddressRange>,
+}
+
+impl WriteFilter {
+    /// Report every write
+    pub fn all() -> Self {
+        Self {
+            table: None,
+            range: None,
+        }
+    }
+
+    /// Report every write to a table
+    pub fn table(table: Table) -> Self {
+        Self {
+            table: Some(table),
+            range: None,
+        }
+    }
+
+    /// Report writes to a table that overlap `range`
+    ///
+    /// Matching events contain the entire write, not just the overlapping addresses.
+    pub fn range(table: Table, range: AddressRange) -> Self {
+        Self {
+            table: Some(table),
+            range: Some(range),
+        }
+    }
+
+    fn matches(&self, table: Table, range: AddressRange) -> bool {
+        fn overlaps(a: AddressRange, b: AddressRange) -> bool {
+            let end = |x: AddressRange| u32::from(x.start) + u32::from(x.count);
+            u32::from(a.start) < end(b) && u32::from(b.start) < end(a)
+        }
+
+        self.table.is_none_or(|x| x == table) && self.range.is_none_or(|x| overlaps(x, range))
+    }
+}
+
+struct Subscriber {
+    filter: WriteFilter,
+    tx: Sender<WriteEvent>,
+    dropped: Arc<AtomicU64>,
+}
+
+/// Receives the writes reported to a subscriber of a [`Database`]
+///
+/// Events are queued up to the capacity given to [`Database::subscribe`]. Events that
+/// don't fit because the subscriber isn't keeping up are discarded and counted.
+/// Dropping the subscription ends it.
+pub struct WriteSubscription {
+    rx: Receiver<WriteEvent>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl WriteSubscription {
+    /// Wait for the next event
+    ///
+    /// Returns `None` once the database and all of its clones are dropped.
+    pub async fn recv(&mut self) -> Option<WriteEvent> {
+        self.rx.recv().await
+    }
+
+    /// Retrieve the next event if one is queued
+    pub fn try_recv(&mut self) -> Option<WriteEvent> {
+        self.rx.try_recv().ok()
+    }
+
+    /// Number of events discarded because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Error returned when a region cannot be added to a [`PointTable`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RegionError {
+    /// A dense region must contain at least one value
+    Empty,
+    /// The region extends past address 65535
+    AddressOverflow,
+    /// The address is already defined in the table
+    Overlap(u16),
+}
+
+impl std::error::Error for RegionError {}
+
+impl std::fmt::Display for RegionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            RegionError::Empty => f.write_str("region contains no values"),
+            RegionError::AddressOverflow => f.write_str("region extends past address 65535"),
+            RegionError::Overlap(address) => {
+                write!(f, "address {address} is already defined in the table")
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct DenseRegion<T> {
+    start: u16,
+    values: Vec<T>,
+}
+
+impl<T> DenseRegion<T> {
+    fn end(&self) -> u16 {
+        // regions are never empty and never overflow
+        self.start + (self.values.len() - 1) as u16
+    }
+}
+
+/// Point values of a single Modbus table
+///
+/// Addresses are stored either in dense regions, contiguous blocks backed by a `Vec`,
+/// or individually as sparse points. Each address may only be defined once.
+#[derive(Clone)]
+pub struct PointTable<T> {
+    // sorted by start address and never overlapping
+    dense: Vec<DenseRegion<T>>,
+    sparse: BTreeMap<u16, T>,
+}
+
+impl<T: Copy> PointTable<T> {
+    fn new() -> Self {
+        Self {
+            dense: Vec::new(),
+            sparse: BTreeMap::new(),
+        }
+    }
+
+    /// Define a contiguous block of addresses beginning at `start`
+    pub fn add_dense(&mut self, start: u16, values: Vec<T>) -> Result<(), RegionError> {
+        if values.is_empty() {
+            return Err(RegionError::Empty);
+        }
+        let end = u16::try_from(start as usize + values.len() - 1)
+            .map_err(|_| RegionError::AddressOverflow)?;
+
+        let pos = self.dense.partition_point(|r| r.start < start);
+        if let Some(prev) = pos.checked_sub(1).and_then(|i| self.dense.get(i)) {
+            if prev.end() >= start {
+                return Err(RegionError::Overlap(start));
+            }
+        }
+        if let Some(next) = self.dense.get(pos) {
+            if next.start <= end {
+                return Err(RegionError::Overlap(next.start));
+            }
+        }
+        if let Some((address, _)) = self.sparse.range(start..=end).next() {
+            return Err(RegionError::Overlap(*address));
+        }
+
+        self.dense.insert(pos, DenseRegion { start, values });
+        Ok(())
+    }
+
+    /// Define a single address
+    pub fn add_sparse(&mut self, address: u16, value: T) -> Result<(), RegionError> {
+        if self.contains(address) {
+            return Err(RegionError::Overlap(address));
+        }
+        self.sparse.insert(address, value);
+        Ok(())
+    }
+
+    /// Check if an address is defined in the table
+    pub fn contains(&self, address: u16) -> bool {
+        self.get(address).is_some()
+    }
+
+    /// Get the value at an address, if defined
+    pub fn get(&self, address: u16) -> Option<T> {
+        match self.region(address) {
+            Some(region) => region
+                .values
+                .get((address - region.start) as usize)
+                .copied(),
+            None => self.sparse.get(&address).copied(),
+        }
+    }
+
+    /// Update the value at an address, returning `false` if the address is not defined
+    pub fn set(&mut self, address: u16, value: T) -> bool {
+        let slot = match self.region_index(address) {
+            Some(i) => {
+                let region = &mut self.dense[i];
+                region.values.get_mut((address - region.start) as usize)
+            }
+            None => self.sparse.get_mut(&address),
+        };
+        match slot {
+            Some(x) => {
+                *x = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn region_index(&self, address: u16) -> Option<usize> {
+        let i = self
+            .dense
+            .partition_point(|r| r.start <= address)
+            .checked_sub(1)?;
+        (self.dense[i].end() >= address).then_some(i)
+    }
+
+    fn region(&self, address: u16) -> Option<&DenseRegion<T>> {
+        self.region_index(address).map(|i| &self.dense[i])
+    }
+
+    fn read_into(&self, range: AddressRange, values: &mut [T]) -> Result<(), ExceptionCode> {
+        // fast path when the whole range lies within a single dense region
+        if let Some(region) = self.region(range.start) {
+            let offset = (range.start - region.start) as usize;
+            if let Some(src) = region.values.get(offset..offset + values.len()) {
+                values.copy_from_slice(src);
+                return Ok(());
+            }
+        }
+
+        for (value, address) in values.iter_mut().zip(range.iter()) {
+            *value = self.get(address).ok_or(ExceptionCode::IllegalDataAddress)?;
+        }
+        Ok(())
+    }
+
+    /// returns the values before the write
+    fn write(
+        &mut self,
+        values: impl Iterator<Item = Indexed<T>> + Clone,
+    ) -> Result<Vec<T>, ExceptionCode> {
+        // read every address first so that a failed request leaves the table untouched
+        let old = values
+            .clone()
+            .map(|x| self.get(x.index))
+            .collect::<Option<Vec<T>>>()
+            .ok_or(ExceptionCode::IllegalDataAddress)?;
+        for x in values {
+            self.set(x.index, x.value);
+        }
+        Ok(old)
+    }
+}
+
+/// The four Modbus tables of a [`Database`]
+#[derive(Clone)]
+pub struct Tables {
+    /// coils
+    pub coils: PointTable<bool>,
+    /// discrete inputs
+    pub discrete_inputs: PointTable<bool>,
+    /// holding registers
+    pub holding_registers: PointTable<u16>,
+    /// input registers
+    pub input_registers: PointTable<u16>,
+}
+
+/// Thread-safe in-memory point database that implements [`RequestHandler`]
+///
+/// Clones share the same tables, so one clone can be passed to the server
+/// while the application keeps another to update values.
+///
+/// Reads use a snapshot of the tables and never wait for updates. Transactions and writes
+/// by Modbus clients are applied to a copy of the tables which replaces the snapshot once
+/// complete, so readers see either all or none of an update. Since every update copies the
+/// tables, group related changes into a single [`Database::transaction`].
+///
+/// Writes performed by Modbus clients can be observed using [`Database::subscribe`].
+#[derive(Clone)]
+pub struct Database {
+    tables: Arc<ArcSwap<Tables>>,
+    // serializes updates so that none of them is lost
+    update: Arc<Mutex<()>>,
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Database {
+    /// Create a database with four empty tables
+    pub fn new() -> Self {
+        Self {
+            tables: Arc::new(ArcSwap::from_pointee(Tables {
+                coils: PointTable::new(),
+                discrete_inputs: PointTable::new(),
+                holding_registers: PointTable::new(),
+                input_registers: PointTable::new(),
+            })),
+            update: Default::default(),
+            subscribers: Default::default(),
+        }
+    }
+
+    /// Receive an event for every write by a Modbus client that matches `filter`
+    ///
+    /// At most `capacity` events (minimum 1) are queued, writes never wait for the subscriber.
+    /// Writes made using [`Database::transaction`] are not reported.
+    pub fn subscribe(&self, filter: WriteFilter, capacity: usize) -> WriteSubscription {
+        let (tx, rx) = tokio::sync::mpsc::channel(capacity.max(1));
+        let dropped = Arc::new(AtomicU64::new(0));
+        self.lock_subscribers().push(Subscriber {
+            filter,
+            tx,
+            dropped: dropped.clone(),
+        });
+        WriteSubscription { rx, dropped }
+    }
+
+    /// Read values from a consistent snapshot of all tables
+    pub fn read<R>(&self, read: impl FnOnce(&Tables) -> R) -> R {
+        read(&self.snapshot())
+    }
+
+    /// Add or update any number of values atomically with respect to the server
+    ///
+    /// Reads, including those of the server, use the previous values until the transaction
+    /// completes.
+    pub fn transaction<R>(&self, transaction: impl FnOnce(&mut Tables) -> R) -> R {
+        let _update = self.lock_update();
+        let mut tables = self.copy();
+        let result = transaction(&mut tables);
+        self.tables.store(Arc::new(tables));
+        result
+    }
+
+    fn snapshot(&self) -> Guard<Arc<Tables>> {
+        self.tables.load()
+    }
+
+    // updates are applied to a copy that only replaces the tables once it is complete
+    fn copy(&self) -> Tables {
+        Tables::clone(&self.snapshot())
+    }
+
+    // nothing is shared until an update completes, so a panic while holding the lock is harmless
+    fn lock_update(&self) -> MutexGuard<'_, ()> {
+        self.update.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn lock_subscribers(&self) -> MutexGuard<'_, Vec<Subscriber>> {
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn write_coils(
+        &self,
+        context: Option<&RequestContext>,
+        range: AddressRange,
+        values: impl Iterator<Item = Indexed<bool>> + Clone,
+    ) -> Result<(), ExceptionCode> {
+        let _update = self.lock_update();
+        let mut tables = self.copy();
+        let old = tables.coils.write(values.clone())?;
+        self.tables.store(Arc::new(tables));
+        // notify before releasing the lock so that events are ordered like the writes
+        self.notify(context, Table::Coils, range, || WrittenValues::Bits {
+            old,
+            new: values.map(|x| x.value).collect(),
+        });
+        Ok(())
+    }
+
+    fn write_registers(
+        &self,
+        context: Option<&RequestContext>,
+        range: AddressRange,
+        values: impl Iterator<Item = Indexed<u16>> + Clone,
+    ) -> Result<(), ExceptionCode> {
+        let _update = self.lock_update();
+        let mut tables = self.copy();
+        let old = tables.holding_registers.write(values.clone())?;
+        self.tables.store(Arc::new(tables));
+        self.notify(context, Table::HoldingRegisters, range, || {
+            WrittenValues::Registers {
+                old,
+                new: values.map(|x| x.value).collect(),
+            }
+        });
+        Ok(())
+    }
+
+    fn notify(
+        &self,
+        context: Option<&RequestContext>,
+        table: Table,
+        range: AddressRange,
+        values: impl FnOnce() -> WrittenValues,
+    ) {
+        let mut subscribers = self.lock_subscribers();
+        subscribers.retain(|x| !x.tx.is_closed());
+        if !subscribers.iter().any(|x| x.filter.matches(table, range)) {
+            return;
+        }
+
+        let event = WriteEvent {
+            origin: context.cloned(),
+            table,
+            range,
+            values: values(),
+        };
+        for subscriber in subscribers.iter() {
+            if subscriber.filter.matches(table, range) {
+                if let Err(TrySendError::Full(_)) = subscriber.tx.try_send(event.clone()) {
+                    subscriber.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+}
+
+fn single(index: u16) -> AddressRange {
+    AddressRange {
+        start: index,
+        count: 1,
+    }
+}
+
+impl RequestHandler for Database {
+    fn read_coil(&self, address: u16) -> Result<bool, ExceptionCode> {
+        self.snapshot()
+            .coils
+            .get(address)
+            .ok_or(ExceptionCode::IllegalDataAddress)
+    }
+
+    fn read_discrete_input(&self, address: u16) -> Result<bool, ExceptionCode> {
+        self.snapshot()
+            .discrete_inputs
+            .get(address)
+            .ok_or(ExceptionCode::IllegalDataAddress)
+    }
+
+    fn read_holding_register(&self, address: u16) -> Result<u16, ExceptionCode> {
+        self.snapshot()
+            .holding_registers
+            .get(address)
+            .ok_or(ExceptionCode::IllegalDataAddress)
+    }
+
+    fn read_input_register(&self, address: u16) -> Result<u16, ExceptionCode> {
+        self.snapshot()
+            .input_registers
+            .get(address)
+            .ok_or(ExceptionCode::IllegalDataAddress)
+    }
+
+    fn read_coils_into(
+        &self,
+        range: AddressRange,
+        values: &mut [bool],
+    ) -> Result<(), ExceptionCode> {
+        self.snapshot().coils.read_into(range, values)
+    }
+
+    fn read_discrete_inputs_into(
+        &self,
+        range: AddressRange,
+        values: &mut [bool],
+    ) -> Result<(), ExceptionCode> {
+        self.snapshot().discrete_inputs.read_into(range, values)
+    }
+
+    fn read_holding_registers_into(
+        &self,
+        range: AddressRange,
+        values: &mut [u16],
+    ) -> Result<(), ExceptionCode> {
+        self.snapshot().holding_registers.read_into(range, values)
+    }
+
+    fn read_input_registers_into(
+        &self,
+        range: AddressRange,
+        values: &mut [u16],
+    ) -> Result<(), ExceptionCode> {
+        self.snapshot().input_registers.read_into(range, values)
+    }
+
+    fn write_single_coil(&mut self, value: Indexed<bool>) -> Result<(), ExceptionCode> {
+        self.write_coils(None, single(value.index), std::iter::once(value))
+    }
+
+    fn write_single_register(&mut self, value: Indexed<u16>) -> Result<(), ExceptionCode> {
+        self.write_registers(None, single(value.index), std::iter::once(value))
+    }
+
+    fn write_multiple_coils(&mut self, values: WriteCoils) -> Result<(), ExceptionCode> {
+        self.write_coils(None, values.range, values.iterator)
+    }
+
+    fn write_multiple_registers(&mut self, values: WriteRegisters) -> Result<(), ExceptionCode> {
+        self.write_registers(None, values.range, values.iterator)
+    }
+
+    fn write_single_coil_with_context(
+        &mut self,
+        context: &RequestContext,
+        value: Indexed<bool>,
+    ) -> Result<(), ExceptionCode> {
+        self.write_coils(Some(context), single(value.index), std::iter::once(value))
+    }
+
+    fn write_single_register_with_context(
+        &mut self,
+        context: &RequestContext,
+        value: Indexed<u16>,
+    ) -> Result<(), ExceptionCode> {
+        self.write_registers(Some(context), single(value.index), std::iter::once(value))
+    }
+
+    fn write_multiple_coils_with_context(
+        &mut self,
+        context: &RequestContext,
+        values: WriteCoils,
+    ) -> Result<(), ExceptionCode> {
+        self.write_coils(Some(context), values.range, values.iterator)
+    }
+
+    fn write_multiple_registers_with_context(
+        &mut self,
+        context: &RequestContext,
+        values: WriteRegisters,
+    ) -> Result<(), ExceptionCode> {
+        self.write_registers(Some(context), values.range, values.iterator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::UnitId;
+
+    fn table() -> PointTable<u16> {
+        let mut table = PointTable::new();
+        table.add_dense(10, vec![1, 2, 3]).unwrap();
+        table.add_sparse(20, 4).unwrap();
+        table.add_dense(30, vec![5, 6]).unwrap();
+        table
+    }
+
+    #[test]
+    fn rejects_invalid_regions() {
+        let mut table = table();
+        assert_eq!(table.add_dense(0, vec![]), Err(RegionError::Empty));
+        assert_eq!(
+            table.add_dense(u16::MAX, vec![0, 0]),
+            Err(RegionError::AddressOverflow)
+        );
+        assert_eq!(
+            table.add_dense(8, vec![0; 3]),
+            Err(RegionError::Overlap(10))
+        );
+        assert_eq!(
+            table.add_dense(12, vec![0; 3]),
+            Err(RegionError::Overlap(12))
+        );
+        assert_eq!(
+            table.add_dense(15, vec![0; 10]),
+            Err(RegionError::Overlap(20))
+        );
+        assert_eq!(table.add_sparse(31, 0), Err(RegionError::Overlap(31)));
+        assert_eq!(table.add_sparse(20, 0), Err(RegionError::Overlap(20)));
+        assert_eq!(table.add_dense(u16::MAX, vec![0]), Ok(()));
+    }
+
+    #[test]
+    fn gets_and_sets_dense_and_sparse_values() {
+        let mut table = table();
+        assert_eq!(table.get(9), None);
+        assert_eq!(table.get(12), Some(3));
+        assert_eq!(table.get(13), None);
+        assert_eq!(table.get(20), Some(4));
+        assert!(table.set(31, 42));
+        assert!(table.set(20, 43));
+        assert!(!table.set(21, 44));
+        assert_eq!(table.get(31), Some(42));
+        assert_eq!(table.get(20), Some(43));
+    }
+
+    #[test]
+    fn reads_ranges_across_regions() {
+        let mut table = table();
+        table.add_dense(13, vec![7; 7]).unwrap();
+
+        let mut values = [0; 4];
+        table
+            .read_into(AddressRange::try_from(11, 4).unwrap(), &mut values)
+            .unwrap();
+        assert_eq!(values, [2, 3, 7, 7]);
+
+        let mut values = [0; 2];
+        table
+            .read_into(AddressRange::try_from(19, 2).unwrap(), &mut values)
+            .unwrap();
+        assert_eq!(values, [7, 4]);
+
+        let mut values = [0; 3];
+        // 21 is not defined
+        assert_eq!(
+            table.read_into(AddressRange::try_from(19, 3).unwrap(), &mut values),
+            Err(ExceptionCode::IllegalDataAddress)
+        );
+    }
+
+    #[test]
+    fn failed_write_leaves_table_untouched() {
+        let mut table = table();
+        let values = [Indexed::new(10, 100), Indexed::new(13, 101)];
+        assert_eq!(
+            table.write(values.iter().copied()),
+            Err(ExceptionCode::IllegalDataAddress)
+        );
+        assert_eq!(table.get(10), Some(1));
+    }
+
+    #[test]
+    fn handler_shares_tables_with_clones() {
+        let db = Database::new();
+        db.transaction(|tables| {
+            tables.coils.add_dense(0, vec![false; 4]).unwrap();
+            tables.holding_registers.add_sparse(7, 0).unwrap();
+        });
+
+        let mut handler = db.clone();
+        handler.write_single_coil(Indexed::new(2, true)).unwrap();
+        handler.write_single_register(Indexed::new(7, 99)).unwrap();
+
+        assert_eq!(db.read(|tables| tables.coils.get(2)), Some(true));
+        assert_eq!(db.read(|tables| tables.holding_registers.get(7)), Some(99));
+        assert_eq!(
+            handler.write_single_register(Indexed::new(8, 0)),
+            Err(ExceptionCode::IllegalDataAddress)
+        );
+    }
+
+    #[test]
+    fn reads_are_not_blocked_by_transactions() {
+        let db = Database::new();
+        db.transaction(|tables| tables.coils.add_sparse(0, false).unwrap());
+
+        let handler = db.clone();
+        db.transaction(|tables| {
+            tables.coils.set(0, true);
+            assert_eq!(handler.read_coil(0), Ok(false));
+        });
+        assert_eq!(handler.read_coil(0), Ok(true));
+    }
+
+    #[test]
+    fn reports_writes_to_matching_subscribers() {
+        let db = Database::new();
+        db.transaction(|tables| {
+            tables.coils.add_dense(0, vec![false; 4]).unwrap();
+            tables.holding_registers.add_dense(0, vec![0; 10]).unwrap();
+        });
+
+        let mut all = db.subscribe(WriteFilter::all(), 16);
+        let mut coils = db.subscribe(WriteFilter::table(Table::Coils), 16);
+        let mut range = db.subscribe(
+            WriteFilter::range(
+                Table::HoldingRegisters,
+                AddressRange::try_from(5, 2).unwrap(),
+            ),
+            16,
+        );
+
+        let origin = RequestContext {
+            unit_id: UnitId::new(3),
+            session: 7,
+            remote: Some("127.0.0.1:50000".parse().unwrap()),
+            role: None,
+            tx_id: Some(11),
+        };
+        let mut handler = db.clone();
+        handler
+            .write_single_coil_with_context(&origin, Indexed::new(1, true))
+            .unwrap();
+        handler
+            .write_single_register_with_context(&origin, Indexed::new(4, 42))
+            .unwrap();
+        handler
+            .write_single_register_with_context(&origin, Indexed::new(6, 43))
+            .unwrap();
+        handler.write_single_register(Indexed::new(5, 44)).unwrap();
+        // failed writes are not reported
+        assert!(handler.write_single_register(Indexed::new(10, 0)).is_err());
+
+        assert_eq!(
+            coils.try_recv().unwrap(),
+            WriteEvent {
+                origin: Some(origin.clone()),
+                table: Table::Coils,
+                range: AddressRange::try_from(1, 1).unwrap(),
+                values: WrittenValues::Bits {
+                    old: vec![false],
+                    new: vec![true],
+                },
+            }
+        );
+        assert!(coils.try_recv().is_none());
+
+        let event = range.try_recv().unwrap();
+        assert_eq!(event.origin, Some(origin));
+        assert_eq!(
+            event.values,
+            WrittenValues::Registers {
+                old: vec![0],
+                new: vec![43],
+            }
+        );
+        assert_eq!(range.try_recv().unwrap().origin, None);
+        assert!(range.try_recv().is_none());
+
+        let count = std::iter::from_fn(|| all.try_recv()).count();
+        assert_eq!(count, 4);
+        assert_eq!(all.dropped(), 0);
+    }
+
+    #[test]
+    fn events_are_dropped_when_the_subscriber_falls_behind() {
+        let db = Database::new();
+        db.transaction(|tables| tables.holding_registers.add_sparse(0, 0).unwrap());
+
+        let mut events = db.subscribe(WriteFilter::all(), 2);
+        let mut handler = db.clone();
+        for value in 1..=5 {
+            handler
+                .write_single_register(Indexed::new(0, value))
+                .unwrap();
+        }
+
+        // the oldest events are kept
+        let new: Vec<WrittenValues> = std::iter::from_fn(|| events.try_recv())
+            .map(|x| x.values)
+            .collect();
+        assert_eq!(
+            new,
+            vec![
+                WrittenValues::Registers {
+                    old: vec![0],
+                    new: vec![1],
+                },
+                WrittenValues::Registers {
+                    old: vec![1],
+                    new: vec![2],
+                },
+            ]
+        );
+        assert_eq!(events.dropped(), 3);
+
+        // there is room again once the queue is drained
+        handler.write_single_register(Indexed::new(0, 6)).unwrap();
+        assert!(events.try_recv().is_some());
+        assert_eq!(events.dropped(), 3);
+    }
+
+    #[test]
+    fn dropped_subscribers_are_removed() {
+        let db = Database::new();
+        db.transaction(|tables| tables.coils.add_sparse(0, false).unwrap());
+
+        drop(db.subscribe(WriteFilter::all(), 1));
+        db.clone().write_single_coil(Indexed::new(0, true)).unwrap();
+        assert!(db.lock_subscribers().is_empty());
+    }
+}
@@ -0,0 +1,643 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use crate::client::{Channel, RequestParam, WriteMultiple};
+use crate::decode::DecodeLevel;
+use crate::error::RequestError;
+use crate::exception::ExceptionCode;
+use crate::maybe_async::MaybeAsync;
+use crate::server::handler::{AsyncRequestHandler, ServerHandlerMap, ServerHandlerType};
+#[cfg(feature = "serial")]
+use crate::server::spawn_rtu_server_task;
+use crate::server::{
+    spawn_tcp_server_task, AddressFilter, RequestContext, ServerHandle, WriteCoils, WriteRegisters,
+};
+#[cfg(feature = "tls")]
+use crate::server::{spawn_tls_server_task, TlsServerConfig};
+use crate::types::{
+    AddressRange, DeviceIdentification, Indexed, MeiCode, ReadDeviceIdCode, ReadDeviceInfoBlock,
+    UnitId,
+};
+
+/// Device on a client [`Channel`] that a gateway forwards requests to
+#[derive(Debug, Clone)]
+pub struct GatewayTarget {
+    channel: Channel,
+    param: RequestParam,
+}
+
+impl GatewayTarget {
+    /// Forward requests to `unit_id` on `channel`, waiting at most `response_timeout` for a response
+    pub fn new(channel: Channel, unit_id: UnitId, response_timeout: Duration) -> Self {
+        Self {
+            channel,
+            param: RequestParam::new(unit_id, response_timeout),
+        }
+    }
+}
+
+/// Maps the unit id of requests received by a gateway to a [`GatewayTarget`]
+///
+/// The unit id of the target may differ from the unit id of the request, which
+/// allows devices on different channels that share a unit id to be reached
+/// through a single gateway. Any number of unit ids may share a channel.
+#[derive(Debug, Clone, Default)]
+pub struct GatewayMap {
+    targets: BTreeMap<UnitId, GatewayTarget>,
+}
+
+impl GatewayMap {
+    /// Create an empty map
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forward requests for `id` to `target`, returning the previous target of `id` if present
+    pub fn add(&mut self, id: UnitId, target: GatewayTarget) -> Option<GatewayTarget> {
+        self.targets.insert(id, target)
+    }
+
+    /// TCP gateways answer requests for every unit id, RTU gateways only answer mapped ids
+    ///
+    /// Only RTU gateways receive broadcasts, which the server passes to every handler. They are
+    /// forwarded by a single handler per channel and target unit id so that devices mapped under
+    /// several unit ids only receive them once.
+    fn into_handlers(self, rtu: bool) -> ServerHandlerMap<GatewayHandler> {
+        let mut handlers = ServerHandlerMap::new();
+        if !rtu {
+            for id in u8::MIN..=u8::MAX {
+                handlers.add(UnitId::new(id), GatewayHandler::Unavailable.wrap());
+            }
+        }
+        let mut broadcast_targets: Vec<GatewayTarget> = Vec::new();
+        for (id, target) in self.targets {
+            let duplicate = broadcast_targets.iter().any(|x| {
+                x.param.id == target.param.id && x.channel.tx.same_channel(&target.channel.tx)
+            });
+            if !duplicate {
+                broadcast_targets.push(target.clone());
+            }
+            let handler = GatewayHandler::Forward {
+                target,
+                skip_broadcasts: rtu && duplicate,
+            };
+            handlers.add(id, handler.wrap());
+        }
+        handlers
+    }
+}
+
+enum GatewayHandler {
+    Forward {
+        target: GatewayTarget,
+        skip_broadcasts: bool,
+    },
+    Unavailable,
+}
+
+impl GatewayHandler {
+    fn wrap(self) -> ServerHandlerType<Self> {
+        std::sync::Arc::new(std::sync::Mutex::new(Box::new(self)))
+    }
+
+    fn forward<T, F>(
+        &self,
+        request: impl FnOnce(Channel, RequestParam) -> F,
+    ) -> MaybeAsync<Result<T, ExceptionCode>>
+    where
+        F: Future<Output = Result<T, RequestError>> + Send + 'static,
+    {
+        match self {
+            Self::Unavailable => MaybeAsync::ready(Err(ExceptionCode::GatewayPathUnavailable)),
+            Self::Forward { target, .. } => {
+                let response = request(target.channel.clone(), target.param);
+                MaybeAsync::asynchronous(async move { response.await.map_err(to_exception) })
+            }
+        }
+    }
+
+    /// forward a write unless it's a broadcast that another handler forwards to the same device
+    fn forward_write<F>(
+        &self,
+        context: &RequestContext,
+        request: impl FnOnce(Channel, RequestParam) -> F,
+    ) -> MaybeAsync<Result<(), ExceptionCode>>
+    where
+        F: Future<Output = Result<(), RequestError>> + Send + 'static,
+    {
+        match self {
+            Self::Forward {
+                skip_broadcasts: true,
+                ..
+            } if context.unit_id == UnitId::broadcast() => MaybeAsync::ready(Ok(())),
+            _ => self.forward(request),
+        }
+    }
+
+    fn read_device_identification(
+        &self,
+        dev_id: ReadDeviceIdCode,
+        obj_id: u8,
+    ) -> MaybeAsync<Result<DeviceIdentification, ExceptionCode>> {
+        let block = ReadDeviceInfoBlock {
+            mei_type: MeiCode::ReadDeviceId,
+            dev_id,
+            obj_id,
+        };
+        self.forward(move |mut channel, param| async move {
+            channel.read_device_identification(param, block).await
+        })
+    }
+}
+
+/// Responses from the target are passed through, other failures are reported as gateway exceptions
+fn to_exception(err: RequestError) -> ExceptionCode {
+    match err {
+        RequestError::Exception(ex) => ex,
+        RequestError::ResponseTimeout => ExceptionCode::GatewayTargetDeviceFailedToRespond,
+        RequestError::BadRequest(_) => ExceptionCode::IllegalDataValue,
+        _ => ExceptionCode::GatewayPathUnavailable,
+    }
+}
+
+fn values<T>(values: Vec<Indexed<T>>) -> Vec<T> {
+    values.into_iter().map(|x| x.value).collect()
+}
+
+impl AsyncRequestHandler for GatewayHandler {
+    fn read_coils(
+        &self,
+        _context: &RequestContext,
+        range: AddressRange,
+    ) -> MaybeAsync<Result<Vec<bool>, ExceptionCode>> {
+        self.forward(move |mut channel, param| async move {
+            channel.read_coils(param, range).await.map(values)
+        })
+    }
+
+    fn read_discrete_inputs(
+        &self,
+        _context: &RequestContext,
+        range: AddressRange,
+    ) -> MaybeAsync<Result<Vec<bool>, ExceptionCode>> {
+        self.forward(move |mut channel, param| async move {
+            channel.read_discrete_inputs(param, range).await.map(values)
+        })
+    }
+
+    fn read_holding_registers(
+        &self,
+        _context: &RequestContext,
+        range: AddressRange,
+    ) -> MaybeAsync<Result<Vec<u16>, ExceptionCode>> {
+        self.forward(move |mut channel, param| async move {
+            channel
+                .read_holding_registers(param, range)
+                .await
+                .map(values)
+        })
+    }
+
+    fn read_input_registers(
+        &self,
+        _context: &RequestContext,
+        range: AddressRange,
+    ) -> MaybeAsync<Result<Vec<u16>, ExceptionCode>> {
+        self.forward(move |mut channel, param| async move {
+            channel.read_input_registers(param, range).await.map(values)
+        })
+    }
+
+    fn read_basic_device_identification(
+        &self,
+        _context: &RequestContext,
+        obj_id: u8,
+    ) -> MaybeAsync<Result<DeviceIdentification, ExceptionCode>> {
+        self.read_device_identification(ReadDeviceIdCode::BasicStreaming, obj_id)
+    }
+
+    fn read_regular_device_identification(
+        &self,
+        _context: &RequestContext,
+        obj_id: u8,
+    ) -> MaybeAsync<Result<DeviceIdentification, ExceptionCode>> {
+        self.read_device_identification(ReadDeviceIdCode::RegularStreaming, obj_id)
+    }
+
+    fn read_extended_device_identification(
+        &self,
+        _context: &RequestContext,
+        obj_id: u8,
+    ) -> MaybeAsync<Result<DeviceIdentification, ExceptionCode>> {
+        self.read_device_identification(ReadDeviceIdCode::ExtendedStreaming, obj_id)
+    }
+
+    fn write_coil(
+        &mut self,
+        context: &RequestContext,
+        value: Indexed<bool>,
+    ) -> MaybeAsync<Result<(), ExceptionCode>> {
+        self.forward_write(context, move |mut channel, param| async move {
+            channel.write_single_coil(param, value).await.map(|_| ())
+        })
+    }
+
+    fn write_register(
+        &mut self,
+        context: &RequestContext,
+        value: Indexed<u16>,
+    ) -> MaybeAsync<Result<(), ExceptionCode>> {
+        self.forward_write(context, move |mut channel, param| async move {
+            channel
+                .write_single_register(param, value)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    fn write_coils(
+        &mut self,
+        context: &RequestContext,
+        values: WriteCoils,
+    ) -> MaybeAsync<Result<(), ExceptionCode>> {
+        let start = values.range.start;
+        match WriteMultiple::from(start, values.iterator.map(|x| x.value).collect()) {
+            Ok(request) => self.forward_write(context, move |mut channel, param| async move {
+                channel
+                    .write_multiple_coils(param, request)
+                    .await
+                    .map(|_| ())
+            }),
+            Err(_) => MaybeAsync::ready(Err(ExceptionCode::IllegalDataValue)),
+        }
+    }
+
+    fn write_registers(
+        &mut self,
+        context: &RequestContext,
+        values: WriteRegisters,
+    ) -> MaybeAsync<Result<(), ExceptionCode>> {
+        let start = values.range.start;
+        match WriteMultiple::from(start, values.iterator.map(|x| x.value).collect()) {
+            Ok(request) => self.forward_write(context, move |mut channel, param| async move {
+                channel
+                    .write_multiple_registers(param, request)
+                    .await
+                    .map(|_| ())
+            }),
+            Err(_) => MaybeAsync::ready(Err(ExceptionCode::IllegalDataValue)),
+        }
+    }
+
+    fn timeout_exception(&self) -> ExceptionCode {
+        ExceptionCode::GatewayTargetDeviceFailedToRespond
+    }
+}
+
+/// Spawns a gateway that accepts Modbus TCP connections and forwards requests to client channels
+///
+/// Requests for unit ids that are not in `map` are answered with
+/// [`ExceptionCode::GatewayPathUnavailable`], and requests that time out on the target channel
+/// or are not answered within the request timeout of the server with
+/// [`ExceptionCode::GatewayTargetDeviceFailedToRespond`]. Exceptions returned by the
+/// target device are passed through unchanged.
+///
+/// Each channel executes one request at a time in the order they were received, so requests
+/// from concurrent TCP sessions to devices on the same serial port are serialized on the bus.
+/// The request timeout of the server, which can be changed using
+/// [`ServerHandle::set_request_timeout`], should allow for this queuing. Requests still queued
+/// on the channel when the server times out are dropped instead of being sent to the target.
+///
+/// * `max_sessions` - Maximum number of concurrent sessions
+/// * `addr` - A socket address to bound to
+/// * `map` - Targets of the gateway keyed by unit id
+/// * `filter` - Address filter which may be used to restrict the connecting IP address
+/// * `decode` - Decode log level
+///
+/// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+pub async fn spawn_tcp_gateway_task(
+    max_sessions: usize,
+    addr: SocketAddr,
+    map: GatewayMap,
+    filter: AddressFilter,
+    decode: DecodeLevel,
+) -> Result<ServerHandle, std::io::Error> {
+    spawn_tcp_server_task(max_sessions, addr, map.into_handlers(false), filter, decode).await
+}
+
+/// Spawns a gateway that accepts Modbus TLS connections and forwards requests to client channels
+///
+/// Requests are forwarded in the same way as [`spawn_tcp_gateway_task`].
+///
+/// * `max_sessions` - Maximum number of concurrent sessions
+/// * `addr` - A socket address to bound to
+/// * `map` - Targets of the gateway keyed by unit id
+/// * `tls_config` - TLS configuration
+/// * `filter` - Address filter which may be used to restrict the connecting IP address
+/// * `decode` - Decode log level
+///
+/// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+#[cfg(feature = "tls")]
+pub async fn spawn_tls_gateway_task(
+    max_sessions: usize,
+    addr: SocketAddr,
+    map: GatewayMap,
+    tls_config: TlsServerConfig,
+    filter: AddressFilter,
+    decode: DecodeLevel,
+) -> Result<ServerHandle, std::io::Error> {
+    spawn_tls_server_task(
+        max_sessions,
+        addr,
+        map.into_handlers(false),
+        tls_config,
+        filter,
+        decode,
+    )
+    .await
+}
+
+/// Spawns a gateway that acts as a Modbus RTU server on a serial port and forwards requests
+/// to client channels, usually TCP or TLS channels
+///
+/// Unlike [`spawn_tcp_gateway_task`], requests for unit ids that are not in `map` are not
+/// answered so that other devices on the bus may respond to them. Exceptions returned by the
+/// target device are passed through unchanged. Broadcast writes are forwarded to every
+/// target in `map` concurrently, and the gateway serves the next request on the bus without
+/// waiting for the targets to answer them.
+///
+/// * `path` - Path to the serial device. Generally `/dev/tty0` on Linux and `COM1` on Windows.
+/// * `settings` - Serial port settings
+/// * `retry` - A boxed trait object that controls when opening the serial port is retried after a failure
+/// * `map` - Targets of the gateway keyed by unit id
+/// * `decode` - Decode log level
+///
+/// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+#[cfg(feature = "serial")]
+pub fn spawn_rtu_gateway_task(
+    path: &str,
+    settings: crate::serial::SerialSettings,
+    retry: Box<dyn crate::retry::RetryStrategy>,
+    map: GatewayMap,
+    decode: DecodeLevel,
+) -> Result<ServerHandle, std::io::Error> {
+    spawn_rtu_server_task(path, settings, retry, map.into_handlers(true), decode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_request_errors_to_exceptions() {
+        assert_eq!(
+            to_exception(RequestError::Exception(ExceptionCode::IllegalDataAddress)),
+            ExceptionCode::IllegalDataAddress
+        );
+        assert_eq!(
+            to_exception(RequestError::ResponseTimeout),
+            ExceptionCode::GatewayTargetDeviceFailedToRespond
+        );
+        assert_eq!(
+            to_exception(RequestError::NoConnection),
+            ExceptionCode::GatewayPathUnavailable
+        );
+    }
+
+    #[test]
+    fn unmapped_ids_are_only_answered_by_tcp_gateways() {
+        let mut tcp = GatewayMap::new().into_handlers(false);
+        let mut rtu = GatewayMap::new().into_handlers(true);
+        assert!(tcp.get(UnitId::new(0)).is_some());
+        assert!(tcp.get(UnitId::new(255)).is_some());
+        assert!(rtu.get(UnitId::new(1)).is_none());
+
+        let handler = tcp.get(UnitId::new(1)).unwrap();
+        let handler = handler.lock().unwrap();
+        let context = RequestContext {
+            unit_id: UnitId::new(1),
+            session: 0,
+            remote: None,
+            role: None,
+            tx_id: Some(1),
+        };
+        let range = AddressRange::try_from(0, 1).unwrap();
+        assert_eq!(
+            tokio_test::block_on(handler.read_coils(&context, range).get()),
+            Err(ExceptionCode::GatewayPathUnavailable)
+        );
+    }
+
+    #[cfg(feature = "serial")]
+    mod rtu {
+        use super::*;
+        use crate::client::message::{Command, Request};
+        use crate::common::frame::{FrameDestination, FrameHeader, FrameWriter, FramedReader};
+        use crate::common::function::FunctionCode;
+        use crate::common::phys::PhysLayer;
+        use crate::common::traits::{Loggable, Serialize};
+        use crate::decode::DecodeSettings;
+        use crate::server::task::{AuthorizationType, SessionTask};
+        use sfio_tokio_mock_io::Event;
+        use tokio::sync::mpsc::Receiver;
+
+        const TIMEOUT: Duration = Duration::from_secs(1);
+
+        fn device() -> (Channel, Receiver<Command>) {
+            let (tx, rx) = tokio::sync::mpsc::channel(16);
+            (Channel::new(tx), rx)
+        }
+
+        fn target(channel: &Channel, id: u8) -> GatewayTarget {
+            GatewayTarget::new(channel.clone(), UnitId::new(id), TIMEOUT)
+        }
+
+        /// run an RTU gateway session, returning a handle to feed it frames and observe its replies
+        fn spawn_session(map: GatewayMap) -> sfio_tokio_mock_io::Handle {
+            let (io, handle) = sfio_tokio_mock_io::mock();
+            let (tx, rx) = tokio::sync::mpsc::channel(1);
+            let mut session = SessionTask::new(
+                map.into_handlers(true).into(),
+                AuthorizationType::None,
+                FrameWriter::rtu(),
+                FramedReader::rtu_request(),
+                rx,
+                DecodeSettings::default(),
+            );
+            tokio::spawn(async move {
+                let _settings = tx;
+                session.run(&mut PhysLayer::new_mock(io)).await
+            });
+            handle
+        }
+
+        fn frame<T: Serialize + Loggable>(
+            destination: FrameDestination,
+            function: FunctionCode,
+            body: &T,
+        ) -> Vec<u8> {
+            FrameWriter::rtu()
+                .format_request(
+                    FrameHeader::new_rtu_header(destination),
+                    function,
+                    body,
+                    DecodeSettings::default(),
+                )
+                .unwrap()
+                .to_vec()
+        }
+
+        fn read_registers(id: u8) -> Vec<u8> {
+            frame(
+                FrameDestination::new_unit_id(id),
+                FunctionCode::ReadHoldingRegisters,
+                &AddressRange::try_from(0, 1).unwrap(),
+            )
+        }
+
+        async fn next_request(rx: &mut Receiver<Command>) -> Request {
+            match rx.recv().await {
+                Some(Command::Request(request)) => request,
+                _ => panic!("expected a request"),
+            }
+        }
+
+        /// the reply without its CRC
+        async fn next_reply(io: &mut sfio_tokio_mock_io::Handle) -> Vec<u8> {
+            loop {
+                if let Event::Write(mut bytes) = io.next_event().await {
+                    bytes.truncate(bytes.len() - 2);
+                    return bytes;
+                }
+            }
+        }
+
+        /// answer a forwarded read of a single holding register
+        async fn answer_read(rx: &mut Receiver<Command>, id: u8, value: u16) {
+            let mut request = next_request(rx).await;
+            assert_eq!(request.id, UnitId::new(id));
+            assert_eq!(
+                request.details.function(),
+                FunctionCode::ReadHoldingRegisters
+            );
+            let [hi, lo] = value.to_be_bytes();
+            request
+                .handle_response(&[0x03, 0x02, hi, lo], DecodeSettings::default())
+                .unwrap();
+        }
+
+        /// answer a forwarded write of 0xCAFE to holding register 7, returning the target unit id
+        async fn answer_write(rx: &mut Receiver<Command>) -> UnitId {
+            let mut request = next_request(rx).await;
+            assert_eq!(
+                request.details.function(),
+                FunctionCode::WriteSingleRegister
+            );
+            request
+                .handle_response(&[0x06, 0x00, 0x07, 0xCA, 0xFE], DecodeSettings::default())
+                .unwrap();
+            request.id
+        }
+
+        #[tokio::test]
+        async fn forwards_requests_to_the_mapped_target() {
+            let (channel, mut rx) = device();
+            let mut map = GatewayMap::new();
+            map.add(UnitId::new(1), target(&channel, 10));
+            let mut io = spawn_session(map);
+
+            io.read(&read_registers(1));
+            answer_read(&mut rx, 10, 0x1234).await;
+            assert_eq!(next_reply(&mut io).await, [0x01, 0x03, 0x02, 0x12, 0x34]);
+        }
+
+        #[tokio::test]
+        async fn requests_not_answered_in_time_fail_with_a_gateway_exception() {
+            let (channel, mut rx) = device();
+            let mut map = GatewayMap::new();
+            map.add(UnitId::new(1), target(&channel, 10));
+            let mut io = spawn_session(map);
+
+            io.read(&read_registers(1));
+            let request = next_request(&mut rx).await;
+            assert!(!request.details.is_cancelled());
+
+            // the request timeout of the server elapses before the target answers
+            tokio::time::pause();
+            assert_eq!(next_reply(&mut io).await, [0x01, 0x83, 0x0B]);
+            assert!(request.details.is_cancelled());
+        }
+
+        #[tokio::test]
+        async fn requests_for_unmapped_ids_are_ignored() {
+            let (channel, mut rx) = device();
+            let mut map = GatewayMap::new();
+            map.add(UnitId::new(1), target(&channel, 10));
+            let mut io = spawn_session(map);
+
+            // requests are processed in order, so the first reply is to the mapped request
+            io.read(&read_registers(2));
+            io.read(&read_registers(1));
+            answer_read(&mut rx, 10, 0x0001).await;
+            assert_eq!(next_reply(&mut io).await, [0x01, 0x03, 0x02, 0x00, 0x01]);
+            assert!(rx.try_recv().is_err());
+        }
+
+        #[tokio::test]
+        async fn broadcasts_are_forwarded_once_per_device() {
+            let (first, mut first_rx) = device();
+            let (second, mut second_rx) = device();
+            let mut map = GatewayMap::new();
+            map.add(UnitId::new(1), target(&first, 10));
+            map.add(UnitId::new(2), target(&first, 10));
+            map.add(UnitId::new(3), target(&first, 11));
+            map.add(UnitId::new(4), target(&second, 10));
+            let mut io = spawn_session(map);
+
+            io.read(&frame(
+                FrameDestination::Broadcast,
+                FunctionCode::WriteSingleRegister,
+                &Indexed::new(7, 0xCAFE),
+            ));
+
+            let targets = [
+                answer_write(&mut first_rx).await,
+                answer_write(&mut first_rx).await,
+                answer_write(&mut second_rx).await,
+            ];
+            assert_eq!(targets, [UnitId::new(10), UnitId::new(11), UnitId::new(10)]);
+
+            // no duplicate write precedes the next request and the broadcast is not answered
+            io.read(&read_registers(2));
+            answer_read(&mut first_rx, 10, 0x0002).await;
+            assert_eq!(next_reply(&mut io).await, [0x02, 0x03, 0x02, 0x00, 0x02]);
+            assert!(second_rx.try_recv().is_err());
+        }
+
+        #[tokio::test]
+        async fn broadcasts_do_not_delay_the_next_request() {
+            let (first, mut first_rx) = device();
+            let (second, mut second_rx) = device();
+            let mut map = GatewayMap::new();
+            map.add(UnitId::new(1), target(&first, 10));
+            map.add(UnitId::new(2), target(&second, 10));
+            let mut io = spawn_session(map);
+
+            io.read(&frame(
+                FrameDestination::Broadcast,
+                FunctionCode::WriteSingleRegister,
+                &Indexed::new(7, 0xCAFE),
+            ));
+
+            // neither target answers the broadcast while the next request is served
+            let _first_write = next_request(&mut first_rx).await;
+            let _second_write = next_request(&mut second_rx).await;
+            io.read(&read_registers(1));
+            answer_read(&mut first_rx, 10, 0x0001).await;
+            let reply = tokio::time::timeout(Duration::from_secs(1), next_reply(&mut io));
+            assert_eq!(reply.await.unwrap(), [0x01, 0x03, 0x02, 0x00, 0x01]);
+        }
+    }
+}
@@ -1,8 +1,12 @@
+use std::any::Any;
+use std::collections::btree_map::Entry;
 use std::collections::BTreeMap;
-use std::sync::{Arc, Mutex};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, RwLock};
 
 use crate::exception::ExceptionCode;
-use crate::server::{WriteCoils, WriteRegisters};
+use crate::maybe_async::MaybeAsync;
+use crate::server::{RequestContext, WriteCoils, WriteRegisters};
 use crate::types::*;
 
 /// Trait implemented by the user to process requests received from the client
@@ -13,6 +17,8 @@ use crate::types::*;
 ///
 /// If an implementation returns a slice smaller than the requested range, this will result
 /// in [`ExceptionCode::ServerDeviceFailure`] being returned to the client.
+///
+/// Handlers that need to await I/O should implement [`AsyncRequestHandler`] instead.
 pub trait RequestHandler: Send + 'static {
     /// Moves a server handler implementation into a `Arc<Mutex<Box<ServerHandler>>>`
     /// suitable for passing to the server
@@ -58,6 +64,54 @@ pub trait RequestHandler: Send + 'static {
         Err(ExceptionCode::IllegalFunction)
     }
 
+    /// Read a block of coils into `values`, which has the same length as `range`
+    ///
+    /// Override this to read the whole range at once. By default, [`Self::read_coil`]
+    /// is called for each address.
+    fn read_coils_into(
+        &self,
+        range: AddressRange,
+        values: &mut [bool],
+    ) -> Result<(), ExceptionCode> {
+        read_each(range, values, |i| self.read_coil(i))
+    }
+
+    /// Read a block of discrete inputs into `values`, which has the same length as `range`
+    ///
+    /// Override this to read the whole range at once. By default, [`Self::read_discrete_input`]
+    /// is called for each address.
+    fn read_discrete_inputs_into(
+        &self,
+        range: AddressRange,
+        values: &mut [bool],
+    ) -> Result<(), ExceptionCode> {
+        read_each(range, values, |i| self.read_discrete_input(i))
+    }
+
+    /// Read a block of holding registers into `values`, which has the same length as `range`
+    ///
+    /// Override this to read the whole range at once. By default, [`Self::read_holding_register`]
+    /// is called for each address.
+    fn read_holding_registers_into(
+        &self,
+        range: AddressRange,
+        values: &mut [u16],
+    ) -> Result<(), ExceptionCode> {
+        read_each(range, values, |i| self.read_holding_register(i))
+    }
+
+    /// Read a block of input registers into `values`, which has the same length as `range`
+    ///
+    /// Override this to read the whole range at once. By default, [`Self::read_input_register`]
+    /// is called for each address.
+    fn read_input_registers_into(
+        &self,
+        range: AddressRange,
+        values: &mut [u16],
+    ) -> Result<(), ExceptionCode> {
+        read_each(range, values, |i| self.read_input_register(i))
+    }
+
     /// Write a single coil value
     fn write_single_coil(&mut self, _value: Indexed<bool>) -> Result<(), ExceptionCode> {
         Err(ExceptionCode::IllegalFunction)
@@ -77,6 +131,345 @@ pub trait RequestHandler: Send + 'static {
     fn write_multiple_registers(&mut self, _values: WriteRegisters) -> Result<(), ExceptionCode> {
         Err(ExceptionCode::IllegalFunction)
     }
+
+    /// Context-aware variant of [`Self::read_coils_into`] called by the server
+    fn read_coils_with_context(
+        &self,
+        _context: &RequestContext,
+        range: AddressRange,
+        values: &mut [bool],
+    ) -> Result<(), ExceptionCode> {
+        self.read_coils_into(range, values)
+    }
+
+    /// Context-aware variant of [`Self::read_discrete_inputs_into`] called by the server
+    fn read_discrete_inputs_with_context(
+        &self,
+        _context: &RequestContext,
+        range: AddressRange,
+        values: &mut [bool],
+    ) -> Result<(), ExceptionCode> {
+        self.read_discrete_inputs_into(range, values)
+    }
+
+    /// Context-aware variant of [`Self::read_holding_registers_into`] called by the server
+    fn read_holding_registers_with_context(
+        &self,
+        _context: &RequestContext,
+        range: AddressRange,
+        values: &mut [u16],
+    ) -> Result<(), ExceptionCode> {
+        self.read_holding_registers_into(range, values)
+    }
+
+    /// Context-aware variant of [`Self::read_input_registers_into`] called by the server
+    fn read_input_registers_with_context(
+        &self,
+        _context: &RequestContext,
+        range: AddressRange,
+        values: &mut [u16],
+    ) -> Result<(), ExceptionCode> {
+        self.read_input_registers_into(range, values)
+    }
+
+    /// Context-aware variant of [`Self::read_basic_device_info`] called by the server
+    fn read_basic_device_info_with_context(
+        &self,
+        _context: &RequestContext,
+        obj_id: u8,
+    ) -> Result<DeviceIdentification, ExceptionCode> {
+        self.read_basic_device_info(obj_id)
+    }
+
+    /// Context-aware variant of [`Self::read_regular_device_info`] called by the server
+    fn read_regular_device_info_with_context(
+        &self,
+        _context: &RequestContext,
+        obj_id: u8,
+    ) -> Result<DeviceIdentification, ExceptionCode> {
+        self.read_regular_device_info(obj_id)
+    }
+
+    /// Context-aware variant of [`Self::read_extended_device_info`] called by the server
+    fn read_extended_device_info_with_context(
+        &self,
+        _context: &RequestContext,
+        obj_id: u8,
+    ) -> Result<DeviceIdentification, ExceptionCode> {
+        self.read_extended_device_info(obj_id)
+    }
+
+    /// Context-aware variant of [`Self::write_single_coil`] called by the server
+    fn write_single_coil_with_context(
+        &mut self,
+        _context: &RequestContext,
+        value: Indexed<bool>,
+    ) -> Result<(), ExceptionCode> {
+        self.write_single_coil(value)
+    }
+
+    /// Context-aware variant of [`Self::write_single_register`] called by the server
+    fn write_single_register_with_context(
+        &mut self,
+        _context: &RequestContext,
+        value: Indexed<u16>,
+    ) -> Result<(), ExceptionCode> {
+        self.write_single_register(value)
+    }
+
+    /// Context-aware variant of [`Self::write_multiple_coils`] called by the server
+    fn write_multiple_coils_with_context(
+        &mut self,
+        _context: &RequestContext,
+        values: WriteCoils,
+    ) -> Result<(), ExceptionCode> {
+        self.write_multiple_coils(values)
+    }
+
+    /// Context-aware variant of [`Self::write_multiple_registers`] called by the server
+    fn write_multiple_registers_with_context(
+        &mut self,
+        _context: &RequestContext,
+        values: WriteRegisters,
+    ) -> Result<(), ExceptionCode> {
+        self.write_multiple_registers(values)
+    }
+}
+
+/// Asynchronous variant of [`RequestHandler`] for handlers that need to await I/O
+///
+/// Each method is called with the handler locked and returns a [`MaybeAsync`] that is awaited
+/// after the lock is released, so the returned futures must own the data they need. If the value
+/// is not available before the request timeout of the server, the exception returned by
+/// [`AsyncRequestHandler::timeout_exception`] is sent to the client.
+///
+/// Each method receives the [`RequestContext`] of the request. Every [`RequestHandler`] is also
+/// an `AsyncRequestHandler` whose results are always ready.
+pub trait AsyncRequestHandler: Send + 'static {
+    /// Read a range of coils
+    fn read_coils(
+        &self,
+        _context: &RequestContext,
+        _range: AddressRange,
+    ) -> MaybeAsync<Result<Vec<bool>, ExceptionCode>> {
+        MaybeAsync::ready(Err(ExceptionCode::IllegalFunction))
+    }
+
+    /// Read a range of discrete inputs
+    fn read_discrete_inputs(
+        &self,
+        _context: &RequestContext,
+        _range: AddressRange,
+    ) -> MaybeAsync<Result<Vec<bool>, ExceptionCode>> {
+        MaybeAsync::ready(Err(ExceptionCode::IllegalFunction))
+    }
+
+    /// Read a range of holding registers
+    fn read_holding_registers(
+        &self,
+        _context: &RequestContext,
+        _range: AddressRange,
+    ) -> MaybeAsync<Result<Vec<u16>, ExceptionCode>> {
+        MaybeAsync::ready(Err(ExceptionCode::IllegalFunction))
+    }
+
+    /// Read a range of input registers
+    fn read_input_registers(
+        &self,
+        _context: &RequestContext,
+        _range: AddressRange,
+    ) -> MaybeAsync<Result<Vec<u16>, ExceptionCode>> {
+        MaybeAsync::ready(Err(ExceptionCode::IllegalFunction))
+    }
+
+    /// Read basic device identification objects
+    fn read_basic_device_identification(
+        &self,
+        _context: &RequestContext,
+        _obj_id: u8,
+    ) -> MaybeAsync<Result<DeviceIdentification, ExceptionCode>> {
+        MaybeAsync::ready(Err(ExceptionCode::IllegalFunction))
+    }
+
+    /// Read regular device identification objects
+    fn read_regular_device_identification(
+        &self,
+        _context: &RequestContext,
+        _obj_id: u8,
+    ) -> MaybeAsync<Result<DeviceIdentification, ExceptionCode>> {
+        MaybeAsync::ready(Err(ExceptionCode::IllegalFunction))
+    }
+
+    /// Read extended device identification objects
+    fn read_extended_device_identification(
+        &self,
+        _context: &RequestContext,
+        _obj_id: u8,
+    ) -> MaybeAsync<Result<DeviceIdentification, ExceptionCode>> {
+        MaybeAsync::ready(Err(ExceptionCode::IllegalFunction))
+    }
+
+    /// Write a single coil
+    fn write_coil(
+        &mut self,
+        _context: &RequestContext,
+        _value: Indexed<bool>,
+    ) -> MaybeAsync<Result<(), ExceptionCode>> {
+        MaybeAsync::ready(Err(ExceptionCode::IllegalFunction))
+    }
+
+    /// Write a single register
+    fn write_register(
+        &mut self,
+        _context: &RequestContext,
+        _value: Indexed<u16>,
+    ) -> MaybeAsync<Result<(), ExceptionCode>> {
+        MaybeAsync::ready(Err(ExceptionCode::IllegalFunction))
+    }
+
+    /// Write multiple coils
+    fn write_coils(
+        &mut self,
+        _context: &RequestContext,
+        _values: WriteCoils,
+    ) -> MaybeAsync<Result<(), ExceptionCode>> {
+        MaybeAsync::ready(Err(ExceptionCode::IllegalFunction))
+    }
+
+    /// Write multiple registers
+    fn write_registers(
+        &mut self,
+        _context: &RequestContext,
+        _values: WriteRegisters,
+    ) -> MaybeAsync<Result<(), ExceptionCode>> {
+        MaybeAsync::ready(Err(ExceptionCode::IllegalFunction))
+    }
+
+    /// Exception returned to the client when a result is not available before the request
+    /// timeout of the server, [`ExceptionCode::ServerDeviceFailure`] by default
+    fn timeout_exception(&self) -> ExceptionCode {
+        ExceptionCode::ServerDeviceFailure
+    }
+}
+
+fn read_each<T>(
+    range: AddressRange,
+    values: &mut [T],
+    read: impl Fn(u16) -> Result<T, ExceptionCode>,
+) -> Result<(), ExceptionCode> {
+    for (value, address) in values.iter_mut().zip(range.iter()) {
+        *value = read(address)?;
+    }
+    Ok(())
+}
+
+fn read_block<T: Copy + Default>(
+    range: AddressRange,
+    read: impl FnOnce(&mut [T]) -> Result<(), ExceptionCode>,
+) -> MaybeAsync<Result<Vec<T>, ExceptionCode>> {
+    let mut values = vec![T::default(); range.count as usize];
+    MaybeAsync::ready(read(&mut values).map(|_| values))
+}
+
+impl<T> AsyncRequestHandler for T
+where
+    T: RequestHandler,
+{
+    fn read_coils(
+        &self,
+        context: &RequestContext,
+        range: AddressRange,
+    ) -> MaybeAsync<Result<Vec<bool>, ExceptionCode>> {
+        read_block(range, |values| {
+            self.read_coils_with_context(context, range, values)
+        })
+    }
+
+    fn read_discrete_inputs(
+        &self,
+        context: &RequestContext,
+        range: AddressRange,
+    ) -> MaybeAsync<Result<Vec<bool>, ExceptionCode>> {
+        read_block(range, |values| {
+            self.read_discrete_inputs_with_context(context, range, values)
+        })
+    }
+
+    fn read_holding_registers(
+        &self,
+        context: &RequestContext,
+        range: AddressRange,
+    ) -> MaybeAsync<Result<Vec<u16>, ExceptionCode>> {
+        read_block(range, |values| {
+            self.read_holding_registers_with_context(context, range, values)
+        })
+    }
+
+    fn read_input_registers(
+        &self,
+        context: &RequestContext,
+        range: AddressRange,
+    ) -> MaybeAsync<Result<Vec<u16>, ExceptionCode>> {
+        read_block(range, |values| {
+            self.read_input_registers_with_context(context, range, values)
+        })
+    }
+
+    fn read_basic_device_identification(
+        &self,
+        context: &RequestContext,
+        obj_id: u8,
+    ) -> MaybeAsync<Result<DeviceIdentification, ExceptionCode>> {
+        MaybeAsync::ready(self.read_basic_device_info_with_context(context, obj_id))
+    }
+
+    fn read_regular_device_identification(
+        &self,
+        context: &RequestContext,
+        obj_id: u8,
+    ) -> MaybeAsync<Result<DeviceIdentification, ExceptionCode>> {
+        MaybeAsync::ready(self.read_regular_device_info_with_context(context, obj_id))
+    }
+
+    fn read_extended_device_identification(
+        &self,
+        context: &RequestContext,
+        obj_id: u8,
+    ) -> MaybeAsync<Result<DeviceIdentification, ExceptionCode>> {
+        MaybeAsync::ready(self.read_extended_device_info_with_context(context, obj_id))
+    }
+
+    fn write_coil(
+        &mut self,
+        context: &RequestContext,
+        value: Indexed<bool>,
+    ) -> MaybeAsync<Result<(), ExceptionCode>> {
+        MaybeAsync::ready(self.write_single_coil_with_context(context, value))
+    }
+
+    fn write_register(
+        &mut self,
+        context: &RequestContext,
+        value: Indexed<u16>,
+    ) -> MaybeAsync<Result<(), ExceptionCode>> {
+        MaybeAsync::ready(self.write_single_register_with_context(context, value))
+    }
+
+    fn write_coils(
+        &mut self,
+        context: &RequestContext,
+        values: WriteCoils,
+    ) -> MaybeAsync<Result<(), ExceptionCode>> {
+        MaybeAsync::ready(self.write_multiple_coils_with_context(context, values))
+    }
+
+    fn write_registers(
+        &mut self,
+        context: &RequestContext,
+        values: WriteRegisters,
+    ) -> MaybeAsync<Result<(), ExceptionCode>> {
+        MaybeAsync::ready(self.write_multiple_registers_with_context(context, values))
+    }
 }
 
 /// Trait useful for converting None into IllegalDataAddress
@@ -101,9 +494,9 @@ where
 pub type ServerHandlerType<T> = Arc<Mutex<Box<T>>>;
 
 /// Type that hides the underlying map implementation
-/// and allows lookups of a [`RequestHandler`] from a [`UnitId`]
+/// and allows lookups of a [`RequestHandler`] or [`AsyncRequestHandler`] from a [`UnitId`]
 #[derive(Debug, Default)]
-pub struct ServerHandlerMap<T: RequestHandler> {
+pub struct ServerHandlerMap<T: AsyncRequestHandler> {
     handlers: BTreeMap<UnitId, ServerHandlerType<T>>,
 }
 
@@ -111,7 +504,7 @@ pub struct ServerHandlerMap<T: RequestHandler> {
 // due to the generic typing....
 impl<T> Clone for ServerHandlerMap<T>
 where
-    T: RequestHandler,
+    T: AsyncRequestHandler,
 {
     fn clone(&self) -> Self {
         ServerHandlerMap {
@@ -122,7 +515,7 @@ where
 
 impl<T> ServerHandlerMap<T>
 where
-    T: RequestHandler,
+    T: AsyncRequestHandler,
 {
     /// Create an empty map
     pub fn new() -> Self {
@@ -151,9 +544,128 @@ where
     ) -> Option<ServerHandlerType<T>> {
         self.handlers.insert(id, server)
     }
+}
+
+/// Handlers of a running server, shared between the [`ServerHandle`](crate::server::ServerHandle),
+/// the server task and the sessions so that units can be changed while sessions are active
+pub(crate) struct SharedHandlerMap<T: AsyncRequestHandler> {
+    handlers: Arc<RwLock<BTreeMap<UnitId, ServerHandlerType<T>>>>,
+}
+
+impl<T> Clone for SharedHandlerMap<T>
+where
+    T: AsyncRequestHandler,
+{
+    fn clone(&self) -> Self {
+        Self {
+            handlers: self.handlers.clone(),
+        }
+    }
+}
 
-    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut ServerHandlerType<T>> {
-        self.handlers.values_mut()
+impl<T> From<ServerHandlerMap<T>> for SharedHandlerMap<T>
+where
+    T: AsyncRequestHandler,
+{
+    fn from(map: ServerHandlerMap<T>) -> Self {
+        Self {
+            handlers: Arc::new(RwLock::new(map.handlers)),
+        }
+    }
+}
+
+impl<T> SharedHandlerMap<T>
+where
+    T: AsyncRequestHandler,
+{
+    pub(crate) fn get(&self, id: UnitId) -> Option<ServerHandlerType<T>> {
+        self.handlers.read().unwrap().get(&id).cloned()
+    }
+
+    pub(crate) fn try_insert(
+        &self,
+        id: UnitId,
+        handler: ServerHandlerType<T>,
+    ) -> Result<(), UnitError> {
+        match self.handlers.write().unwrap().entry(id) {
+            Entry::Occupied(_) => Err(UnitError::AlreadyExists(id)),
+            Entry::Vacant(entry) => {
+                entry.insert(handler);
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) fn replace(
+        &self,
+        id: UnitId,
+        handler: ServerHandlerType<T>,
+    ) -> Result<ServerHandlerType<T>, UnitError> {
+        match self.handlers.write().unwrap().get_mut(&id) {
+            None => Err(UnitError::NotFound(id)),
+            Some(current) => Ok(std::mem::replace(current, handler)),
+        }
+    }
+
+    /// snapshot of all the handlers, used to execute broadcast requests
+    pub(crate) fn all(&self) -> Vec<ServerHandlerType<T>> {
+        self.handlers.read().unwrap().values().cloned().collect()
+    }
+}
+
+/// Type-erased view of the [`SharedHandlerMap`] held by a [`ServerHandle`](crate::server::ServerHandle)
+pub(crate) trait UnitMap: Send + Sync + 'static {
+    fn remove(&self, id: UnitId) -> Result<(), UnitError>;
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl std::fmt::Debug for dyn UnitMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("UnitMap")
+    }
+}
+
+impl<T> UnitMap for SharedHandlerMap<T>
+where
+    T: AsyncRequestHandler,
+{
+    fn remove(&self, id: UnitId) -> Result<(), UnitError> {
+        match self.handlers.write().unwrap().remove(&id) {
+            None => Err(UnitError::NotFound(id)),
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Error returned when the units of a running server cannot be changed
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UnitError {
+    /// A handler is already associated with the unit id
+    AlreadyExists(UnitId),
+    /// No handler is associated with the unit id
+    NotFound(UnitId),
+    /// The handler type does not match the type of the handlers the server was spawned with
+    WrongHandlerType,
+    /// The handle was not returned by one of the spawn functions and doesn't have access to the units
+    Unavailable,
+}
+
+impl std::error::Error for UnitError {}
+
+impl std::fmt::Display for UnitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            UnitError::AlreadyExists(id) => write!(f, "a handler already exists for unit {id}"),
+            UnitError::NotFound(id) => write!(f, "no handler exists for unit {id}"),
+            UnitError::WrongHandlerType => {
+                f.write_str("handler type does not match the handlers of the server")
+            }
+            UnitError::Unavailable => f.write_str("the units of the server are not accessible"),
+        }
     }
 }
 
@@ -247,6 +759,23 @@ pub trait AuthorizationHandler: Send + Sync + 'static {
     }
 }
 
+/// Determines the role of clients connecting to a plain TCP server that authorizes requests
+///
+/// Implemented for any `Fn(IpAddr) -> Option<String>` closure.
+pub trait RoleProvider: Send + Sync + 'static {
+    /// Role of a client connecting from `addr`, `None` to refuse the connection
+    fn role(&self, addr: IpAddr) -> Option<String>;
+}
+
+impl<F> RoleProvider for F
+where
+    F: Fn(IpAddr) -> Option<String> + Send + Sync + 'static,
+{
+    fn role(&self, addr: IpAddr) -> Option<String> {
+        self(addr)
+    }
+}
+
 /// Read-only authorization handler that blindly accepts
 /// all read requests.
 #[derive(Debug, Clone, Copy)]
@@ -365,4 +894,101 @@ mod tests {
         assert!(map.add(UnitId::new(2), DefaultHandler {}.wrap()).is_none());
         assert!(map.add(UnitId::new(1), DefaultHandler {}.wrap()).is_some());
     }
+
+    #[test]
+    fn server_handler_map_clones_are_independent() {
+        let mut map = ServerHandlerMap::new();
+        let mut clone = map.clone();
+        map.add(UnitId::new(1), DefaultHandler {}.wrap());
+        assert!(map.get(UnitId::new(1)).is_some());
+        assert!(clone.get(UnitId::new(1)).is_none());
+    }
+
+    #[test]
+    fn shared_handler_map_clones_share_the_handlers() {
+        let map = SharedHandlerMap::from(ServerHandlerMap::new());
+        let clone = map.clone();
+
+        assert!(map
+            .try_insert(UnitId::new(1), DefaultHandler {}.wrap())
+            .is_ok());
+        assert!(clone.get(UnitId::new(1)).is_some());
+        assert_eq!(
+            clone.try_insert(UnitId::new(1), DefaultHandler {}.wrap()),
+            Err(UnitError::AlreadyExists(UnitId::new(1)))
+        );
+
+        let replacement = DefaultHandler {}.wrap();
+        assert!(clone.replace(UnitId::new(1), replacement.clone()).is_ok());
+        assert!(Arc::ptr_eq(&map.get(UnitId::new(1)).unwrap(), &replacement));
+        assert!(matches!(
+            map.replace(UnitId::new(2), DefaultHandler {}.wrap()),
+            Err(UnitError::NotFound(_))
+        ));
+
+        assert_eq!(UnitMap::remove(&clone, UnitId::new(1)), Ok(()));
+        assert!(map.get(UnitId::new(1)).is_none());
+        assert!(map.all().is_empty());
+    }
+
+    struct PerAddressHandler;
+    impl RequestHandler for PerAddressHandler {
+        fn read_holding_register(&self, address: u16) -> Result<u16, ExceptionCode> {
+            match address {
+                0..=9 => Ok(address * 2),
+                _ => Err(ExceptionCode::IllegalDataAddress),
+            }
+        }
+    }
+
+    struct BulkHandler;
+    impl RequestHandler for BulkHandler {
+        fn read_holding_registers_into(
+            &self,
+            range: AddressRange,
+            values: &mut [u16],
+        ) -> Result<(), ExceptionCode> {
+            values.fill(range.start);
+            Ok(())
+        }
+    }
+
+    fn read_holding_registers<T: AsyncRequestHandler>(
+        handler: &T,
+        start: u16,
+        count: u16,
+    ) -> Result<Vec<u16>, ExceptionCode> {
+        let context = RequestContext {
+            unit_id: UnitId::new(1),
+            session: 0,
+            remote: None,
+            role: None,
+            tx_id: None,
+        };
+        let range = AddressRange::try_from(start, count).unwrap();
+        tokio_test::block_on(
+            AsyncRequestHandler::read_holding_registers(handler, &context, range).get(),
+        )
+    }
+
+    #[test]
+    fn bulk_read_defaults_to_per_address_reads() {
+        assert_eq!(
+            read_holding_registers(&PerAddressHandler, 2, 3),
+            Ok(vec![4, 6, 8])
+        );
+        assert_eq!(
+            read_holding_registers(&PerAddressHandler, 8, 3),
+            Err(ExceptionCode::IllegalDataAddress)
+        );
+    }
+
+    #[test]
+    fn bulk_read_uses_overridden_hook() {
+        assert_eq!(read_holding_registers(&BulkHandler, 5, 2), Ok(vec![5, 5]));
+        assert_eq!(
+            read_holding_registers(&DefaultHandler, 5, 2),
+            Err(ExceptionCode::IllegalFunction)
+        );
+    }
 }
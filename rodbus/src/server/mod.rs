@@ -1,26 +1,43 @@
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 
 use tracing::Instrument;
 
-use crate::decode::DecodeLevel;
+use crate::decode::{DecodeFormat, DecodeLevel};
+use crate::observer::TrafficObserver;
+use crate::server::handler::{SharedHandlerMap, UnitMap};
+use crate::server::session::Sessions;
+#[cfg(feature = "serial")]
+use crate::server::task::AuthorizationType;
 use crate::server::task::ServerSetting;
-use crate::tcp::server::{ServerTask, TcpServerConnectionHandler};
+use crate::tcp::server::{ServerControl, ServerTask, TcpServerConnectionHandler};
+use crate::types::UnitId;
 
 /// server handling
 mod address_filter;
+pub(crate) mod database;
+pub(crate) mod gateway;
 pub(crate) mod handler;
 pub(crate) mod request;
 pub(crate) mod response;
+pub(crate) mod session;
 pub(crate) mod task;
 pub(crate) mod types;
 
 /// Fine for this to be a constant since the corresponding channel is only used to change settings
 pub(crate) const SERVER_SETTING_CHANNEL_CAPACITY: usize = 8;
 
+/// Time allowed for a handler to produce a result before `ServerDeviceFailure` is returned
+pub(crate) const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
 use crate::error::Shutdown;
 
 pub use address_filter::*;
+pub use database::*;
+pub use gateway::*;
 pub use handler::*;
+pub use session::*;
 pub use types::*;
 
 // re-export to the public API
@@ -33,6 +50,9 @@ pub use crate::tcp::tls::*;
 #[derive(Debug)]
 pub struct ServerHandle {
     tx: tokio::sync::mpsc::Sender<ServerSetting>,
+    units: Option<Arc<dyn UnitMap>>,
+    sessions: Sessions,
+    control: Option<tokio::sync::mpsc::Sender<ServerControl>>,
 }
 
 impl ServerHandle {
@@ -40,7 +60,73 @@ impl ServerHandle {
     ///
     /// This function is only required for the C bindings
     pub fn new(tx: tokio::sync::mpsc::Sender<ServerSetting>) -> Self {
-        ServerHandle { tx }
+        ServerHandle {
+            tx,
+            units: None,
+            sessions: Sessions::default(),
+            control: None,
+        }
+    }
+
+    pub(crate) fn with_units<T: AsyncRequestHandler>(
+        mut self,
+        handlers: SharedHandlerMap<T>,
+    ) -> Self {
+        self.units = Some(Arc::new(handlers));
+        self
+    }
+
+    pub(crate) fn with_sessions(mut self, sessions: Sessions) -> Self {
+        self.sessions = sessions;
+        self
+    }
+
+    pub(crate) fn with_control(
+        mut self,
+        control: tokio::sync::mpsc::Sender<ServerControl>,
+    ) -> Self {
+        self.control = Some(control);
+        self
+    }
+
+    fn handlers<T: AsyncRequestHandler>(&self) -> Result<&SharedHandlerMap<T>, UnitError> {
+        self.units
+            .as_ref()
+            .ok_or(UnitError::Unavailable)?
+            .as_any()
+            .downcast_ref()
+            .ok_or(UnitError::WrongHandlerType)
+    }
+
+    /// Add a handler for a unit id that isn't currently served
+    ///
+    /// Future sessions and all active sessions start answering requests for the unit immediately.
+    /// `T` must be the handler type the server was spawned with.
+    pub fn add_unit<T: AsyncRequestHandler>(
+        &self,
+        id: UnitId,
+        handler: ServerHandlerType<T>,
+    ) -> Result<(), UnitError> {
+        self.handlers()?.try_insert(id, handler)
+    }
+
+    /// Replace the handler of a unit id that is currently served, returning the previous handler
+    ///
+    /// Requests that are already executing complete using the previous handler.
+    pub fn replace_unit<T: AsyncRequestHandler>(
+        &self,
+        id: UnitId,
+        handler: ServerHandlerType<T>,
+    ) -> Result<ServerHandlerType<T>, UnitError> {
+        self.handlers()?.replace(id, handler)
+    }
+
+    /// Stop serving a unit id in future sessions and all active sessions
+    pub fn remove_unit(&self, id: UnitId) -> Result<(), UnitError> {
+        self.units
+            .as_ref()
+            .ok_or(UnitError::Unavailable)?
+            .remove(id)
     }
 
     /// Change the decoding level for future sessions and all active sessions
@@ -48,6 +134,81 @@ impl ServerHandle {
         self.tx.send(ServerSetting::ChangeDecoding(level)).await?;
         Ok(())
     }
+
+    /// Change the format of the decoded data for future sessions and all active sessions
+    pub async fn set_decode_format(&mut self, format: DecodeFormat) -> Result<(), Shutdown> {
+        self.tx
+            .send(ServerSetting::ChangeDecodeFormat(format))
+            .await?;
+        Ok(())
+    }
+
+    /// Set or clear the observer that receives the traffic of future sessions and all active sessions
+    pub async fn set_traffic_observer(
+        &mut self,
+        observer: Option<Arc<dyn TrafficObserver>>,
+    ) -> Result<(), Shutdown> {
+        self.tx
+            .send(ServerSetting::ChangeObserver(observer))
+            .await?;
+        Ok(())
+    }
+
+    /// Change the time that future sessions and all active sessions allow an
+    /// [`AsyncRequestHandler`] to complete a request, 5 seconds by default
+    ///
+    /// Requests that do not complete in time are answered with
+    /// [`ExceptionCode::ServerDeviceFailure`](crate::ExceptionCode::ServerDeviceFailure).
+    pub async fn set_request_timeout(&mut self, timeout: Duration) -> Result<(), Shutdown> {
+        self.tx
+            .send(ServerSetting::ChangeRequestTimeout(timeout))
+            .await?;
+        Ok(())
+    }
+
+    /// Change the filter that controls which addresses may connect to a TCP or TLS server
+    ///
+    /// If `close_mismatched` is true, active sessions whose remote address does not match the new filter
+    /// are closed. Otherwise, the filter only applies to future connections.
+    ///
+    /// This has no effect on an RTU server.
+    pub async fn set_address_filter(
+        &mut self,
+        filter: AddressFilter,
+        close_mismatched: bool,
+    ) -> Result<(), Shutdown> {
+        if let Some(control) = &self.control {
+            control
+                .send(ServerControl::ChangeAddressFilter(filter, close_mismatched))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// List the established sessions of a TCP or TLS server
+    ///
+    /// Sessions appear once the TLS handshake completes. RTU servers do not report sessions.
+    pub fn sessions(&self) -> Vec<SessionInfo> {
+        self.sessions.get()
+    }
+
+    /// Close a session of a TCP or TLS server, returning `false` if the session is not established
+    pub async fn disconnect(&mut self, id: u64) -> Result<bool, Shutdown> {
+        let control = match &self.control {
+            Some(control) if self.sessions.contains(id) => control,
+            _ => return Ok(false),
+        };
+        control.send(ServerControl::Disconnect(id)).await?;
+        Ok(true)
+    }
+
+    /// Set or clear the listener notified when sessions connect to or disconnect from a TCP or TLS server
+    ///
+    /// Sessions that are already established are not reported to the listener when it is set, but
+    /// their disconnection is.
+    pub fn set_session_listener(&self, listener: Option<Arc<dyn SessionListener>>) {
+        self.sessions.set_listener(listener);
+    }
 }
 
 /// Spawns a TCP server task onto the runtime. This method can only
@@ -62,34 +223,95 @@ impl ServerHandle {
 /// * `decode` - Decode log level
 ///
 /// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
-pub async fn spawn_tcp_server_task<T: RequestHandler>(
+pub async fn spawn_tcp_server_task<T: AsyncRequestHandler>(
+    max_sessions: usize,
+    addr: SocketAddr,
+    handlers: ServerHandlerMap<T>,
+    filter: AddressFilter,
+    decode: DecodeLevel,
+) -> Result<ServerHandle, std::io::Error> {
+    spawn_tcp_server_task_impl(
+        max_sessions,
+        addr,
+        handlers,
+        TcpServerConnectionHandler::Tcp,
+        filter,
+        decode,
+    )
+    .await
+}
+
+/// Spawns a TCP server task onto the runtime that checks the authorization of requests against
+/// the supplied handler. The role of each client is determined from its IP address when it connects.
+///
+/// Each incoming connection will spawn a new task to handle it.
+///
+/// * `max_sessions` - Maximum number of concurrent sessions
+/// * `addr` - A socket address to bound to
+/// * `handlers` - A map of handlers keyed by a unit id
+/// * `auth_handler` - Handler used to authorize requests
+/// * `roles` - Provides the role of a client from its IP address, connections without a role are closed
+/// * `filter` - Address filter which may be used to restrict the connecting IP address
+/// * `decode` - Decode log level
+///
+/// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+pub async fn spawn_tcp_server_task_with_authz<T: AsyncRequestHandler>(
+    max_sessions: usize,
+    addr: SocketAddr,
+    handlers: ServerHandlerMap<T>,
+    auth_handler: Arc<dyn AuthorizationHandler>,
+    roles: Arc<dyn RoleProvider>,
+    filter: AddressFilter,
+    decode: DecodeLevel,
+) -> Result<ServerHandle, std::io::Error> {
+    spawn_tcp_server_task_impl(
+        max_sessions,
+        addr,
+        handlers,
+        TcpServerConnectionHandler::TcpWithAuthz(auth_handler, roles),
+        filter,
+        decode,
+    )
+    .await
+}
+
+async fn spawn_tcp_server_task_impl<T: AsyncRequestHandler>(
     max_sessions: usize,
     addr: SocketAddr,
     handlers: ServerHandlerMap<T>,
+    connection_handler: TcpServerConnectionHandler,
     filter: AddressFilter,
     decode: DecodeLevel,
 ) -> Result<ServerHandle, std::io::Error> {
     let listener = tokio::net::TcpListener::bind(addr).await?;
 
+    let handlers = SharedHandlerMap::from(handlers);
     let (tx, rx) = tokio::sync::mpsc::channel(SERVER_SETTING_CHANNEL_CAPACITY);
+    let (control_tx, control_rx) = tokio::sync::mpsc::channel(SERVER_SETTING_CHANNEL_CAPACITY);
+    let sessions = Sessions::default();
+    let handle = ServerHandle::new(tx)
+        .with_units(handlers.clone())
+        .with_sessions(sessions.clone())
+        .with_control(control_tx);
 
     let task = async move {
         ServerTask::new(
             max_sessions,
             listener,
             handlers,
-            TcpServerConnectionHandler::Tcp,
+            connection_handler,
             filter,
             decode,
+            sessions,
         )
-        .run(rx)
+        .run(rx, control_rx)
         .instrument(tracing::info_span!("Modbus-Server-TCP", "listen" = ?addr))
         .await;
     };
 
     tokio::spawn(task);
 
-    Ok(ServerHandle::new(tx))
+    Ok(handle)
 }
 
 /// Spawns a RTU server task onto the runtime.
@@ -102,21 +324,74 @@ pub async fn spawn_tcp_server_task<T: RequestHandler>(
 ///
 /// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
 #[cfg(feature = "serial")]
-pub fn spawn_rtu_server_task<T: RequestHandler>(
+pub fn spawn_rtu_server_task<T: AsyncRequestHandler>(
+    path: &str,
+    settings: crate::serial::SerialSettings,
+    retry: Box<dyn crate::retry::RetryStrategy>,
+    handlers: ServerHandlerMap<T>,
+    decode: DecodeLevel,
+) -> Result<ServerHandle, std::io::Error> {
+    spawn_rtu_server_task_impl(
+        path,
+        settings,
+        retry,
+        handlers,
+        AuthorizationType::None,
+        decode,
+    )
+}
+
+/// Spawns a RTU server task onto the runtime that checks the authorization of requests against
+/// the supplied handler. All the requests received on the port are authorized using the same role.
+///
+/// * `path` - Path to the serial device. Generally `/dev/tty0` on Linux and `COM1` on Windows.
+/// * `settings` - Serial port settings
+/// * `retry` - A boxed trait object that controls when opening the serial port is retried after a failure
+/// * `handlers` - A map of handlers keyed by a unit id
+/// * `auth_handler` - Handler used to authorize requests
+/// * `role` - Role of the requests received on the port
+/// * `decode` - Decode log level
+///
+/// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+#[cfg(feature = "serial")]
+pub fn spawn_rtu_server_task_with_authz<T: AsyncRequestHandler>(
     path: &str,
     settings: crate::serial::SerialSettings,
     retry: Box<dyn crate::retry::RetryStrategy>,
     handlers: ServerHandlerMap<T>,
+    auth_handler: Arc<dyn AuthorizationHandler>,
+    role: String,
     decode: DecodeLevel,
 ) -> Result<ServerHandle, std::io::Error> {
+    spawn_rtu_server_task_impl(
+        path,
+        settings,
+        retry,
+        handlers,
+        AuthorizationType::Handler(auth_handler, role.into()),
+        decode,
+    )
+}
+
+#[cfg(feature = "serial")]
+fn spawn_rtu_server_task_impl<T: AsyncRequestHandler>(
+    path: &str,
+    settings: crate::serial::SerialSettings,
+    retry: Box<dyn crate::retry::RetryStrategy>,
+    handlers: ServerHandlerMap<T>,
+    auth: AuthorizationType,
+    decode: DecodeLevel,
+) -> Result<ServerHandle, std::io::Error> {
+    let handlers = SharedHandlerMap::from(handlers);
     let (tx, rx) = tokio::sync::mpsc::channel(SERVER_SETTING_CHANNEL_CAPACITY);
+    let handle = ServerHandle::new(tx).with_units(handlers.clone());
     let session = crate::server::task::SessionTask::new(
         handlers,
-        crate::server::task::AuthorizationType::None,
+        auth,
         crate::common::frame::FrameWriter::rtu(),
         crate::common::frame::FramedReader::rtu_request(),
         rx,
-        decode,
+        decode.into(),
     );
 
     let mut rtu = crate::serial::server::RtuServerTask {
@@ -136,7 +411,7 @@ pub fn spawn_rtu_server_task<T: RequestHandler>(
 
     tokio::spawn(task);
 
-    Ok(ServerHandle::new(tx))
+    Ok(handle)
 }
 
 /// Spawns a "raw" TLS server task onto the runtime. This TLS server does NOT require that
@@ -154,7 +429,7 @@ pub fn spawn_rtu_server_task<T: RequestHandler>(
 ///
 /// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
 #[cfg(feature = "tls")]
-pub async fn spawn_tls_server_task<T: RequestHandler>(
+pub async fn spawn_tls_server_task<T: AsyncRequestHandler>(
     max_sessions: usize,
     addr: SocketAddr,
     handlers: ServerHandlerMap<T>,
@@ -191,11 +466,11 @@ pub async fn spawn_tls_server_task<T: RequestHandler>(
 ///
 /// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
 #[cfg(feature = "tls")]
-pub async fn spawn_tls_server_task_with_authz<T: RequestHandler>(
+pub async fn spawn_tls_server_task_with_authz<T: AsyncRequestHandler>(
     max_sessions: usize,
     addr: SocketAddr,
     handlers: ServerHandlerMap<T>,
-    auth_handler: std::sync::Arc<dyn AuthorizationHandler>,
+    auth_handler: Arc<dyn AuthorizationHandler>,
     tls_config: TlsServerConfig,
     filter: AddressFilter,
     decode: DecodeLevel,
@@ -213,18 +488,25 @@ pub async fn spawn_tls_server_task_with_authz<T: RequestHandler>(
 }
 
 #[cfg(feature = "tls")]
-async fn spawn_tls_server_task_impl<T: RequestHandler>(
+async fn spawn_tls_server_task_impl<T: AsyncRequestHandler>(
     max_sessions: usize,
     addr: SocketAddr,
     handlers: ServerHandlerMap<T>,
-    auth_handler: Option<std::sync::Arc<dyn AuthorizationHandler>>,
+    auth_handler: Option<Arc<dyn AuthorizationHandler>>,
     tls_config: TlsServerConfig,
     filter: AddressFilter,
     decode: DecodeLevel,
 ) -> Result<ServerHandle, std::io::Error> {
     let listener = tokio::net::TcpListener::bind(addr).await?;
 
+    let handlers = SharedHandlerMap::from(handlers);
     let (tx, rx) = tokio::sync::mpsc::channel(SERVER_SETTING_CHANNEL_CAPACITY);
+    let (control_tx, control_rx) = tokio::sync::mpsc::channel(SERVER_SETTING_CHANNEL_CAPACITY);
+    let sessions = Sessions::default();
+    let handle = ServerHandle::new(tx)
+        .with_units(handlers.clone())
+        .with_sessions(sessions.clone())
+        .with_control(control_tx);
 
     let task = async move {
         ServerTask::new(
@@ -234,13 +516,14 @@ async fn spawn_tls_server_task_impl<T: RequestHandler>(
             TcpServerConnectionHandler::Tls(tls_config, auth_handler),
             filter,
             decode,
+            sessions,
         )
-        .run(rx)
+        .run(rx, control_rx)
         .instrument(tracing::info_span!("Modbus-Server-TLS", "listen" = ?addr))
         .await
     };
 
     tokio::spawn(task);
 
-    Ok(ServerHandle::new(tx))
+    Ok(handle)
 }
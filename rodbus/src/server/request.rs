@@ -2,15 +2,17 @@ use crate::client::requests::read_device_identification::ReadDeviceIdentificatio
 use crate::common::frame::{FrameHeader, FrameWriter, FunctionField};
 use crate::common::function::FunctionCode;
 use crate::common::traits::{Loggable, Parse, Serialize};
-use crate::decode::AppDecodeLevel;
+use crate::decode::{AppDecodeLevel, DecodeSettings};
 use crate::error::RequestError;
 use crate::exception::ExceptionCode;
-use crate::server::handler::RequestHandler;
+use crate::maybe_async::MaybeAsync;
+use crate::server::handler::{AsyncRequestHandler, ServerHandlerType};
 use crate::server::response::{BitWriter, RegisterWriter};
 use crate::server::*;
 use crate::types::*;
 
 use scursor::ReadCursor;
+use std::time::Duration;
 
 #[derive(Debug)]
 pub(crate) enum Request<'a> {
@@ -37,24 +39,69 @@ pub(crate) enum BroadcastRequest<'a> {
 
 impl<'a> BroadcastRequest<'a> {
     // execute a broadcast request against the handler
-    pub(crate) fn execute<T: RequestHandler>(&self, handler: &mut T) {
-        match self {
-            BroadcastRequest::WriteSingleCoil(x) => {
-                let _ = handler.write_single_coil(*x);
-            }
-            BroadcastRequest::WriteSingleRegister(x) => {
-                let _ = handler.write_single_register(*x);
-            }
-            BroadcastRequest::WriteMultipleCoils(x) => {
-                let _ = handler.write_multiple_coils(*x);
-            }
-            BroadcastRequest::WriteMultipleRegisters(x) => {
-                let _ = handler.write_multiple_registers(*x);
-            }
+    //
+    // nothing is sent back for a broadcast, so results that are not immediately available
+    // are awaited by a separate task instead of holding up the session
+    pub(crate) fn execute<T: AsyncRequestHandler>(
+        &self,
+        handler: &ServerHandlerType<T>,
+        context: &RequestContext,
+        timeout: Duration,
+    ) {
+        let result = start(handler, |handler| match self {
+            BroadcastRequest::WriteSingleCoil(x) => handler.write_coil(context, *x),
+            BroadcastRequest::WriteSingleRegister(x) => handler.write_register(context, *x),
+            BroadcastRequest::WriteMultipleCoils(x) => handler.write_coils(context, *x),
+            BroadcastRequest::WriteMultipleRegisters(x) => handler.write_registers(context, *x),
+        });
+        if !result.result.is_ready() {
+            tokio::spawn(async move {
+                let _ = complete(result, timeout).await;
+            });
+        }
+    }
+}
+
+/// result of a handler along with the exception to return if it is not available in time
+struct Pending<T> {
+    result: MaybeAsync<Result<T, ExceptionCode>>,
+    timeout_exception: ExceptionCode,
+}
+
+/// start a request while the handler is locked
+fn start<H, T>(
+    handler: &ServerHandlerType<H>,
+    request: impl FnOnce(&mut H) -> MaybeAsync<Result<T, ExceptionCode>>,
+) -> Pending<T>
+where
+    H: AsyncRequestHandler,
+{
+    let mut handler = handler.lock().unwrap();
+    Pending {
+        result: request(&mut handler),
+        timeout_exception: handler.timeout_exception(),
+    }
+}
+
+/// wait for the result of a handler, mapping a timeout to the exception of the handler
+async fn complete<T>(pending: Pending<T>, timeout: Duration) -> Result<T, ExceptionCode> {
+    match pending.result.get_within(timeout).await {
+        Some(x) => x,
+        None => {
+            tracing::warn!("request handler did not complete within {:?}", timeout);
+            Err(pending.timeout_exception)
         }
     }
 }
 
+/// values returned by a handler for a range, any value that is missing results in an exception
+fn value_at<T: Copy>(values: &[T], range: AddressRange, address: u16) -> Result<T, ExceptionCode> {
+    values
+        .get(address.wrapping_sub(range.start) as usize)
+        .copied()
+        .ok_or(ExceptionCode::ServerDeviceFailure)
+}
+
 impl<'a> Request<'a> {
     pub(crate) fn get_function(&self) -> FunctionCode {
         match self {
@@ -84,19 +131,21 @@ impl<'a> Request<'a> {
         }
     }
 
-    pub(crate) fn get_reply<'b>(
+    pub(crate) async fn get_reply<'b, T: AsyncRequestHandler>(
         &self,
         header: FrameHeader,
-        handler: &mut dyn RequestHandler,
+        handler: &ServerHandlerType<T>,
+        context: &RequestContext,
         writer: &'b mut FrameWriter,
-        level: DecodeLevel,
+        level: DecodeSettings,
+        timeout: Duration,
     ) -> Result<&'b [u8], RequestError> {
         fn write_result<T>(
             function: FunctionCode,
             header: FrameHeader,
             writer: &mut FrameWriter,
             result: Result<T, ExceptionCode>,
-            level: DecodeLevel,
+            level: DecodeSettings,
         ) -> Result<&[u8], RequestError>
         where
             T: Serialize + Loggable,
@@ -109,56 +158,103 @@ impl<'a> Request<'a> {
 
         let function = self.get_function();
 
-        // make a first pass effort to serialize a response
+        // the handler is only locked while the request is started, never while it is awaited
         match self {
             Request::ReadCoils(range) => {
-                let bits = BitWriter::new(*range, |i| handler.read_coil(i));
-                writer.format_reply(header, function, &bits, level)
+                let result = start(handler, |x| x.read_coils(context, range.get()));
+                let result = complete(result, timeout).await;
+                Self::write_bits(header, function, *range, result, writer, level)
             }
             Request::ReadDiscreteInputs(range) => {
-                let bits = BitWriter::new(*range, |i| handler.read_discrete_input(i));
-                writer.format_reply(header, function, &bits, level)
+                let result = start(handler, |x| x.read_discrete_inputs(context, range.get()));
+                let result = complete(result, timeout).await;
+                Self::write_bits(header, function, *range, result, writer, level)
             }
             Request::ReadHoldingRegisters(range) => {
-                let registers = RegisterWriter::new(*range, |i| handler.read_holding_register(i));
-                writer.format_reply(header, function, &registers, level)
+                let result = start(handler, |x| x.read_holding_registers(context, range.get()));
+                let result = complete(result, timeout).await;
+                Self::write_registers(header, function, *range, result, writer, level)
             }
             Request::ReadInputRegisters(range) => {
-                let registers = RegisterWriter::new(*range, |i| handler.read_input_register(i));
-                writer.format_reply(header, function, &registers, level)
+                let result = start(handler, |x| x.read_input_registers(context, range.get()));
+                let result = complete(result, timeout).await;
+                Self::write_registers(header, function, *range, result, writer, level)
             }
             Request::ReadDeviceIdentification(read) => {
                 //TODO: The server needs to answer the request depending on the values of dev_id, obj_id
                 let info = match read.dev_id {
-                    ReadDeviceIdCode::BasicStreaming => handler.read_basic_device_info(read.obj_id),
-                    ReadDeviceIdCode::RegularStreaming => handler.read_regular_device_info(read.obj_id),
-                    ReadDeviceIdCode::ExtendedStreaming => handler.read_extended_device_info(read.obj_id),
+                    ReadDeviceIdCode::BasicStreaming => start(handler, |x| {
+                        x.read_basic_device_identification(context, read.obj_id)
+                    }),
+                    ReadDeviceIdCode::RegularStreaming => start(handler, |x| {
+                        x.read_regular_device_identification(context, read.obj_id)
+                    }),
+                    ReadDeviceIdCode::ExtendedStreaming => start(handler, |x| {
+                        x.read_extended_device_identification(context, read.obj_id)
+                    }),
                     _ => return Err(RequestError::Exception(ExceptionCode::IllegalDataAddress)),
                 };
+                let info = complete(info, timeout).await;
 
-                writer.format_reply(header, function, &info.unwrap(), level)
+                write_result(function, header, writer, info, level)
             }
             Request::WriteSingleCoil(request) => {
-                let result = handler.write_single_coil(*request).map(|_| *request);
+                let result = start(handler, |x| x.write_coil(context, *request));
+                let result = complete(result, timeout).await.map(|_| *request);
                 write_result(function, header, writer, result, level)
             }
             Request::WriteSingleRegister(request) => {
-                let result = handler.write_single_register(*request).map(|_| *request);
+                let result = start(handler, |x| x.write_register(context, *request));
+                let result = complete(result, timeout).await.map(|_| *request);
                 write_result(function, header, writer, result, level)
             }
             Request::WriteMultipleCoils(items) => {
-                let result = handler.write_multiple_coils(*items).map(|_| items.range);
+                let result = start(handler, |x| x.write_coils(context, *items));
+                let result = complete(result, timeout).await.map(|_| items.range);
                 write_result(function, header, writer, result, level)
             }
             Request::WriteMultipleRegisters(items) => {
-                let result = handler
-                    .write_multiple_registers(*items)
-                    .map(|_| items.range);
+                let result = start(handler, |x| x.write_registers(context, *items));
+                let result = complete(result, timeout).await.map(|_| items.range);
                 write_result(function, header, writer, result, level)
             }
         }
     }
 
+    fn write_bits(
+        header: FrameHeader,
+        function: FunctionCode,
+        range: ReadBitsRange,
+        result: Result<Vec<bool>, ExceptionCode>,
+        writer: &mut FrameWriter,
+        level: DecodeSettings,
+    ) -> Result<&[u8], RequestError> {
+        match result {
+            Ok(values) => {
+                let bits = BitWriter::new(range, |i| value_at(&values, range.get(), i));
+                writer.format_reply(header, function, &bits, level)
+            }
+            Err(ex) => writer.format_ex(header, FunctionField::Exception(function), ex, level),
+        }
+    }
+
+    fn write_registers(
+        header: FrameHeader,
+        function: FunctionCode,
+        range: ReadRegistersRange,
+        result: Result<Vec<u16>, ExceptionCode>,
+        writer: &mut FrameWriter,
+        level: DecodeSettings,
+    ) -> Result<&[u8], RequestError> {
+        match result {
+            Ok(values) => {
+                let registers = RegisterWriter::new(range, |i| value_at(&values, range.get(), i));
+                writer.format_reply(header, function, &registers, level)
+            }
+            Err(ex) => writer.format_ex(header, FunctionField::Exception(function), ex, level),
+        }
+    }
+
     pub(crate) fn parse(
         function: FunctionCode,
         cursor: &'a mut ReadCursor,
@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Information about a session connected to a TCP or TLS server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionInfo {
+    /// Id of the session within the server
+    pub id: u64,
+    /// Remote address of the client
+    pub remote: SocketAddr,
+    /// Role of the client when the server authorizes requests, `None` otherwise
+    pub role: Option<String>,
+    /// Time at which the session was established
+    pub connected_at: SystemTime,
+    /// Request counters of the session
+    pub counters: SessionCounters,
+}
+
+/// Count of the requests received by a session
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct SessionCounters {
+    /// Total number of requests received
+    pub requests: u64,
+    /// Requests answered with an exception before reaching a handler because they used an
+    /// unknown function, were malformed or were not authorized
+    pub rejected: u64,
+    /// Requests ignored because no handler is associated with their unit id
+    pub unmapped: u64,
+}
+
+/// Callbacks invoked when sessions connect to or disconnect from a TCP or TLS server
+///
+/// Callbacks are invoked from the session tasks and must not block.
+pub trait SessionListener: Send + Sync + 'static {
+    /// A session was established, after the completion of the TLS handshake if applicable
+    fn on_connect(&self, _session: &SessionInfo) {}
+
+    /// A session was closed, `session` contains its final counters
+    fn on_disconnect(&self, _session: &SessionInfo) {}
+}
+
+/// Counters updated by a running session
+#[derive(Default)]
+pub(crate) struct Counters {
+    requests: AtomicU64,
+    rejected: AtomicU64,
+    unmapped: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) fn on_request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_rejected(&self) {
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_unmapped(&self) {
+        self.unmapped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> SessionCounters {
+        SessionCounters {
+            requests: self.requests.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            unmapped: self.unmapped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+struct Entry {
+    remote: SocketAddr,
+    role: Option<String>,
+    connected_at: SystemTime,
+    counters: Arc<Counters>,
+}
+
+impl Entry {
+    fn info(&self, id: u64) -> SessionInfo {
+        SessionInfo {
+            id,
+            remote: self.remote,
+            role: self.role.clone(),
+            connected_at: self.connected_at,
+            counters: self.counters.get(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    sessions: BTreeMap<u64, Entry>,
+    listener: Option<Arc<dyn SessionListener>>,
+}
+
+/// Established sessions of a server, shared between the [`ServerHandle`](crate::server::ServerHandle)
+/// and the session tasks
+#[derive(Clone, Default)]
+pub(crate) struct Sessions {
+    inner: Arc<Mutex<Registry>>,
+}
+
+impl std::fmt::Debug for Sessions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Sessions")
+    }
+}
+
+impl Sessions {
+    pub(crate) fn get(&self) -> Vec<SessionInfo> {
+        let registry = self.inner.lock().unwrap();
+        registry
+            .sessions
+            .iter()
+            .map(|(id, entry)| entry.info(*id))
+            .collect()
+    }
+
+    pub(crate) fn contains(&self, id: u64) -> bool {
+        self.inner.lock().unwrap().sessions.contains_key(&id)
+    }
+
+    pub(crate) fn set_listener(&self, listener: Option<Arc<dyn SessionListener>>) {
+        self.inner.lock().unwrap().listener = listener;
+    }
+
+    /// record an established session, it is removed when the returned value is dropped
+    pub(crate) fn register(
+        &self,
+        id: u64,
+        remote: SocketAddr,
+        role: Option<String>,
+    ) -> Registration {
+        let entry = Entry {
+            remote,
+            role,
+            connected_at: SystemTime::now(),
+            counters: Default::default(),
+        };
+        let counters = entry.counters.clone();
+        let info = entry.info(id);
+
+        // the listener is invoked without holding the lock
+        let listener = {
+            let mut registry = self.inner.lock().unwrap();
+            registry.sessions.insert(id, entry);
+            registry.listener.clone()
+        };
+        if let Some(listener) = listener {
+            listener.on_connect(&info);
+        }
+
+        Registration {
+            id,
+            sessions: self.clone(),
+            counters,
+        }
+    }
+
+    fn remove(&self, id: u64) {
+        let (info, listener) = {
+            let mut registry = self.inner.lock().unwrap();
+            let info = registry.sessions.remove(&id).map(|entry| entry.info(id));
+            (info, registry.listener.clone())
+        };
+        if let (Some(info), Some(listener)) = (info, listener) {
+            listener.on_disconnect(&info);
+        }
+    }
+}
+
+/// Registration of an established session
+pub(crate) struct Registration {
+    id: u64,
+    sessions: Sessions,
+    counters: Arc<Counters>,
+}
+
+impl Registration {
+    pub(crate) fn counters(&self) -> Arc<Counters> {
+        self.counters.clone()
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.sessions.remove(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Listener {
+        events: Mutex<Vec<(bool, SessionInfo)>>,
+    }
+
+    impl SessionListener for Listener {
+        fn on_connect(&self, session: &SessionInfo) {
+            self.events.lock().unwrap().push((true, session.clone()));
+        }
+
+        fn on_disconnect(&self, session: &SessionInfo) {
+            self.events.lock().unwrap().push((false, session.clone()));
+        }
+    }
+
+    #[test]
+    fn sessions_are_listed_until_the_registration_is_dropped() {
+        let sessions = Sessions::default();
+        let listener = Arc::new(Listener::default());
+        sessions.set_listener(Some(listener.clone()));
+
+        let remote = "127.0.0.1:5000".parse().unwrap();
+        let registration = sessions.register(3, remote, Some("operator".to_string()));
+        registration.counters().on_request();
+        registration.counters().on_request();
+        registration.counters().on_rejected();
+
+        let list = sessions.get();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].id, 3);
+        assert_eq!(list[0].remote, remote);
+        assert_eq!(list[0].role.as_deref(), Some("operator"));
+        assert_eq!(
+            list[0].counters,
+            SessionCounters {
+                requests: 2,
+                rejected: 1,
+                unmapped: 0,
+            }
+        );
+        assert!(sessions.contains(3));
+
+        drop(registration);
+        assert!(sessions.get().is_empty());
+        assert!(!sessions.contains(3));
+
+        let events = listener.events.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events[0].0);
+        assert_eq!(events[0].1.counters, SessionCounters::default());
+        assert!(!events[1].0);
+        assert_eq!(events[1].1, list[0]);
+    }
+}
@@ -1,48 +1,67 @@
 use crate::common::phys::PhysLayer;
-use crate::server::{Authorization, AuthorizationHandler};
+use crate::decode::{DecodeFormat, DecodeSettings};
+use crate::server::{Authorization, AuthorizationHandler, RequestContext};
 use crate::{DecodeLevel, UnitId};
 
 use crate::common::frame::{
     Frame, FrameDestination, FrameHeader, FrameWriter, FramedReader, FunctionField,
 };
 use crate::common::function::FunctionCode;
+use crate::common::structured;
+use crate::decoder::MessageType;
 use crate::error::*;
 use crate::exception::ExceptionCode;
-use crate::server::handler::{RequestHandler, ServerHandlerMap};
+use crate::observer::{Direction, Tap, TrafficObserver};
+use crate::server::handler::{AsyncRequestHandler, SharedHandlerMap};
 use crate::server::request::{Request, RequestDisplay};
+use crate::server::session::Counters;
 
 use scursor::ReadCursor;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
 /// Messages that can be sent to change server settings dynamically
-#[derive(Copy, Clone)]
+///
+/// Settings are applied by the server task and by every session, they are not `Copy`
+/// because an observer may be shared with the sessions.
+#[derive(Clone)]
+#[allow(clippy::enum_variant_names)] // the existing `ChangeDecoding` variant sets the naming
 pub enum ServerSetting {
     ChangeDecoding(DecodeLevel),
+    ChangeDecodeFormat(DecodeFormat),
+    ChangeObserver(Option<Arc<dyn TrafficObserver>>),
+    ChangeRequestTimeout(Duration),
 }
 
 pub(crate) struct SessionTask<T>
 where
-    T: RequestHandler,
+    T: AsyncRequestHandler,
 {
-    handlers: ServerHandlerMap<T>,
+    handlers: SharedHandlerMap<T>,
     auth: AuthorizationType,
     commands: tokio::sync::mpsc::Receiver<ServerSetting>,
     writer: FrameWriter,
     reader: FramedReader,
-    decode: DecodeLevel,
+    decode: DecodeSettings,
+    observer: Option<Arc<dyn TrafficObserver>>,
+    session: u64,
+    remote: Option<SocketAddr>,
+    timeout: Duration,
+    counters: Arc<Counters>,
 }
 
 impl<T> SessionTask<T>
 where
-    T: RequestHandler,
+    T: AsyncRequestHandler,
 {
     pub(crate) fn new(
-        handlers: ServerHandlerMap<T>,
+        handlers: SharedHandlerMap<T>,
         auth: AuthorizationType,
         writer: FrameWriter,
         reader: FramedReader,
         commands: tokio::sync::mpsc::Receiver<ServerSetting>,
-        decode: DecodeLevel,
+        decode: DecodeSettings,
     ) -> Self {
         Self {
             handlers,
@@ -51,9 +70,41 @@ where
             writer,
             reader,
             decode,
+            observer: None,
+            session: 0,
+            remote: None,
+            timeout: crate::server::DEFAULT_REQUEST_TIMEOUT,
+            counters: Default::default(),
         }
     }
 
+    pub(crate) fn with_observer(
+        mut self,
+        session: u64,
+        observer: Option<Arc<dyn TrafficObserver>>,
+    ) -> Self {
+        self.session = session;
+        self.observer = observer;
+        self
+    }
+
+    pub(crate) fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub(crate) fn with_counters(mut self, counters: Arc<Counters>) -> Self {
+        self.counters = counters;
+        self
+    }
+
+    fn set_tap(&mut self, io: &mut PhysLayer) {
+        let tap = Tap::new(self.observer.clone(), self.session, io.endpoints());
+        self.writer.set_tap(tap.clone());
+        self.reader.set_tap(tap.clone());
+        io.set_tap(tap);
+    }
+
     async fn reply_with_error(
         &mut self,
         io: &mut PhysLayer,
@@ -75,12 +126,14 @@ where
         // do not answer on broadcast
         if header.destination != FrameDestination::Broadcast {
             let bytes = self.writer.format_ex(header, func, ex, self.decode)?;
-            io.write(bytes, self.decode.physical).await?;
+            io.write(bytes, self.decode).await?;
         }
         Ok(())
     }
 
     pub(crate) async fn run(&mut self, io: &mut PhysLayer) -> RequestError {
+        self.remote = io.endpoints().map(|x| x.remote);
+        self.set_tap(io);
         loop {
             if let Err(err) = self.run_one(io).await {
                 tracing::warn!("session error: {}", err);
@@ -90,10 +143,7 @@ where
     }
 
     #[cfg(feature = "serial")]
-    pub(crate) async fn sleep_for(
-        &mut self,
-        duration: std::time::Duration,
-    ) -> Result<(), Shutdown> {
+    pub(crate) async fn sleep_for(&mut self, duration: Duration) -> Result<(), Shutdown> {
         match tokio::time::timeout(duration, self.process_settings()).await {
             // mpsc closed
             Ok(_) => Err(Shutdown),
@@ -125,6 +175,8 @@ where
                     None => Err(crate::error::RequestError::Shutdown),
                     Some(setting) => {
                         self.apply_setting(setting);
+                        // the observer may have changed
+                        self.set_tap(io);
                         Ok(())
                     }
                }
@@ -135,13 +187,23 @@ where
     fn apply_setting(&mut self, setting: ServerSetting) {
         match setting {
             ServerSetting::ChangeDecoding(level) => {
-                self.decode = level;
+                self.decode.set_level(level);
+            }
+            ServerSetting::ChangeDecodeFormat(format) => {
+                self.decode.format = format;
+            }
+            ServerSetting::ChangeObserver(observer) => {
+                self.observer = observer;
+            }
+            ServerSetting::ChangeRequestTimeout(timeout) => {
+                self.timeout = timeout;
             }
         }
     }
 
     async fn handle_frame(&mut self, io: &mut PhysLayer, frame: Frame) -> Result<(), RequestError> {
         let mut cursor = ReadCursor::new(frame.payload());
+        self.counters.on_request();
 
         let function = match cursor.read_u8() {
             Err(_) => {
@@ -152,6 +214,7 @@ where
                 Some(x) => x,
                 None => {
                     tracing::warn!("received unknown function code: {}", value);
+                    self.counters.on_rejected();
                     return self
                         .reply_with_error_generic(
                             io,
@@ -168,6 +231,7 @@ where
             Ok(x) => x,
             Err(err) => {
                 tracing::warn!("error parsing {:?} request: {}", function, err);
+                self.counters.on_rejected();
                 return self
                     .reply_with_error(io, frame.header, function, ExceptionCode::IllegalDataValue)
                     .await;
@@ -175,10 +239,19 @@ where
         };
 
         if self.decode.app.enabled() {
-            tracing::info!(
-                "PDU RX - {}",
-                RequestDisplay::new(self.decode.app, &request)
-            );
+            if self.decode.is_structured() {
+                structured::pdu(
+                    Direction::Rx,
+                    MessageType::Request,
+                    self.decode.app,
+                    frame.payload(),
+                );
+            } else {
+                tracing::info!(
+                    "PDU RX - {}",
+                    RequestDisplay::new(self.decode.app, &request)
+                );
+            }
         }
 
         // check authorization
@@ -186,6 +259,7 @@ where
             .auth
             .is_authorized(frame.header.destination.into_unit_id(), &request)
         {
+            self.counters.on_rejected();
             if !frame.header.destination.is_broadcast() {
                 self.reply_with_error(
                     io,
@@ -198,32 +272,45 @@ where
             return Ok(());
         }
 
+        let context = RequestContext {
+            unit_id: frame.header.destination.into_unit_id(),
+            session: self.session,
+            remote: self.remote,
+            role: self.auth.role().cloned(),
+            tx_id: frame.header.tx_id.map(|x| x.to_u16()),
+        };
+
         // if no addresses match, then don't respond
         match frame.header.destination {
             FrameDestination::UnitId(unit_id) => {
                 let handler = match self.handlers.get(unit_id) {
                     None => {
                         tracing::warn!("received frame for unmapped unit id: {}", unit_id);
+                        self.counters.on_unmapped();
                         return Ok(());
                     }
                     Some(handler) => handler,
                 };
                 // get the reply data (or exception reply)
-                let reply: &[u8] = request.get_reply(
-                    frame.header,
-                    handler.lock().unwrap().as_mut(),
-                    &mut self.writer,
-                    self.decode,
-                )?;
-                io.write(reply, self.decode.physical).await?;
+                let reply: &[u8] = request
+                    .get_reply(
+                        frame.header,
+                        &handler,
+                        &context,
+                        &mut self.writer,
+                        self.decode,
+                        self.timeout,
+                    )
+                    .await?;
+                io.write(reply, self.decode).await?;
             }
             FrameDestination::Broadcast => match request.into_broadcast_request() {
                 None => {
                     tracing::warn!("broadcast is not supported for {}", function);
                 }
                 Some(request) => {
-                    for handler in self.handlers.iter_mut() {
-                        request.execute(handler.lock().unwrap().as_mut());
+                    for handler in self.handlers.all() {
+                        request.execute(&handler, &context, self.timeout);
                     }
                 }
             },
@@ -235,11 +322,10 @@ where
 
 /// Determines how authorization of user defined requests are handled
 pub(crate) enum AuthorizationType {
-    /// Requests do not require authorization checks (TCP / RTU)
+    /// Requests do not require authorization checks
     None,
     /// Requests are authorized using a user-supplied handler
-    #[allow(dead_code)] // when tls feature is disabled
-    Handler(Arc<dyn AuthorizationHandler>, String),
+    Handler(Arc<dyn AuthorizationHandler>, Arc<str>),
 }
 
 impl AuthorizationType {
@@ -268,6 +354,13 @@ impl AuthorizationType {
         }
     }
 
+    pub(crate) fn role(&self) -> Option<&Arc<str>> {
+        match self {
+            AuthorizationType::None => None,
+            AuthorizationType::Handler(_, role) => Some(role),
+        }
+    }
+
     pub(crate) fn is_authorized(&self, unit_id: UnitId, request: &Request) -> Authorization {
         match self {
             AuthorizationType::None => Authorization::Allow,
@@ -1,4 +1,7 @@
-use crate::types::{AddressRange, BitIterator, RegisterIterator};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::types::{AddressRange, BitIterator, RegisterIterator, UnitId};
 
 /// Request to write coils received by the server
 #[derive(Debug, Copy, Clone)]
@@ -29,3 +32,19 @@ impl<'a> WriteRegisters<'a> {
         Self { range, iterator }
     }
 }
+
+/// Session and frame information of a request received by the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RequestContext {
+    /// Unit id of the request, [`UnitId::broadcast`] for broadcast requests
+    pub unit_id: UnitId,
+    /// Id of the session within the server
+    pub session: u64,
+    /// Remote address of the session, `None` for serial ports
+    pub remote: Option<SocketAddr>,
+    /// Role of the client when the server authorizes requests, `None` otherwise
+    pub role: Option<Arc<str>>,
+    /// Transaction id of the MBAP header, `None` for RTU
+    pub tx_id: Option<u16>,
+}
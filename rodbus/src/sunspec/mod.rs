@@ -0,0 +1,158 @@
+use crate::client::{Channel, RequestParam};
+use crate::constants::limits;
+use crate::error::RequestError;
+use crate::types::AddressRange;
+
+mod models;
+
+pub use models::*;
+
+/// Registers containing the ASCII characters `SunS` that mark the start of the SunSpec map
+pub const MARKER: [u16; 2] = [0x5375, 0x6E53];
+
+/// Standard base addresses of the SunSpec map in the order they are probed
+pub const BASE_ADDRESSES: [u16; 3] = [40000, 50000, 0];
+
+/// Model id that terminates the model chain
+pub const END_MODEL_ID: u16 = 0xFFFF;
+
+/// Header of a model in the SunSpec model chain
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ModelHeader {
+    /// Model id
+    pub id: u16,
+    /// Address of the first register of the model body, i.e. after the id and length registers
+    pub address: u16,
+    /// Number of registers in the model body
+    pub length: u16,
+}
+
+/// Errors that occur while discovering or reading SunSpec models
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SunSpecError {
+    /// The `SunS` marker was not found at any of the [`BASE_ADDRESSES`]
+    NotFound,
+    /// A model has a length that is too short for its id or runs past the last address
+    BadModelLength(u16, u16), // id and length
+    /// The underlying request failed
+    Request(RequestError),
+}
+
+impl std::error::Error for SunSpecError {}
+
+impl std::fmt::Display for SunSpecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SunSpecError::NotFound => f.write_str("SunSpec marker not found"),
+            SunSpecError::BadModelLength(id, length) => {
+                write!(f, "invalid length of SunSpec model {id}: {length}")
+            }
+            SunSpecError::Request(err) => err.fmt(f),
+        }
+    }
+}
+
+impl From<RequestError> for SunSpecError {
+    fn from(err: RequestError) -> Self {
+        SunSpecError::Request(err)
+    }
+}
+
+/// Discovers and decodes the SunSpec models of a device using a [`Channel`]
+pub struct SunSpecClient {
+    channel: Channel,
+    param: RequestParam,
+}
+
+impl SunSpecClient {
+    /// Create a client that sends requests using `param`
+    pub fn new(channel: Channel, param: RequestParam) -> Self {
+        Self { channel, param }
+    }
+
+    /// Find the address of the `SunS` marker by probing the [`BASE_ADDRESSES`]
+    ///
+    /// Exception responses are treated as the marker not being present at the address.
+    pub async fn find_base_address(&mut self) -> Result<u16, SunSpecError> {
+        for base in BASE_ADDRESSES {
+            let range =
+                AddressRange::try_from(base, MARKER.len() as u16).map_err(RequestError::from)?;
+            match self.channel.read_holding_registers(self.param, range).await {
+                Ok(registers) => {
+                    if registers.iter().map(|x| x.value).eq(MARKER) {
+                        return Ok(base);
+                    }
+                }
+                Err(RequestError::Exception(_)) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Err(SunSpecError::NotFound)
+    }
+
+    /// Walk the model chain and return the header of every model
+    pub async fn discover(&mut self) -> Result<Vec<ModelHeader>, SunSpecError> {
+        let base = self.find_base_address().await?;
+
+        let mut headers = Vec::new();
+        let mut address = base as u32 + MARKER.len() as u32;
+        loop {
+            let range = AddressRange::try_from(address as u16, 2).map_err(RequestError::from)?;
+            let registers = self.read(range).await?;
+            let (id, length) = (registers[0], registers[1]);
+            if id == END_MODEL_ID {
+                return Ok(headers);
+            }
+
+            let body = address + 2;
+            let next = body + length as u32;
+            // the end marker must also fit in the address space
+            if next + 2 > u16::MAX as u32 + 1 {
+                return Err(SunSpecError::BadModelLength(id, length));
+            }
+
+            headers.push(ModelHeader {
+                id,
+                address: body as u16,
+                length,
+            });
+            address = next;
+        }
+    }
+
+    /// Read and decode a single model
+    pub async fn read_model(&mut self, header: ModelHeader) -> Result<Model, SunSpecError> {
+        let mut registers = Vec::with_capacity(header.length as usize);
+        let mut offset = 0;
+        while offset < header.length {
+            let count = std::cmp::min(header.length - offset, limits::MAX_READ_REGISTERS_COUNT);
+            let start = header
+                .address
+                .checked_add(offset)
+                .ok_or(SunSpecError::BadModelLength(header.id, header.length))?;
+            let range = AddressRange::try_from(start, count).map_err(RequestError::from)?;
+            registers.extend(self.read(range).await?);
+            offset += count;
+        }
+
+        Model::decode(header, &registers)
+    }
+
+    /// Discover and decode all the models of the device
+    pub async fn read_all(&mut self) -> Result<Vec<Model>, SunSpecError> {
+        let mut models = Vec::new();
+        for header in self.discover().await? {
+            models.push(self.read_model(header).await?);
+        }
+        Ok(models)
+    }
+
+    async fn read(&mut self, range: AddressRange) -> Result<Vec<u16>, SunSpecError> {
+        let registers = self
+            .channel
+            .read_holding_registers(self.param, range)
+            .await?;
+        Ok(registers.into_iter().map(|x| x.value).collect())
+    }
+}
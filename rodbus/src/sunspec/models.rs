@@ -0,0 +1,803 @@
+use crate::codec::{decode_ascii, WordOrder};
+use crate::sunspec::{ModelHeader, SunSpecError};
+use crate::types::Indexed;
+
+/// Decoded SunSpec model
+#[derive(Clone, Debug, PartialEq)]
+pub enum Model {
+    /// Model 1
+    Common(Common),
+    /// Models 101, 102 and 103
+    Inverter(Inverter),
+    /// Model 120
+    Nameplate(Nameplate),
+    /// Model 121
+    BasicSettings(BasicSettings),
+    /// Model 122
+    Status(Status),
+    /// Model 123
+    Controls(Controls),
+    /// Model 124
+    Storage(Storage),
+    /// Model 160
+    Mppt(Mppt),
+    /// Model that is not decoded by the library
+    Unknown(RawModel),
+}
+
+impl Model {
+    /// Decode the body of a model
+    ///
+    /// Models that are not decoded by the library are returned as [`Model::Unknown`].
+    pub fn decode(header: ModelHeader, registers: &[u16]) -> Result<Self, SunSpecError> {
+        let bad_length = || SunSpecError::BadModelLength(header.id, header.length);
+        if registers.len() != header.length as usize {
+            return Err(bad_length());
+        }
+
+        let min_length = match header.id {
+            1 => Common::LENGTH,
+            101..=103 => Inverter::LENGTH,
+            120 => Nameplate::LENGTH,
+            121 => BasicSettings::LENGTH,
+            122 => Status::LENGTH,
+            123 => Controls::LENGTH,
+            124 => Storage::LENGTH,
+            160 => Mppt::FIXED_LENGTH,
+            _ => 0,
+        };
+        if registers.len() < min_length {
+            return Err(bad_length());
+        }
+
+        let r = Registers(registers);
+        let model = match header.id {
+            1 => Model::Common(Common::decode(&r)),
+            101 => Model::Inverter(Inverter::decode(Phases::Single, &r)),
+            102 => Model::Inverter(Inverter::decode(Phases::Split, &r)),
+            103 => Model::Inverter(Inverter::decode(Phases::Three, &r)),
+            120 => Model::Nameplate(Nameplate::decode(&r)),
+            121 => Model::BasicSettings(BasicSettings::decode(&r)),
+            122 => Model::Status(Status::decode(&r)),
+            123 => Model::Controls(Controls::decode(&r)),
+            124 => Model::Storage(Storage::decode(&r)),
+            160 => Model::Mppt(Mppt::decode(&r).ok_or_else(bad_length)?),
+            _ => Model::Unknown(RawModel {
+                header,
+                registers: registers.to_vec(),
+            }),
+        };
+
+        Ok(model)
+    }
+}
+
+/// Registers of a model that is not decoded by the library
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawModel {
+    /// Header of the model
+    pub header: ModelHeader,
+    /// Registers of the model body
+    pub registers: Vec<u16>,
+}
+
+/// Model 1, identification of the device
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Common {
+    /// Manufacturer
+    pub manufacturer: Option<String>,
+    /// Model name
+    pub model: Option<String>,
+    /// Options
+    pub options: Option<String>,
+    /// Version
+    pub version: Option<String>,
+    /// Serial number
+    pub serial_number: Option<String>,
+    /// Modbus device address
+    pub device_address: Option<u16>,
+}
+
+impl Common {
+    const LENGTH: usize = 65;
+
+    fn decode(r: &Registers) -> Self {
+        Self {
+            manufacturer: r.string(0, 16),
+            model: r.string(16, 16),
+            options: r.string(32, 8),
+            version: r.string(40, 8),
+            serial_number: r.string(48, 16),
+            device_address: r.uint16(64),
+        }
+    }
+}
+
+/// Phase configuration of an inverter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phases {
+    /// Single phase, model 101
+    Single,
+    /// Split phase, model 102
+    Split,
+    /// Three phase, model 103
+    Three,
+}
+
+/// Operating state of an inverter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InverterState {
+    /// Device is not operating
+    Off,
+    /// Device is sleeping or auto-shutdown
+    Sleeping,
+    /// Device is starting up
+    Starting,
+    /// Device is tracking the maximum power point
+    Mppt,
+    /// Device is operating at reduced power output
+    Throttled,
+    /// Device is shutting down
+    ShuttingDown,
+    /// One or more faults exist
+    Fault,
+    /// Device is in standby mode
+    Standby,
+    /// Value that is not defined by the model
+    Other(u16),
+}
+
+impl From<u16> for InverterState {
+    fn from(value: u16) -> Self {
+        match value {
+            1 => InverterState::Off,
+            2 => InverterState::Sleeping,
+            3 => InverterState::Starting,
+            4 => InverterState::Mppt,
+            5 => InverterState::Throttled,
+            6 => InverterState::ShuttingDown,
+            7 => InverterState::Fault,
+            8 => InverterState::Standby,
+            _ => InverterState::Other(value),
+        }
+    }
+}
+
+/// Models 101 to 103, inverter measurements
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Inverter {
+    /// Phase configuration given by the model id
+    pub phases: Phases,
+    /// AC current in A
+    pub current: Option<f64>,
+    /// Current of phases A, B and C in A
+    pub phase_current: [Option<f64>; 3],
+    /// Voltage between phases AB, BC and CA in V
+    pub line_voltage: [Option<f64>; 3],
+    /// Voltage of phases A, B and C to neutral in V
+    pub phase_voltage: [Option<f64>; 3],
+    /// AC power in W
+    pub power: Option<f64>,
+    /// Line frequency in Hz
+    pub frequency: Option<f64>,
+    /// Apparent power in VA
+    pub apparent_power: Option<f64>,
+    /// Reactive power in var
+    pub reactive_power: Option<f64>,
+    /// Power factor in percent
+    pub power_factor: Option<f64>,
+    /// AC energy in Wh
+    pub energy: Option<f64>,
+    /// DC current in A
+    pub dc_current: Option<f64>,
+    /// DC voltage in V
+    pub dc_voltage: Option<f64>,
+    /// DC power in W
+    pub dc_power: Option<f64>,
+    /// Cabinet temperature in °C
+    pub cabinet_temperature: Option<f64>,
+    /// Heat sink temperature in °C
+    pub heat_sink_temperature: Option<f64>,
+    /// Transformer temperature in °C
+    pub transformer_temperature: Option<f64>,
+    /// Other temperature in °C
+    pub other_temperature: Option<f64>,
+    /// Operating state
+    pub state: Option<InverterState>,
+    /// Vendor specific operating state
+    pub vendor_state: Option<u16>,
+    /// Event flags
+    pub events: Option<u32>,
+    /// Reserved event flags
+    pub events_2: Option<u32>,
+    /// Vendor specific event flags
+    pub vendor_events: [Option<u32>; 4],
+}
+
+impl Inverter {
+    const LENGTH: usize = 50;
+
+    fn decode(phases: Phases, r: &Registers) -> Self {
+        let a_sf = r.scale(4);
+        let v_sf = r.scale(11);
+        let tmp_sf = r.scale(35);
+
+        Self {
+            phases,
+            current: scaled(r.uint16(0), a_sf),
+            phase_current: [1, 2, 3].map(|i| scaled(r.uint16(i), a_sf)),
+            line_voltage: [5, 6, 7].map(|i| scaled(r.uint16(i), v_sf)),
+            phase_voltage: [8, 9, 10].map(|i| scaled(r.uint16(i), v_sf)),
+            power: scaled(r.int16(12), r.scale(13)),
+            frequency: scaled(r.uint16(14), r.scale(15)),
+            apparent_power: scaled(r.int16(16), r.scale(17)),
+            reactive_power: scaled(r.int16(18), r.scale(19)),
+            power_factor: scaled(r.int16(20), r.scale(21)),
+            energy: scaled(r.acc32(22), r.scale(24)),
+            dc_current: scaled(r.uint16(25), r.scale(26)),
+            dc_voltage: scaled(r.uint16(27), r.scale(28)),
+            dc_power: scaled(r.int16(29), r.scale(30)),
+            cabinet_temperature: scaled(r.int16(31), tmp_sf),
+            heat_sink_temperature: scaled(r.int16(32), tmp_sf),
+            transformer_temperature: scaled(r.int16(33), tmp_sf),
+            other_temperature: scaled(r.int16(34), tmp_sf),
+            state: r.uint16(36).map(InverterState::from),
+            vendor_state: r.uint16(37),
+            events: r.uint32(38),
+            events_2: r.uint32(40),
+            vendor_events: [42, 44, 46, 48].map(|i| r.uint32(i)),
+        }
+    }
+}
+
+/// Model 120, inverter ratings
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Nameplate {
+    /// Type of DER device, 4 for PV and 82 for PV with storage
+    pub der_type: Option<u16>,
+    /// Continuous power output capability in W
+    pub power: Option<f64>,
+    /// Continuous apparent power output capability in VA
+    pub apparent_power: Option<f64>,
+    /// Continuous reactive power capability in quadrants 1 to 4 in var
+    pub reactive_power: [Option<f64>; 4],
+    /// Maximum RMS AC current level capability in A
+    pub current: Option<f64>,
+    /// Minimum power factor capability in quadrants 1 to 4 in cos()
+    pub power_factor: [Option<f64>; 4],
+    /// Nominal energy rating of storage device in Wh
+    pub energy: Option<f64>,
+    /// Usable capacity of the battery in Ah
+    pub capacity: Option<f64>,
+    /// Maximum rate of energy transfer into the storage device in W
+    pub max_charge_rate: Option<f64>,
+    /// Maximum rate of energy transfer out of the storage device in W
+    pub max_discharge_rate: Option<f64>,
+}
+
+impl Nameplate {
+    const LENGTH: usize = 26;
+
+    fn decode(r: &Registers) -> Self {
+        Self {
+            der_type: r.uint16(0),
+            power: scaled(r.uint16(1), r.scale(2)),
+            apparent_power: scaled(r.uint16(3), r.scale(4)),
+            reactive_power: [5, 6, 7, 8].map(|i| scaled(r.int16(i), r.scale(9))),
+            current: scaled(r.uint16(10), r.scale(11)),
+            power_factor: [12, 13, 14, 15].map(|i| scaled(r.int16(i), r.scale(16))),
+            energy: scaled(r.uint16(17), r.scale(18)),
+            capacity: scaled(r.uint16(19), r.scale(20)),
+            max_charge_rate: scaled(r.uint16(21), r.scale(22)),
+            max_discharge_rate: scaled(r.uint16(23), r.scale(24)),
+        }
+    }
+}
+
+/// Model 121, inverter controls basic settings
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BasicSettings {
+    /// Setting for maximum power output in W
+    pub max_power: Option<f64>,
+    /// Voltage at the point of common coupling in V
+    pub reference_voltage: Option<f64>,
+    /// Offset from the PCC to the inverter in V
+    pub reference_voltage_offset: Option<f64>,
+    /// Setpoint for maximum voltage in V
+    pub max_voltage: Option<f64>,
+    /// Setpoint for minimum voltage in V
+    pub min_voltage: Option<f64>,
+    /// Setpoint for maximum apparent power in VA
+    pub max_apparent_power: Option<f64>,
+    /// Setting for maximum reactive power in quadrants 1 to 4 in var
+    pub max_reactive_power: [Option<f64>; 4],
+    /// Default ramp rate of change of active power in percent of max power per second
+    pub ramp_rate: Option<f64>,
+    /// Setpoint for minimum power factor in quadrants 1 to 4 in cos()
+    pub min_power_factor: [Option<f64>; 4],
+    /// Reactive power action, 1 for switching and 2 for maintaining
+    pub reactive_power_action: Option<u16>,
+    /// Calculation method for total apparent power, 1 for vector and 2 for arithmetic
+    pub apparent_power_calculation: Option<u16>,
+    /// Setpoint for maximum ramp rate as percentage of nominal maximum ramp rate
+    pub max_ramp_rate: Option<f64>,
+    /// Setpoint for nominal frequency at the ECP in Hz
+    pub nominal_frequency: Option<f64>,
+    /// Identity of the connected phase for single phase inverters, 1 to 3 for A to C
+    pub connected_phase: Option<u16>,
+}
+
+impl BasicSettings {
+    const LENGTH: usize = 30;
+
+    fn decode(r: &Registers) -> Self {
+        let v_sf = r.scale(23);
+
+        Self {
+            max_power: scaled(r.uint16(0), r.scale(20)),
+            reference_voltage: scaled(r.uint16(1), r.scale(21)),
+            reference_voltage_offset: scaled(r.int16(2), r.scale(22)),
+            max_voltage: scaled(r.uint16(3), v_sf),
+            min_voltage: scaled(r.uint16(4), v_sf),
+            max_apparent_power: scaled(r.uint16(5), r.scale(24)),
+            max_reactive_power: [6, 7, 8, 9].map(|i| scaled(r.int16(i), r.scale(25))),
+            ramp_rate: scaled(r.uint16(10), r.scale(26)),
+            min_power_factor: [11, 12, 13, 14].map(|i| scaled(r.int16(i), r.scale(27))),
+            reactive_power_action: r.uint16(15),
+            apparent_power_calculation: r.uint16(16),
+            max_ramp_rate: scaled(r.uint16(17), r.scale(28)),
+            nominal_frequency: scaled(r.uint16(18), r.scale(29)),
+            connected_phase: r.uint16(19),
+        }
+    }
+}
+
+/// Model 122, inverter controls extended measurements and status
+#[derive(Clone, Debug, PartialEq)]
+pub struct Status {
+    /// PV inverter present, available, operating and test flags
+    pub pv_connection: Option<u16>,
+    /// Storage inverter present, available, operating and test flags
+    pub storage_connection: Option<u16>,
+    /// ECP connection status, bit 0 set when connected
+    pub ecp_connection: Option<u16>,
+    /// AC lifetime active energy output in Wh
+    pub active_energy: Option<u64>,
+    /// AC lifetime apparent energy output in VAh
+    pub apparent_energy: Option<u64>,
+    /// AC lifetime reactive energy output in quadrants 1 to 4 in varh
+    pub reactive_energy: [Option<u64>; 4],
+    /// Amount of reactive power available in var
+    pub available_reactive_power: Option<f64>,
+    /// Amount of active power available in W
+    pub available_power: Option<f64>,
+    /// Bit mask of the limits that are at their maximum
+    pub limit_mask: Option<u32>,
+    /// Bit mask of the active inverter controls
+    pub active_controls: Option<u32>,
+    /// Source of the time synchronization
+    pub time_source: Option<String>,
+    /// Seconds since 01-01-2000 00:00 UTC
+    pub timestamp: Option<u32>,
+    /// Ride-through status flags
+    pub ride_through_status: Option<u16>,
+    /// Isolation resistance in ohms
+    pub isolation_resistance: Option<f64>,
+}
+
+impl Status {
+    const LENGTH: usize = 44;
+
+    fn decode(r: &Registers) -> Self {
+        Self {
+            pv_connection: r.uint16(0),
+            storage_connection: r.uint16(1),
+            ecp_connection: r.uint16(2),
+            active_energy: r.acc64(3),
+            apparent_energy: r.acc64(7),
+            reactive_energy: [11, 15, 19, 23].map(|i| r.acc64(i)),
+            available_reactive_power: scaled(r.int16(27), r.scale(28)),
+            available_power: scaled(r.uint16(29), r.scale(30)),
+            limit_mask: r.uint32(31),
+            active_controls: r.uint32(33),
+            time_source: r.string(35, 4),
+            timestamp: r.uint32(39),
+            ride_through_status: r.uint16(41),
+            isolation_resistance: scaled(r.uint16(42), r.scale(43)),
+        }
+    }
+}
+
+/// Model 123, immediate inverter controls
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Controls {
+    /// Time window for the connect/disconnect in seconds
+    pub connection_window: Option<u16>,
+    /// Timeout period for the connect/disconnect in seconds
+    pub connection_revert: Option<u16>,
+    /// Connection control, 0 for disconnected and 1 for connected
+    pub connection: Option<u16>,
+    /// Power output limit in percent of max power
+    pub power_limit: Option<f64>,
+    /// Time window for the power limit change in seconds
+    pub power_limit_window: Option<u16>,
+    /// Timeout period for the power limit in seconds
+    pub power_limit_revert: Option<u16>,
+    /// Ramp time for moving from the current setpoint to the new setpoint in seconds
+    pub power_limit_ramp: Option<u16>,
+    /// Throttle enable, 0 for disabled and 1 for enabled
+    pub power_limit_enabled: Option<u16>,
+    /// Power factor setpoint in cos()
+    pub power_factor: Option<f64>,
+    /// Time window for the power factor change in seconds
+    pub power_factor_window: Option<u16>,
+    /// Timeout period for the power factor in seconds
+    pub power_factor_revert: Option<u16>,
+    /// Ramp time for moving from the current setpoint to the new setpoint in seconds
+    pub power_factor_ramp: Option<u16>,
+    /// Fixed power factor enable, 0 for disabled and 1 for enabled
+    pub power_factor_enabled: Option<u16>,
+    /// Reactive power in percent of max power
+    pub reactive_power_of_max_power: Option<f64>,
+    /// Reactive power in percent of max reactive power
+    pub reactive_power_of_max_reactive_power: Option<f64>,
+    /// Reactive power in percent of available reactive power
+    pub reactive_power_of_available: Option<f64>,
+    /// Time window for the reactive power change in seconds
+    pub reactive_power_window: Option<u16>,
+    /// Timeout period for the reactive power in seconds
+    pub reactive_power_revert: Option<u16>,
+    /// Ramp time for moving from the current setpoint to the new setpoint in seconds
+    pub reactive_power_ramp: Option<u16>,
+    /// Reactive power mode, 1 for max power, 2 for max reactive power and 3 for available
+    pub reactive_power_mode: Option<u16>,
+    /// Fixed reactive power enable, 0 for disabled and 1 for enabled
+    pub reactive_power_enabled: Option<u16>,
+}
+
+impl Controls {
+    const LENGTH: usize = 24;
+
+    fn decode(r: &Registers) -> Self {
+        let var_sf = r.scale(23);
+
+        Self {
+            connection_window: r.uint16(0),
+            connection_revert: r.uint16(1),
+            connection: r.uint16(2),
+            power_limit: scaled(r.uint16(3), r.scale(21)),
+            power_limit_window: r.uint16(4),
+            power_limit_revert: r.uint16(5),
+            power_limit_ramp: r.uint16(6),
+            power_limit_enabled: r.uint16(7),
+            power_factor: scaled(r.int16(8), r.scale(22)),
+            power_factor_window: r.uint16(9),
+            power_factor_revert: r.uint16(10),
+            power_factor_ramp: r.uint16(11),
+            power_factor_enabled: r.uint16(12),
+            reactive_power_of_max_power: scaled(r.int16(13), var_sf),
+            reactive_power_of_max_reactive_power: scaled(r.int16(14), var_sf),
+            reactive_power_of_available: scaled(r.int16(15), var_sf),
+            reactive_power_window: r.uint16(16),
+            reactive_power_revert: r.uint16(17),
+            reactive_power_ramp: r.uint16(18),
+            reactive_power_mode: r.uint16(19),
+            reactive_power_enabled: r.uint16(20),
+        }
+    }
+}
+
+/// Model 124, basic storage controls
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Storage {
+    /// Setpoint for maximum charge in W
+    pub max_charge: Option<f64>,
+    /// Setpoint for maximum charging rate in percent of max charge per second
+    pub charge_gradient: Option<f64>,
+    /// Setpoint for maximum discharge rate in percent of max discharge per second
+    pub discharge_gradient: Option<f64>,
+    /// Active hold, discharge and charge modes
+    pub control_mode: Option<u16>,
+    /// Setpoint for maximum charging apparent power in VA
+    pub max_charge_apparent_power: Option<f64>,
+    /// Setpoint for minimum reserve as percent of nominal maximum storage
+    pub min_reserve: Option<f64>,
+    /// Currently available energy as percent of capacity rating
+    pub charge_state: Option<f64>,
+    /// State of charge minus reserve in Ah
+    pub available_storage: Option<f64>,
+    /// Internal battery voltage in V
+    pub battery_voltage: Option<f64>,
+    /// Charge status of the storage device
+    pub charge_status: Option<u16>,
+    /// Percent of max discharge rate
+    pub discharge_rate: Option<f64>,
+    /// Percent of max charging rate
+    pub charge_rate: Option<f64>,
+    /// Time window for the charge/discharge rate change in seconds
+    pub rate_window: Option<u16>,
+    /// Timeout period for the charge/discharge rate in seconds
+    pub rate_revert: Option<u16>,
+    /// Ramp time for moving from the current setpoint to the new setpoint in seconds
+    pub rate_ramp: Option<u16>,
+    /// Whether charging from the grid is allowed, 0 for PV only and 1 for grid
+    pub grid_charging: Option<u16>,
+}
+
+impl Storage {
+    const LENGTH: usize = 24;
+
+    fn decode(r: &Registers) -> Self {
+        let gra_sf = r.scale(17);
+        let rate_sf = r.scale(23);
+
+        Self {
+            max_charge: scaled(r.uint16(0), r.scale(16)),
+            charge_gradient: scaled(r.uint16(1), gra_sf),
+            discharge_gradient: scaled(r.uint16(2), gra_sf),
+            control_mode: r.uint16(3),
+            max_charge_apparent_power: scaled(r.uint16(4), r.scale(18)),
+            min_reserve: scaled(r.uint16(5), r.scale(19)),
+            charge_state: scaled(r.uint16(6), r.scale(20)),
+            available_storage: scaled(r.uint16(7), r.scale(21)),
+            battery_voltage: scaled(r.uint16(8), r.scale(22)),
+            charge_status: r.uint16(9),
+            discharge_rate: scaled(r.int16(10), rate_sf),
+            charge_rate: scaled(r.int16(11), rate_sf),
+            rate_window: r.uint16(12),
+            rate_revert: r.uint16(13),
+            rate_ramp: r.uint16(14),
+            grid_charging: r.uint16(15),
+        }
+    }
+}
+
+/// Model 160, multiple MPPT inverter extension
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mppt {
+    /// Global event flags
+    pub events: Option<u32>,
+    /// Timestamp period
+    pub timestamp_period: Option<u16>,
+    /// Measurements of every module
+    pub modules: Vec<MpptModule>,
+}
+
+/// Measurements of a single module of [`Mppt`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct MpptModule {
+    /// Input id
+    pub id: Option<u16>,
+    /// Input id string
+    pub name: Option<String>,
+    /// DC current in A
+    pub dc_current: Option<f64>,
+    /// DC voltage in V
+    pub dc_voltage: Option<f64>,
+    /// DC power in W
+    pub dc_power: Option<f64>,
+    /// Lifetime energy in Wh
+    pub dc_energy: Option<f64>,
+    /// Timestamp in seconds since 01-01-2000 00:00 UTC
+    pub timestamp: Option<u32>,
+    /// Temperature in °C
+    pub temperature: Option<i16>,
+    /// Operating state, same values as [`Inverter::state`]
+    pub state: Option<InverterState>,
+    /// Module event flags
+    pub events: Option<u32>,
+}
+
+impl Mppt {
+    const FIXED_LENGTH: usize = 8;
+    const MODULE_LENGTH: usize = 20;
+
+    fn decode(r: &Registers) -> Option<Self> {
+        let count = r.uint16(6).unwrap_or(0) as usize;
+        if r.0.len() < Self::FIXED_LENGTH + count * Self::MODULE_LENGTH {
+            return None;
+        }
+
+        let (a_sf, v_sf, w_sf, wh_sf) = (r.scale(0), r.scale(1), r.scale(2), r.scale(3));
+        let modules = (0..count)
+            .map(|i| {
+                let offset = Self::FIXED_LENGTH + i * Self::MODULE_LENGTH;
+                let m = Registers(&r.0[offset..offset + Self::MODULE_LENGTH]);
+                MpptModule {
+                    id: m.uint16(0),
+                    name: m.string(1, 8),
+                    dc_current: scaled(m.uint16(9), a_sf),
+                    dc_voltage: scaled(m.uint16(10), v_sf),
+                    dc_power: scaled(m.uint16(11), w_sf),
+                    dc_energy: scaled(m.acc32(12), wh_sf),
+                    timestamp: m.uint32(14),
+                    temperature: m.int16(16),
+                    state: m.uint16(17).map(InverterState::from),
+                    events: m.uint32(18),
+                }
+            })
+            .collect();
+
+        Some(Self {
+            events: r.uint32(4),
+            timestamp_period: r.uint16(7),
+            modules,
+        })
+    }
+}
+
+/// Model registers whose length was checked against the points being decoded
+///
+/// Every accessor returns `None` for the "not implemented" value of its SunSpec type.
+struct Registers<'a>(&'a [u16]);
+
+impl<'a> Registers<'a> {
+    fn uint16(&self, offset: usize) -> Option<u16> {
+        Some(self.0[offset]).filter(|x| *x != 0xFFFF)
+    }
+
+    fn int16(&self, offset: usize) -> Option<i16> {
+        Some(self.0[offset] as i16).filter(|x| *x != i16::MIN)
+    }
+
+    fn uint32(&self, offset: usize) -> Option<u32> {
+        let value = (self.0[offset] as u32) << 16 | self.0[offset + 1] as u32;
+        Some(value).filter(|x| *x != u32::MAX)
+    }
+
+    fn acc32(&self, offset: usize) -> Option<u32> {
+        let value = (self.0[offset] as u32) << 16 | self.0[offset + 1] as u32;
+        Some(value).filter(|x| *x != 0)
+    }
+
+    fn acc64(&self, offset: usize) -> Option<u64> {
+        let value = self.0[offset..offset + 4]
+            .iter()
+            .fold(0, |acc, x| acc << 16 | *x as u64);
+        Some(value).filter(|x| *x != 0)
+    }
+
+    fn scale(&self, offset: usize) -> Option<i32> {
+        self.int16(offset)
+            .map(i32::from)
+            .filter(|x| (-10..=10).contains(x))
+    }
+
+    fn string(&self, offset: usize, count: usize) -> Option<String> {
+        let registers = self.0[offset..offset + count]
+            .iter()
+            .map(|x| Indexed::new(0, *x));
+        decode_ascii(registers, WordOrder::Abcd)
+            .ok()
+            .filter(|x| !x.is_empty())
+    }
+}
+
+/// Apply a scale factor or return `None` if either the value or the scale factor is missing
+fn scaled<T: Into<f64>>(value: Option<T>, scale: Option<i32>) -> Option<f64> {
+    let value = value?.into();
+    let scale = scale?;
+    // dividing keeps values such as 123 * 10^-1 exact
+    if scale < 0 {
+        Some(value / 10f64.powi(-scale))
+    } else {
+        Some(value * 10f64.powi(scale))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(id: u16, length: usize) -> ModelHeader {
+        ModelHeader {
+            id,
+            address: 40004,
+            length: length as u16,
+        }
+    }
+
+    fn ascii(value: &str, count: usize) -> Vec<u16> {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.resize(2 * count, 0);
+        bytes
+            .chunks(2)
+            .map(|x| u16::from_be_bytes([x[0], x[1]]))
+            .collect()
+    }
+
+    #[test]
+    fn decodes_common_model() {
+        let mut registers = ascii("Acme", 16);
+        registers.extend(ascii("PV-1000", 16));
+        registers.extend([0; 8]);
+        registers.extend(ascii("1.2.3", 8));
+        registers.extend(ascii("SN42", 16));
+        registers.push(7);
+
+        let model = Model::decode(header(1, 65), &registers).unwrap();
+        assert_eq!(
+            model,
+            Model::Common(Common {
+                manufacturer: Some("Acme".to_string()),
+                model: Some("PV-1000".to_string()),
+                options: None,
+                version: Some("1.2.3".to_string()),
+                serial_number: Some("SN42".to_string()),
+                device_address: Some(7),
+            })
+        );
+    }
+
+    #[test]
+    fn applies_scale_factors_to_inverter_measurements() {
+        let mut registers = vec![0xFFFF; 50];
+        registers[0] = 123; // A
+        registers[4] = (-1i16) as u16; // A_SF
+        registers[12] = 2500; // W
+        registers[13] = 1; // W_SF
+        registers[22] = 0x0001; // WH
+        registers[23] = 0x0000;
+        registers[24] = 0; // WH_SF
+        registers[31] = (-5i16) as u16; // TmpCab
+        registers[35] = 0x8000; // Tmp_SF not implemented
+        registers[36] = 4; // St
+
+        let inverter = match Model::decode(header(103, 50), &registers).unwrap() {
+            Model::Inverter(x) => x,
+            x => panic!("unexpected model: {x:?}"),
+        };
+
+        assert_eq!(inverter.phases, Phases::Three);
+        assert_eq!(inverter.current, Some(12.3));
+        assert_eq!(inverter.phase_current, [None; 3]);
+        assert_eq!(inverter.power, Some(25000.0));
+        assert_eq!(inverter.energy, Some(65536.0));
+        assert_eq!(inverter.cabinet_temperature, None);
+        assert_eq!(inverter.state, Some(InverterState::Mppt));
+    }
+
+    #[test]
+    fn decodes_repeating_mppt_modules() {
+        let mut registers = vec![(-2i16) as u16, 0, 0, 0, 0, 0, 2, 0xFFFF];
+        for (id, current) in [(1, 150), (2, 275)] {
+            let mut module = vec![0xFFFF; 20];
+            module[0] = id;
+            module[1..9].copy_from_slice(&ascii("string", 8));
+            module[9] = current;
+            registers.extend(module);
+        }
+
+        let mppt = match Model::decode(header(160, registers.len()), &registers).unwrap() {
+            Model::Mppt(x) => x,
+            x => panic!("unexpected model: {x:?}"),
+        };
+
+        assert_eq!(mppt.modules.len(), 2);
+        assert_eq!(mppt.modules[1].id, Some(2));
+        assert_eq!(mppt.modules[1].name.as_deref(), Some("string"));
+        assert_eq!(mppt.modules[1].dc_current, Some(2.75));
+
+        // the module count must fit in the model length
+        assert_eq!(
+            Model::decode(header(160, 28), &registers[..28]),
+            Err(SunSpecError::BadModelLength(160, 28))
+        );
+    }
+
+    #[test]
+    fn unknown_models_are_returned_as_raw_registers() {
+        let registers = vec![1, 2, 3];
+        assert_eq!(
+            Model::decode(header(64001, 3), &registers),
+            Ok(Model::Unknown(RawModel {
+                header: header(64001, 3),
+                registers,
+            }))
+        );
+        assert_eq!(
+            Model::decode(header(101, 10), &[0; 10]),
+            Err(SunSpecError::BadModelLength(101, 10))
+        );
+    }
+}
@@ -47,7 +47,7 @@ pub(crate) fn create_tcp_channel(
         .instrument(tracing::info_span!("Modbus-Client-TCP", endpoint = ?host))
         .await;
     };
-    (Channel { tx }, task)
+    (Channel::new(tx), task)
 }
 
 pub(crate) enum TcpTaskConnectionHandler {
@@ -1,8 +1,10 @@
 use crate::common::buffer::ReadBuffer;
 use crate::common::frame::{Frame, FrameHeader, FrameInfo, FrameType, FunctionField, TxId};
+use crate::common::structured;
 use crate::common::traits::Serialize;
-use crate::decode::FrameDecodeLevel;
+use crate::decode::{DecodeSettings, FrameDecodeLevel};
 use crate::error::{FrameParseError, RequestError};
+use crate::observer::Direction;
 use crate::types::UnitId;
 
 use scursor::WriteCursor;
@@ -17,9 +19,9 @@ pub(crate) mod constants {
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub(crate) struct MbapHeader {
-    tx_id: TxId,
-    len_field: u16,
-    unit_id: UnitId,
+    pub(crate) tx_id: TxId,
+    pub(crate) len_field: u16,
+    pub(crate) unit_id: UnitId,
 }
 
 #[derive(Clone, Copy)]
@@ -87,7 +89,7 @@ impl MbapParser {
     pub(crate) fn parse(
         &mut self,
         cursor: &mut ReadBuffer,
-        decode_level: FrameDecodeLevel,
+        decode_level: DecodeSettings,
     ) -> Result<Option<Frame>, RequestError> {
         match self.state {
             ParseState::Header(header, adu_length) => {
@@ -98,11 +100,20 @@ impl MbapParser {
                 let frame = Self::parse_body(&header, adu_length, cursor)?;
                 self.state = ParseState::Begin;
 
-                if decode_level.enabled() {
-                    tracing::info!(
-                        "MBAP RX - {}",
-                        MbapDisplay::new(decode_level, header, frame.payload())
-                    );
+                if decode_level.frame.enabled() {
+                    if decode_level.is_structured() {
+                        structured::mbap(
+                            Direction::Rx,
+                            decode_level.frame,
+                            header,
+                            frame.payload(),
+                        );
+                    } else {
+                        tracing::info!(
+                            "MBAP RX - {}",
+                            MbapDisplay::new(decode_level.frame, header, frame.payload())
+                        );
+                    }
                 }
 
                 Ok(Some(frame))
@@ -206,8 +217,8 @@ mod tests {
 
     use crate::common::frame::{FrameDestination, FramedReader};
     use crate::common::function::FunctionCode;
+    use crate::decode::DecodeSettings;
     use crate::error::*;
-    use crate::DecodeLevel;
 
     use super::*;
 
@@ -242,7 +253,7 @@ mod tests {
         let mut reader = FramedReader::tcp();
         let mut layer = PhysLayer::new_mock(io);
         let mut task =
-            tokio_test::task::spawn(reader.next_frame(&mut layer, DecodeLevel::nothing()));
+            tokio_test::task::spawn(reader.next_frame(&mut layer, DecodeSettings::default()));
 
         assert!(task.poll().is_pending());
         io_handle.read(f1);
@@ -260,7 +271,7 @@ mod tests {
         let mut reader = FramedReader::tcp();
         let mut layer = PhysLayer::new_mock(io);
         let mut task =
-            tokio_test::task::spawn(reader.next_frame(&mut layer, DecodeLevel::nothing()));
+            tokio_test::task::spawn(reader.next_frame(&mut layer, DecodeSettings::default()));
 
         io_handle.read(input);
         if let Poll::Ready(frame) = task.poll() {
@@ -294,11 +305,13 @@ mod tests {
         let mut reader = FramedReader::tcp();
         let mut layer = PhysLayer::new_mock(io);
         let mut task =
-            tokio_test::task::spawn(reader.next_frame(&mut layer, DecodeLevel::nothing()));
+            tokio_test::task::spawn(reader.next_frame(&mut layer, DecodeSettings::default()));
 
         io_handle.read(SIMPLE_FRAME);
         if let Poll::Ready(frame) = task.poll() {
-            assert_equals_simple_frame(&frame.unwrap());
+            let frame = frame.unwrap();
+            assert_equals_simple_frame(&frame);
+            assert_eq!(frame.to_adu(), SIMPLE_FRAME);
         } else {
             panic!("Task not ready");
         }
@@ -315,7 +328,7 @@ mod tests {
         let mut task = tokio_test::task::spawn(async {
             assert_eq!(
                 reader
-                    .next_frame(&mut PhysLayer::new_mock(io), DecodeLevel::nothing())
+                    .next_frame(&mut PhysLayer::new_mock(io), DecodeSettings::default())
                     .await
                     .unwrap()
                     .payload(),
@@ -1,27 +1,40 @@
 use std::collections::BTreeMap;
+use std::time::Duration;
 
 use tracing::Instrument;
 
 use crate::common::frame::{FrameWriter, FramedReader};
 use crate::common::phys::PhysLayer;
-use crate::decode::DecodeLevel;
-use crate::server::handler::{RequestHandler, ServerHandlerMap};
+use crate::decode::{DecodeLevel, DecodeSettings};
+use crate::observer::TrafficObserver;
+use crate::server::handler::{AsyncRequestHandler, SharedHandlerMap};
+use crate::server::session::Sessions;
 use crate::server::task::{AuthorizationType, ServerSetting};
 
-use crate::server::AddressFilter;
+use crate::server::{AddressFilter, AuthorizationHandler, RoleProvider};
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tokio::net::TcpListener;
 
-#[cfg(feature = "tls")]
-use crate::server::AuthorizationHandler;
-
 /// event sent back to the server task when a session ends
-struct SessionClose(u128);
+struct SessionClose(u64);
+
+/// Commands sent by the [`ServerHandle`](crate::server::ServerHandle) that only concern the
+/// server task and are not forwarded to the sessions
+pub(crate) enum ServerControl {
+    Disconnect(u64),
+    ChangeAddressFilter(AddressFilter, bool),
+}
+
+struct SessionRecord {
+    addr: SocketAddr,
+    sender: tokio::sync::mpsc::Sender<ServerSetting>,
+}
 
 struct SessionTracker {
     max_sessions: usize,
-    id: u128,
-    sessions: BTreeMap<u128, tokio::sync::mpsc::Sender<ServerSetting>>,
+    id: u64,
+    sessions: BTreeMap<u64, SessionRecord>,
 }
 
 impl SessionTracker {
@@ -39,13 +52,17 @@ impl SessionTracker {
         }
     }
 
-    fn get_next_id(&mut self) -> u128 {
+    fn get_next_id(&mut self) -> u64 {
         let ret = self.id;
         self.id += 1;
         ret
     }
 
-    pub(crate) fn add(&mut self, sender: tokio::sync::mpsc::Sender<ServerSetting>) -> u128 {
+    pub(crate) fn add(
+        &mut self,
+        addr: SocketAddr,
+        sender: tokio::sync::mpsc::Sender<ServerSetting>,
+    ) -> u64 {
         if self.sessions.len() >= self.max_sessions {
             if let Some(oldest) = self.sessions.keys().next().copied() {
                 tracing::warn!(
@@ -59,22 +76,38 @@ impl SessionTracker {
         }
 
         let id = self.get_next_id();
-        self.sessions.insert(id, sender);
+        self.sessions.insert(id, SessionRecord { addr, sender });
         id
     }
 
-    pub(crate) fn remove(&mut self, id: u128) {
+    pub(crate) fn remove(&mut self, id: u64) {
         self.sessions.remove(&id);
     }
+
+    /// remove the sessions whose address doesn't match the filter
+    pub(crate) fn retain(&mut self, filter: &AddressFilter) {
+        self.sessions.retain(|id, record| {
+            let matches = filter.matches(record.addr.ip());
+            if !matches {
+                tracing::warn!(
+                    "IP address {:?} does not match the new filter, closing session: {}",
+                    record.addr.ip(),
+                    id
+                );
+            }
+            matches
+        });
+    }
 }
 
 #[derive(Clone)]
 pub(crate) enum TcpServerConnectionHandler {
     Tcp,
+    TcpWithAuthz(Arc<dyn AuthorizationHandler>, Arc<dyn RoleProvider>),
     #[cfg(feature = "tls")]
     Tls(
         crate::tcp::tls::TlsServerConfig,
-        Option<std::sync::Arc<dyn AuthorizationHandler>>,
+        Option<Arc<dyn AuthorizationHandler>>,
     ),
 }
 
@@ -82,9 +115,20 @@ impl TcpServerConnectionHandler {
     async fn handle(
         &mut self,
         socket: tokio::net::TcpStream,
+        addr: SocketAddr,
     ) -> Result<(PhysLayer, AuthorizationType), String> {
         match self {
             Self::Tcp => Ok((PhysLayer::new_tcp(socket), AuthorizationType::None)),
+            Self::TcpWithAuthz(auth_handler, roles) => {
+                let role = roles
+                    .role(addr.ip())
+                    .ok_or_else(|| format!("no role for IP address {}", addr.ip()))?;
+                tracing::info!("client role: {}", role);
+                Ok((
+                    PhysLayer::new_tcp(socket),
+                    AuthorizationType::Handler(auth_handler.clone(), role.into()),
+                ))
+            }
             #[cfg(feature = "tls")]
             Self::Tls(config, auth_handler) => {
                 let res = config.handle_connection(socket, auth_handler.clone()).await;
@@ -97,28 +141,32 @@ impl TcpServerConnectionHandler {
     }
 }
 
-pub(crate) struct ServerTask<T: RequestHandler> {
+pub(crate) struct ServerTask<T: AsyncRequestHandler> {
     listener: TcpListener,
-    handlers: ServerHandlerMap<T>,
+    handlers: SharedHandlerMap<T>,
     tracker: SessionTracker,
     connection_handler: TcpServerConnectionHandler,
     filter: AddressFilter,
-    decode: DecodeLevel,
+    decode: DecodeSettings,
+    observer: Option<Arc<dyn TrafficObserver>>,
+    timeout: Duration,
+    sessions: Sessions,
     tx: tokio::sync::mpsc::Sender<SessionClose>,
     rx: tokio::sync::mpsc::Receiver<SessionClose>,
 }
 
 impl<T> ServerTask<T>
 where
-    T: RequestHandler,
+    T: AsyncRequestHandler,
 {
     pub(crate) fn new(
         max_sessions: usize,
         listener: TcpListener,
-        handlers: ServerHandlerMap<T>,
+        handlers: SharedHandlerMap<T>,
         connection_handler: TcpServerConnectionHandler,
         filter: AddressFilter,
         decode: DecodeLevel,
+        sessions: Sessions,
     ) -> Self {
         let (tx, rx) = tokio::sync::mpsc::channel(8);
 
@@ -128,7 +176,10 @@ where
             tracker: SessionTracker::new(max_sessions),
             connection_handler,
             filter,
-            decode,
+            decode: decode.into(),
+            observer: None,
+            timeout: crate::server::DEFAULT_REQUEST_TIMEOUT,
+            sessions,
             tx,
             rx,
         }
@@ -136,23 +187,58 @@ where
 
     async fn change_setting(&mut self, setting: ServerSetting) {
         // first, change it locally so that it is applied to new sessions
-        match setting {
+        match &setting {
             ServerSetting::ChangeDecoding(level) => {
                 tracing::info!("changed decoding level to {:?}", level);
-                self.decode = level;
+                self.decode.set_level(*level);
+            }
+            ServerSetting::ChangeDecodeFormat(format) => {
+                tracing::info!("changed decoding format to {:?}", format);
+                self.decode.format = *format;
+            }
+            ServerSetting::ChangeObserver(observer) => {
+                tracing::info!("changed traffic observer");
+                self.observer = observer.clone();
+            }
+            ServerSetting::ChangeRequestTimeout(timeout) => {
+                tracing::info!("changed request timeout to {:?}", timeout);
+                self.timeout = *timeout;
             }
         }
 
-        for sender in self.tracker.sessions.values_mut() {
+        for record in self.tracker.sessions.values_mut() {
             // best effort to send the setting to each session this isn't critical so we wouldn't
             // want to slow the server down by awaiting it
-            let _ = sender.send(setting).await;
+            let _ = record.sender.send(setting.clone()).await;
         }
     }
 
-    pub(crate) async fn run(&mut self, mut commands: tokio::sync::mpsc::Receiver<ServerSetting>) {
+    fn control(&mut self, control: ServerControl) {
+        match control {
+            ServerControl::Disconnect(id) => {
+                tracing::info!("disconnecting session: {}", id);
+                // the session task stops when its sender is dropped
+                self.tracker.remove(id);
+            }
+            ServerControl::ChangeAddressFilter(filter, close_mismatched) => {
+                tracing::info!("changed address filter to {:?}", filter);
+                if close_mismatched {
+                    self.tracker.retain(&filter);
+                }
+                self.filter = filter;
+            }
+        }
+    }
+
+    pub(crate) async fn run(
+        &mut self,
+        mut commands: tokio::sync::mpsc::Receiver<ServerSetting>,
+        mut control: tokio::sync::mpsc::Receiver<ServerControl>,
+    ) {
         loop {
             tokio::select! {
+               // the sender is owned by the handle along with the sender of the settings
+               Some(control) = control.recv() => self.control(control),
                setting = commands.recv() => {
                     match setting {
                         Some(setting) => self.change_setting(setting).await,
@@ -192,7 +278,7 @@ where
 
     async fn handle(&mut self, socket: tokio::net::TcpStream, addr: SocketAddr) {
         let (tx, rx) = tokio::sync::mpsc::channel(8); // all we do is change settings, so a constant is fine
-        let id = self.tracker.add(tx);
+        let id = self.tracker.add(addr, tx);
         tracing::info!(
             "accepted connection from: {} - assigned session id: {}",
             addr,
@@ -204,6 +290,9 @@ where
         let connection_handler = self.connection_handler.clone();
         let handler_map = self.handlers.clone();
         let decode_level = self.decode;
+        let observer = self.observer.clone();
+        let timeout = self.timeout;
+        let sessions = self.sessions.clone();
 
         let session = async move {
             run_session(
@@ -213,6 +302,10 @@ where
                 decode_level,
                 handler_map,
                 rx,
+                id,
+                observer,
+                timeout,
+                sessions,
             )
             .await;
 
@@ -230,19 +323,26 @@ where
     }
 }
 
-async fn run_session<T: RequestHandler>(
+#[allow(clippy::too_many_arguments)]
+async fn run_session<T: AsyncRequestHandler>(
     socket: tokio::net::TcpStream,
     addr: SocketAddr,
     mut handler: TcpServerConnectionHandler,
-    decode: DecodeLevel,
-    handlers: ServerHandlerMap<T>,
+    decode: DecodeSettings,
+    handlers: SharedHandlerMap<T>,
     commands: tokio::sync::mpsc::Receiver<ServerSetting>,
+    id: u64,
+    observer: Option<Arc<dyn TrafficObserver>>,
+    timeout: Duration,
+    sessions: Sessions,
 ) {
-    match handler.handle(socket).await {
+    match handler.handle(socket, addr).await {
         Err(err) => {
             tracing::warn!("error from {}: {}", addr, err);
         }
         Ok((mut phys, auth)) => {
+            let registration =
+                sessions.register(id, addr, auth.role().map(|role| role.to_string()));
             let _ = crate::server::task::SessionTask::new(
                 handlers,
                 auth,
@@ -251,6 +351,9 @@ async fn run_session<T: RequestHandler>(
                 commands,
                 decode,
             )
+            .with_observer(id, observer)
+            .with_request_timeout(timeout)
+            .with_counters(registration.counters())
             .run(&mut phys)
             .await;
         }
@@ -64,7 +64,7 @@ pub(crate) fn create_tls_channel(
         .instrument(tracing::info_span!("Modbus-Client-TCP", endpoint = ?host))
         .await;
     };
-    (Channel { tx }, task)
+    (Channel::new(tx), task)
 }
 
 impl TlsClientConfig {
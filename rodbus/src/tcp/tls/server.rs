@@ -79,7 +79,7 @@ impl TlsServerConfig {
                         let role = extract_modbus_role(&parsed)?;
 
                         tracing::info!("client role: {}", role);
-                        AuthorizationType::Handler(handler, role)
+                        AuthorizationType::Handler(handler, role.into())
                     }
                 };
 
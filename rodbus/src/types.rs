@@ -196,6 +196,19 @@ pub struct Indexed<T> {
     pub value: T,
 }
 
+/// One of the four primary tables of the Modbus data model
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Table {
+    /// Single bit, read/write
+    Coils,
+    /// Single bit, read-only
+    DiscreteInputs,
+    /// 16-bit register, read/write
+    HoldingRegisters,
+    /// 16-bit register, read-only
+    InputRegisters,
+}
+
 /// Zero-copy type used to iterate over a collection of bits
 #[derive(Debug, Copy, Clone)]
 pub struct BitIterator<'a> {
@@ -228,6 +241,29 @@ impl std::fmt::Display for UnitId {
     }
 }
 
+impl Table {
+    /// Returns true if the table contains single bit values
+    pub fn is_bit(self) -> bool {
+        matches!(self, Table::Coils | Table::DiscreteInputs)
+    }
+
+    /// Returns true if the table contains 16-bit register values
+    pub fn is_register(self) -> bool {
+        !self.is_bit()
+    }
+}
+
+impl std::fmt::Display for Table {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Table::Coils => f.write_str("coils"),
+            Table::DiscreteInputs => f.write_str("discrete inputs"),
+            Table::HoldingRegisters => f.write_str("holding registers"),
+            Table::InputRegisters => f.write_str("input registers"),
+        }
+    }
+}
+
 impl<'a> BitIterator<'a> {
     pub(crate) fn parse_all(
         range: AddressRange,
@@ -402,7 +438,8 @@ impl AddressRange {
         start..end
     }
 
-    pub(crate) fn iter(&self) -> impl Iterator<Item = u16> {
+    /// Iterate over the addresses in the range
+    pub fn iter(&self) -> impl Iterator<Item = u16> {
         AddressIterator::new(self.start, self.count)
     }
 
@@ -1,5 +1,6 @@
 use std::net::SocketAddr;
 use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
 
 use rodbus::client::*;
@@ -229,3 +230,892 @@ fn can_read_and_write_values() {
     let rt = Runtime::new().unwrap();
     rt.block_on(test_requests_and_responses())
 }
+
+#[test]
+fn can_read_and_write_values_with_blocking_channel() {
+    let rt = Runtime::new().unwrap();
+    let handler = Handler::new().wrap();
+    let addr = SocketAddr::from_str("127.0.0.1:40001").unwrap();
+
+    let _server = rt
+        .block_on(spawn_tcp_server_task(
+            1,
+            addr,
+            ServerHandlerMap::single(UnitId::new(1), handler.clone()),
+            AddressFilter::Any,
+            DecodeLevel::default(),
+        ))
+        .unwrap();
+
+    let mut channel = blocking::BlockingChannel::spawn_tcp(
+        HostAddr::ip(addr.ip(), addr.port()),
+        10,
+        default_retry_strategy(),
+        DecodeLevel::default(),
+        None,
+    )
+    .unwrap();
+
+    channel.enable().unwrap();
+
+    let params = RequestParam::new(UnitId::new(0x01), Duration::from_secs(1));
+
+    assert_eq!(
+        channel
+            .write_multiple_registers_verified(
+                params,
+                WriteMultiple::from(2, vec![0x0102, 0x0304]).unwrap()
+            )
+            .unwrap(),
+        AddressRange::try_from(2, 2).unwrap()
+    );
+    assert_eq!(
+        channel.read_u32(params, 2, WordOrder::Abcd).unwrap(),
+        0x01020304
+    );
+    assert_eq!(handler.lock().unwrap().holding_registers[3], 0x0304);
+}
+
+struct DelayedHandler {
+    holding_registers: std::sync::Arc<std::sync::Mutex<Vec<u16>>>,
+    delay: Duration,
+}
+
+impl AsyncRequestHandler for DelayedHandler {
+    fn read_holding_registers(
+        &self,
+        _context: &RequestContext,
+        range: AddressRange,
+    ) -> MaybeAsync<Result<Vec<u16>, ExceptionCode>> {
+        let registers = self.holding_registers.clone();
+        let delay = self.delay;
+        MaybeAsync::asynchronous(async move {
+            tokio::time::sleep(delay).await;
+            let registers = registers.lock().unwrap();
+            range
+                .iter()
+                .map(|i| {
+                    registers
+                        .get(i as usize)
+                        .copied()
+                        .ok_or(ExceptionCode::IllegalDataAddress)
+                })
+                .collect()
+        })
+    }
+
+    fn write_register(
+        &mut self,
+        _context: &RequestContext,
+        value: Indexed<u16>,
+    ) -> MaybeAsync<Result<(), ExceptionCode>> {
+        let registers = self.holding_registers.clone();
+        MaybeAsync::asynchronous(async move {
+            match registers.lock().unwrap().get_mut(value.index as usize) {
+                Some(x) => {
+                    *x = value.value;
+                    Ok(())
+                }
+                None => Err(ExceptionCode::IllegalDataAddress),
+            }
+        })
+    }
+}
+
+#[test]
+fn async_handler_completes_requests_and_times_out() {
+    let rt = Runtime::new().unwrap();
+    let addr = SocketAddr::from_str("127.0.0.1:40002").unwrap();
+    let handler = DelayedHandler {
+        holding_registers: std::sync::Arc::new(std::sync::Mutex::new(vec![0; 4])),
+        delay: Duration::from_millis(200),
+    };
+
+    rt.block_on(async {
+        let mut server = spawn_tcp_server_task(
+            1,
+            addr,
+            ServerHandlerMap::single(
+                UnitId::new(1),
+                std::sync::Arc::new(std::sync::Mutex::new(Box::new(handler))),
+            ),
+            AddressFilter::Any,
+            DecodeLevel::default(),
+        )
+        .await
+        .unwrap();
+
+        let mut channel = spawn_tcp_client_task(
+            HostAddr::ip(addr.ip(), addr.port()),
+            10,
+            default_retry_strategy(),
+            DecodeLevel::default(),
+            None,
+        );
+        channel.enable().await.unwrap();
+
+        let params = RequestParam::new(UnitId::new(0x01), Duration::from_secs(1));
+
+        assert_eq!(
+            channel
+                .write_single_register(params, Indexed::new(1, 0xCAFE))
+                .await
+                .unwrap(),
+            Indexed::new(1, 0xCAFE)
+        );
+        assert_eq!(
+            channel
+                .read_holding_registers(params, AddressRange::try_from(0, 2).unwrap())
+                .await
+                .unwrap(),
+            vec![Indexed::new(0, 0), Indexed::new(1, 0xCAFE)]
+        );
+
+        // the handler is now slower than the server allows
+        server
+            .set_request_timeout(Duration::from_millis(50))
+            .await
+            .unwrap();
+        assert_eq!(
+            channel
+                .read_holding_registers(params, AddressRange::try_from(0, 2).unwrap())
+                .await,
+            Err(RequestError::Exception(ExceptionCode::ServerDeviceFailure))
+        );
+    });
+}
+
+#[test]
+fn gateway_forwards_requests_to_mapped_targets() {
+    let rt = Runtime::new().unwrap();
+    let device_addr = SocketAddr::from_str("127.0.0.1:40003").unwrap();
+    let gateway_addr = SocketAddr::from_str("127.0.0.1:40004").unwrap();
+
+    let database = Database::new();
+    database.transaction(|tables| {
+        tables
+            .holding_registers
+            .add_dense(0, vec![1, 2, 3])
+            .unwrap();
+        tables.coils.add_dense(0, vec![false; 4]).unwrap();
+    });
+
+    rt.block_on(async {
+        let _device = spawn_tcp_server_task(
+            1,
+            device_addr,
+            ServerHandlerMap::single(UnitId::new(1), database.clone().wrap()),
+            AddressFilter::Any,
+            DecodeLevel::default(),
+        )
+        .await
+        .unwrap();
+
+        let device = spawn_tcp_client_task(
+            HostAddr::ip(device_addr.ip(), device_addr.port()),
+            10,
+            default_retry_strategy(),
+            DecodeLevel::default(),
+            None,
+        );
+        device.enable().await.unwrap();
+
+        let mut map = GatewayMap::new();
+        // unit id 5 of the gateway is remapped to unit id 1 of the device
+        map.add(
+            UnitId::new(5),
+            GatewayTarget::new(device.clone(), UnitId::new(1), Duration::from_secs(1)),
+        );
+        // the device does not answer for unit id 2
+        map.add(
+            UnitId::new(6),
+            GatewayTarget::new(device, UnitId::new(2), Duration::from_millis(100)),
+        );
+        let _gateway = spawn_tcp_gateway_task(
+            1,
+            gateway_addr,
+            map,
+            AddressFilter::Any,
+            DecodeLevel::default(),
+        )
+        .await
+        .unwrap();
+
+        let mut channel = spawn_tcp_client_task(
+            HostAddr::ip(gateway_addr.ip(), gateway_addr.port()),
+            10,
+            default_retry_strategy(),
+            DecodeLevel::default(),
+            None,
+        );
+        channel.enable().await.unwrap();
+
+        let params = RequestParam::new(UnitId::new(5), Duration::from_secs(1));
+        assert_eq!(
+            channel
+                .read_holding_registers(params, AddressRange::try_from(1, 2).unwrap())
+                .await
+                .unwrap(),
+            vec![Indexed::new(1, 2), Indexed::new(2, 3)]
+        );
+        channel
+            .write_multiple_coils(params, WriteMultiple::from(1, vec![true, true]).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(database.read(|tables| tables.coils.get(2)), Some(true));
+
+        // exceptions from the device are passed through
+        assert_eq!(
+            channel
+                .read_holding_registers(params, AddressRange::try_from(3, 1).unwrap())
+                .await,
+            Err(RequestError::Exception(ExceptionCode::IllegalDataAddress))
+        );
+        assert_eq!(
+            channel
+                .read_coils(
+                    RequestParam::new(UnitId::new(6), Duration::from_secs(1)),
+                    AddressRange::try_from(0, 1).unwrap()
+                )
+                .await,
+            Err(RequestError::Exception(
+                ExceptionCode::GatewayTargetDeviceFailedToRespond
+            ))
+        );
+        assert_eq!(
+            channel
+                .read_coils(
+                    RequestParam::new(UnitId::new(7), Duration::from_secs(1)),
+                    AddressRange::try_from(0, 1).unwrap()
+                )
+                .await,
+            Err(RequestError::Exception(
+                ExceptionCode::GatewayPathUnavailable
+            ))
+        );
+    });
+}
+
+#[test]
+fn handlers_receive_the_context_of_requests() {
+    let rt = Runtime::new().unwrap();
+    let addr = SocketAddr::from_str("127.0.0.1:40005").unwrap();
+
+    let database = Database::new();
+    database.transaction(|tables| tables.holding_registers.add_sparse(7, 0).unwrap());
+    let mut events = database.subscribe(WriteFilter::all(), 16);
+
+    rt.block_on(async {
+        let _server = spawn_tcp_server_task(
+            1,
+            addr,
+            ServerHandlerMap::single(UnitId::new(3), database.wrap()),
+            AddressFilter::Any,
+            DecodeLevel::default(),
+        )
+        .await
+        .unwrap();
+
+        let mut channel = spawn_tcp_client_task(
+            HostAddr::ip(addr.ip(), addr.port()),
+            10,
+            default_retry_strategy(),
+            DecodeLevel::default(),
+            None,
+        );
+        channel.enable().await.unwrap();
+
+        let params = RequestParam::new(UnitId::new(3), Duration::from_secs(1));
+        channel
+            .write_single_register(params, Indexed::new(7, 42))
+            .await
+            .unwrap();
+
+        let event = events.recv().await.unwrap();
+        let context = event.origin.unwrap();
+        assert_eq!(context.unit_id, UnitId::new(3));
+        assert_eq!(context.remote.map(|x| x.ip()), Some(addr.ip()));
+        assert!(context.tx_id.is_some());
+        assert_eq!(context.role, None);
+        assert_eq!(
+            event.values,
+            WrittenValues::Registers {
+                old: vec![0],
+                new: vec![42],
+            }
+        );
+    });
+}
+
+#[test]
+fn units_can_be_changed_while_sessions_are_active() {
+    let rt = Runtime::new().unwrap();
+    let addr = SocketAddr::from_str("127.0.0.1:40006").unwrap();
+
+    let database = |value: u16| {
+        let database = Database::new();
+        database.transaction(|tables| tables.holding_registers.add_sparse(0, value).unwrap());
+        database.wrap()
+    };
+
+    rt.block_on(async {
+        let server = spawn_tcp_server_task(
+            1,
+            addr,
+            ServerHandlerMap::single(UnitId::new(1), database(1)),
+            AddressFilter::Any,
+            DecodeLevel::default(),
+        )
+        .await
+        .unwrap();
+
+        let mut channel = spawn_tcp_client_task(
+            HostAddr::ip(addr.ip(), addr.port()),
+            10,
+            default_retry_strategy(),
+            DecodeLevel::default(),
+            None,
+        );
+        channel.enable().await.unwrap();
+
+        let range = AddressRange::try_from(0, 1).unwrap();
+        let params = RequestParam::new(UnitId::new(2), Duration::from_millis(200));
+
+        // make sure the session is established before changing the units
+        assert_eq!(
+            channel
+                .read_holding_registers(
+                    RequestParam::new(UnitId::new(1), Duration::from_secs(1)),
+                    range
+                )
+                .await,
+            Ok(vec![Indexed::new(0, 1)])
+        );
+        assert_eq!(
+            channel.read_holding_registers(params, range).await,
+            Err(RequestError::ResponseTimeout)
+        );
+
+        server.add_unit(UnitId::new(2), database(2)).unwrap();
+        assert_eq!(
+            server.add_unit(UnitId::new(2), database(3)),
+            Err(UnitError::AlreadyExists(UnitId::new(2)))
+        );
+        assert_eq!(
+            channel.read_holding_registers(params, range).await,
+            Ok(vec![Indexed::new(0, 2)])
+        );
+
+        server.replace_unit(UnitId::new(2), database(3)).unwrap();
+        assert_eq!(
+            channel.read_holding_registers(params, range).await,
+            Ok(vec![Indexed::new(0, 3)])
+        );
+
+        server.remove_unit(UnitId::new(2)).unwrap();
+        assert_eq!(
+            server.remove_unit(UnitId::new(2)),
+            Err(UnitError::NotFound(UnitId::new(2)))
+        );
+        assert_eq!(
+            channel.read_holding_registers(params, range).await,
+            Err(RequestError::ResponseTimeout)
+        );
+
+        // the server was spawned with a different type of handler
+        assert_eq!(
+            server.add_unit(UnitId::new(4), Handler::new().wrap()),
+            Err(UnitError::WrongHandlerType)
+        );
+    });
+}
+
+struct SessionEvents {
+    tx: tokio::sync::mpsc::UnboundedSender<(bool, SessionInfo)>,
+}
+
+impl SessionListener for SessionEvents {
+    fn on_connect(&self, session: &SessionInfo) {
+        let _ = self.tx.send((true, session.clone()));
+    }
+
+    fn on_disconnect(&self, session: &SessionInfo) {
+        let _ = self.tx.send((false, session.clone()));
+    }
+}
+
+#[test]
+fn sessions_can_be_listed_and_disconnected() {
+    let rt = Runtime::new().unwrap();
+    let addr = SocketAddr::from_str("127.0.0.1:40007").unwrap();
+
+    let (tx, mut events) = tokio::sync::mpsc::unbounded_channel();
+
+    rt.block_on(async {
+        let mut server = spawn_tcp_server_task(
+            1,
+            addr,
+            ServerHandlerMap::single(UnitId::new(1), Handler::new().wrap()),
+            AddressFilter::Any,
+            DecodeLevel::default(),
+        )
+        .await
+        .unwrap();
+        server.set_session_listener(Some(Arc::new(SessionEvents { tx })));
+
+        let mut channel = spawn_tcp_client_task(
+            HostAddr::ip(addr.ip(), addr.port()),
+            10,
+            default_retry_strategy(),
+            DecodeLevel::default(),
+            None,
+        );
+        channel.enable().await.unwrap();
+
+        let range = AddressRange::try_from(0, 1).unwrap();
+        channel
+            .read_coils(
+                RequestParam::new(UnitId::new(1), Duration::from_secs(1)),
+                range,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            channel
+                .read_coils(
+                    RequestParam::new(UnitId::new(1), Duration::from_secs(1)),
+                    AddressRange::try_from(20, 1).unwrap()
+                )
+                .await,
+            Err(RequestError::Exception(ExceptionCode::IllegalDataAddress))
+        );
+
+        let (connected, session) = events.recv().await.unwrap();
+        assert!(connected);
+        assert_eq!(session.remote.ip(), addr.ip());
+        assert_eq!(session.role, None);
+
+        let sessions = server.sessions();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, session.id);
+        assert_eq!(sessions[0].connected_at, session.connected_at);
+        assert_eq!(
+            sessions[0].counters,
+            SessionCounters {
+                requests: 2,
+                rejected: 0,
+                unmapped: 0,
+            }
+        );
+
+        assert!(server.disconnect(session.id).await.unwrap());
+        let (connected, closed) = events.recv().await.unwrap();
+        assert!(!connected);
+        assert_eq!(closed.id, session.id);
+        assert_eq!(closed.counters.requests, 2);
+        assert!(server.sessions().iter().all(|x| x.id != session.id));
+        assert!(!server.disconnect(session.id).await.unwrap());
+    });
+}
+
+#[test]
+fn sessions_that_no_longer_match_the_address_filter_are_closed() {
+    let rt = Runtime::new().unwrap();
+    let addr = SocketAddr::from_str("127.0.0.1:40008").unwrap();
+
+    let (tx, mut events) = tokio::sync::mpsc::unbounded_channel();
+
+    rt.block_on(async {
+        let mut server = spawn_tcp_server_task(
+            1,
+            addr,
+            ServerHandlerMap::single(UnitId::new(1), Handler::new().wrap()),
+            AddressFilter::Cidr("127.0.0.0/8".parse().unwrap()),
+            DecodeLevel::default(),
+        )
+        .await
+        .unwrap();
+        server.set_session_listener(Some(Arc::new(SessionEvents { tx })));
+
+        let mut channel = spawn_tcp_client_task(
+            HostAddr::ip(addr.ip(), addr.port()),
+            10,
+            default_retry_strategy(),
+            DecodeLevel::default(),
+            None,
+        );
+        channel.enable().await.unwrap();
+
+        let params = RequestParam::new(UnitId::new(1), Duration::from_secs(1));
+        let range = AddressRange::try_from(0, 1).unwrap();
+        channel.read_coils(params, range).await.unwrap();
+        let (connected, session) = events.recv().await.unwrap();
+        assert!(connected);
+
+        // the session still matches
+        server
+            .set_address_filter(
+                AddressFilter::Rules(vec![
+                    FilterRule::Deny(AddressFilter::Cidr("10.0.0.0/8".parse().unwrap())),
+                    FilterRule::Allow(AddressFilter::Any),
+                ]),
+                true,
+            )
+            .await
+            .unwrap();
+        channel.read_coils(params, range).await.unwrap();
+        assert_eq!(server.sessions().len(), 1);
+
+        server
+            .set_address_filter(
+                AddressFilter::Rules(vec![
+                    FilterRule::Deny(AddressFilter::Exact(addr.ip())),
+                    FilterRule::Allow(AddressFilter::Any),
+                ]),
+                true,
+            )
+            .await
+            .unwrap();
+        let (connected, closed) = events.recv().await.unwrap();
+        assert!(!connected);
+        assert_eq!(closed.id, session.id);
+        assert!(channel.read_coils(params, range).await.is_err());
+        assert!(server.sessions().is_empty());
+    });
+}
+
+#[test]
+fn plain_tcp_server_authorizes_requests_using_the_role_of_the_remote_address() {
+    let rt = Runtime::new().unwrap();
+    let addr = SocketAddr::from_str("127.0.0.1:40009").unwrap();
+
+    rt.block_on(async {
+        let server = spawn_tcp_server_task_with_authz(
+            1,
+            addr,
+            ServerHandlerMap::single(UnitId::new(1), Handler::new().wrap()),
+            ReadOnlyAuthorizationHandler::create(),
+            Arc::new(|ip: std::net::IpAddr| ip.is_loopback().then(|| "reader".to_string())),
+            AddressFilter::Any,
+            DecodeLevel::default(),
+        )
+        .await
+        .unwrap();
+
+        let mut channel = spawn_tcp_client_task(
+            HostAddr::ip(addr.ip(), addr.port()),
+            10,
+            default_retry_strategy(),
+            DecodeLevel::default(),
+            None,
+        );
+        channel.enable().await.unwrap();
+
+        let params = RequestParam::new(UnitId::new(1), Duration::from_secs(1));
+        assert_eq!(
+            channel
+                .read_coils(params, AddressRange::try_from(0, 1).unwrap())
+                .await,
+            Ok(vec![Indexed::new(0, false)])
+        );
+        assert_eq!(
+            channel
+                .write_single_coil(params, Indexed::new(0, true))
+                .await,
+            Err(RequestError::Exception(ExceptionCode::IllegalFunction))
+        );
+
+        let sessions = server.sessions();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].role.as_deref(), Some("reader"));
+        assert_eq!(sessions[0].counters.rejected, 1);
+    });
+}
+
+/// Accepts writes to holding registers but limits the stored values to 100
+struct ClampingHandler {
+    holding_registers: [u16; 10],
+}
+
+impl ClampingHandler {
+    const MAX: u16 = 100;
+
+    fn store(&mut self, value: Indexed<u16>) -> Result<(), ExceptionCode> {
+        match self.holding_registers.get_mut(value.index as usize) {
+            Some(x) => {
+                *x = value.value.min(Self::MAX);
+                Ok(())
+            }
+            None => Err(ExceptionCode::IllegalDataAddress),
+        }
+    }
+}
+
+impl RequestHandler for ClampingHandler {
+    fn read_holding_register(&self, address: u16) -> Result<u16, ExceptionCode> {
+        match self.holding_registers.get(address as usize) {
+            Some(x) => Ok(*x),
+            None => Err(ExceptionCode::IllegalDataAddress),
+        }
+    }
+
+    fn write_single_register(&mut self, value: Indexed<u16>) -> Result<(), ExceptionCode> {
+        self.store(value)
+    }
+
+    fn write_multiple_registers(&mut self, values: WriteRegisters) -> Result<(), ExceptionCode> {
+        for x in values.iterator {
+            self.store(x)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn verified_writes_detect_values_changed_by_the_server() {
+    let rt = Runtime::new().unwrap();
+    let addr = SocketAddr::from_str("127.0.0.1:40010").unwrap();
+
+    rt.block_on(async {
+        let _server = spawn_tcp_server_task(
+            1,
+            addr,
+            ServerHandlerMap::single(
+                UnitId::new(1),
+                ClampingHandler {
+                    holding_registers: [0; 10],
+                }
+                .wrap(),
+            ),
+            AddressFilter::Any,
+            DecodeLevel::default(),
+        )
+        .await
+        .unwrap();
+
+        let mut channel = spawn_tcp_client_task(
+            HostAddr::ip(addr.ip(), addr.port()),
+            10,
+            default_retry_strategy(),
+            DecodeLevel::default(),
+            None,
+        );
+        channel.enable().await.unwrap();
+
+        let params = RequestParam::new(UnitId::new(1), Duration::from_secs(1));
+        assert_eq!(
+            channel
+                .write_single_register_verified(params, Indexed::new(0, 50))
+                .await,
+            Ok(Indexed::new(0, 50))
+        );
+        assert_eq!(
+            channel
+                .write_single_register_verified(params, Indexed::new(1, 500))
+                .await,
+            Err(VerifyError::Mismatch(vec![1]))
+        );
+        assert_eq!(
+            channel
+                .write_multiple_registers_verified(
+                    params,
+                    WriteMultiple::from(2, vec![10, 200, 30, 400]).unwrap()
+                )
+                .await,
+            Err(VerifyError::Mismatch(vec![3, 5]))
+        );
+        // the server did perform the writes
+        assert_eq!(
+            channel
+                .read_holding_registers(params, AddressRange::try_from(0, 6).unwrap())
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|x| x.value)
+                .collect::<Vec<u16>>(),
+            vec![50, 100, 10, 100, 30, 100]
+        );
+    });
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Level {
+    Physical,
+    Frame,
+    Pdu,
+}
+
+#[derive(Clone, Debug)]
+struct Traffic {
+    level: Level,
+    direction: Direction,
+    session: u64,
+    unit_id: Option<UnitId>,
+    function: Option<u8>,
+    bytes: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Recorder {
+    events: std::sync::Mutex<Vec<Traffic>>,
+}
+
+impl Recorder {
+    fn record(&self, level: Level, event: TrafficEvent) {
+        self.events.lock().unwrap().push(Traffic {
+            level,
+            direction: event.direction,
+            session: event.session,
+            unit_id: event.unit_id,
+            function: event.function,
+            bytes: event.bytes.to_vec(),
+        });
+    }
+
+    /// recorded traffic with consecutive physical reads merged, as a frame may be read in pieces
+    fn take(&self) -> Vec<Traffic> {
+        let mut merged: Vec<Traffic> = Vec::new();
+        for event in self.events.lock().unwrap().drain(..) {
+            match merged.last_mut() {
+                Some(last)
+                    if event.level == Level::Physical
+                        && last.level == Level::Physical
+                        && last.direction == event.direction =>
+                {
+                    last.bytes.extend(event.bytes)
+                }
+                _ => merged.push(event),
+            }
+        }
+        merged
+    }
+}
+
+impl TrafficObserver for Recorder {
+    fn on_physical(&self, event: TrafficEvent) {
+        self.record(Level::Physical, event);
+    }
+
+    fn on_frame(&self, event: TrafficEvent) {
+        self.record(Level::Frame, event);
+    }
+
+    fn on_pdu(&self, event: TrafficEvent) {
+        self.record(Level::Pdu, event);
+    }
+}
+
+/// check the traffic of a single MBAP request or response, in the order it was observed
+fn assert_traffic(events: &[Traffic], direction: Direction, session: u64, pdu: &[u8]) -> Vec<u8> {
+    let frame = &events
+        .iter()
+        .find(|x| x.level == Level::Frame)
+        .unwrap()
+        .bytes;
+    // the length field covers the unit id and the PDU
+    assert_eq!(&frame[4..6], &(pdu.len() as u16 + 1).to_be_bytes());
+    assert_eq!(frame[6], 1);
+    assert_eq!(&frame[7..], pdu);
+
+    let levels: Vec<Level> = events.iter().map(|x| x.level).collect();
+    match direction {
+        // frames are formatted before they are written
+        Direction::Tx => assert_eq!(levels, [Level::Frame, Level::Pdu, Level::Physical]),
+        // and read before they are parsed
+        Direction::Rx => assert_eq!(levels, [Level::Physical, Level::Frame, Level::Pdu]),
+    }
+
+    for event in events {
+        assert_eq!(event.direction, direction);
+        assert_eq!(event.session, session);
+        match event.level {
+            Level::Physical => {
+                assert_eq!(event.unit_id, None);
+                assert_eq!(event.function, None);
+                assert_eq!(&event.bytes, frame);
+            }
+            Level::Frame => {
+                assert_eq!(event.unit_id, Some(UnitId::new(1)));
+                assert_eq!(event.function, Some(pdu[0]));
+            }
+            Level::Pdu => {
+                assert_eq!(event.unit_id, Some(UnitId::new(1)));
+                assert_eq!(event.function, Some(pdu[0]));
+                assert_eq!(event.bytes, pdu);
+            }
+        }
+    }
+
+    frame.clone()
+}
+
+#[test]
+fn observers_receive_the_traffic_of_clients_and_servers() {
+    let rt = Runtime::new().unwrap();
+    let addr = SocketAddr::from_str("127.0.0.1:40011").unwrap();
+
+    rt.block_on(async {
+        let mut handler = Handler::new();
+        handler.holding_registers[0] = 0xCAFE;
+        let mut server = spawn_tcp_server_task(
+            1,
+            addr,
+            ServerHandlerMap::single(UnitId::new(1), handler.wrap()),
+            AddressFilter::Any,
+            DecodeLevel::default(),
+        )
+        .await
+        .unwrap();
+        let server_traffic = Arc::new(Recorder::default());
+        server
+            .set_traffic_observer(Some(server_traffic.clone()))
+            .await
+            .unwrap();
+
+        let mut channel = spawn_tcp_client_task(
+            HostAddr::ip(addr.ip(), addr.port()),
+            10,
+            default_retry_strategy(),
+            DecodeLevel::default(),
+            None,
+        );
+        let client_traffic = Arc::new(Recorder::default());
+        channel
+            .set_traffic_observer(Some(client_traffic.clone()))
+            .await
+            .unwrap();
+        channel.enable().await.unwrap();
+
+        let values = channel
+            .read_holding_registers(
+                RequestParam::new(UnitId::new(1), Duration::from_secs(1)),
+                AddressRange::try_from(0, 1).unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(values, vec![Indexed::new(0, 0xCAFE)]);
+
+        let request = [0x03, 0x00, 0x00, 0x00, 0x01];
+        let response = [0x03, 0x02, 0xCA, 0xFE];
+
+        // the response is observed by the client once the request completes
+        let client = client_traffic.take();
+        assert_eq!(client.len(), 6);
+        assert_eq!(client[0].session, 1);
+        let sent = assert_traffic(&client[..3], Direction::Tx, 1, &request);
+        let received = assert_traffic(&client[3..], Direction::Rx, 1, &response);
+
+        // the server writes the response after observing it
+        let session = server.sessions()[0].id;
+        let server = server_traffic.take();
+        assert_eq!(server.len(), 6);
+        assert_eq!(
+            assert_traffic(&server[..3], Direction::Rx, session, &request),
+            sent
+        );
+        assert_eq!(
+            assert_traffic(&server[3..], Direction::Tx, session, &response),
+            received
+        );
+    });
+}